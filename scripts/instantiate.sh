@@ -18,7 +18,9 @@ INIT='{
         "base_factor": "2",
         "limit_factor": "1.5",
         "full_range_weight": "0.55"
-    }
+    },
+    "protocol": "'$WALLET'",
+    "vault_creation_costs": []
 }'
 
 echo "Instantiating from $WALLET"
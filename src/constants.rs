@@ -4,13 +4,38 @@ pub const MIN_TICK: i32 = -108_000_000;
 pub const MAX_TICK: i32 = 342_000_000;
 pub const MIN_LIQUIDITY: Uint128 = Uint128::new(1000);
 pub const TWAP_SECONDS: u64 = 60;
+pub const DEFAULT_TWAP_MAX_DEVIATION: Decimal = Decimal::percent(1);
+/// Osmosis prunes twap records older than 48 hours.
+pub const MAX_TWAP_SECONDS: u64 = 48 * 60 * 60;
+/// Conditions for anyone to rebalance once the vault rebalancer is inactive.
+pub const FALLBACK_PRICE_FACTOR_BEFORE_REBALANCE: Decimal = Decimal::permille(1050);
+pub const FALLBACK_SECONDS_BEFORE_REBALANCE: u64 = 60 * 60;
+pub const SECONDS_IN_A_DAY: u64 = 24 * 60 * 60;
+pub const SECONDS_IN_A_YEAR: u64 = 365 * SECONDS_IN_A_DAY;
+/// Delay before looser rebalance limits take effect, so depositors can exit first.
+pub const REBALANCE_LIMITS_TIMELOCK_SECONDS: u64 = 2 * SECONDS_IN_A_DAY;
+/// Longest deposited shares can be locked for, so holders can always exit eventually.
+pub const MAX_WITHDRAWAL_COOLDOWN_SECONDS: u64 = 7 * SECONDS_IN_A_DAY;
+pub const DEFAULT_QUERY_LIMIT: u32 = 10;
+pub const MAX_QUERY_LIMIT: u32 = 30;
 pub const POSITION_CREATION_SLIPPAGE: Decimal = Decimal::permille(999);
 
-pub static PROTOCOL_ADDR: &str = "osmo1a8gd76fw6umx652v7cs73vnge2zju8s8hcm86t";
 pub const DEFAULT_PROTOCOL_FEE: Decimal = Decimal::permille(50);
 pub const MAX_PROTOCOL_FEE: Decimal = Decimal::permille(100);
+/// Admin fee increases are capped to this much per notice period.
+pub const MAX_ADMIN_FEE_INCREASE: Decimal = Decimal::percent(1);
+/// How long an admin fee increase stays pending, as announced by the `AdminFee`
+/// query, before it applies to the spread fees collected.
+pub const ADMIN_FEE_INCREASE_NOTICE_SECONDS: u64 = 7 * SECONDS_IN_A_DAY;
+pub const MAX_PERFORMANCE_FEE: Decimal = Decimal::permille(300);
+pub const MAX_ENTRY_EXIT_FEE: Decimal = Decimal::permille(20);
 /// USDC denom for mainnet.
-pub const VAULT_CREATION_COST_DENOM: &str = "ibc/498A0751C798A0D9A389AA3691123DADA57DAA4FE165D5C75894505B876BA6E4";
-/// 20 USDC max vault creation cost. Its only proper as USDC has 6 decimals.
-pub const MAX_VAULT_CREATION_COST: Uint128 = Uint128::new(20_000_000);
-pub const DEFAULT_VAULT_CREATION_COST: Uint128 = Uint128::new(5_000_000);
+pub const MAINNET_USDC_DENOM: &str = "ibc/498A0751C798A0D9A389AA3691123DADA57DAA4FE165D5C75894505B876BA6E4";
+/// Max vault creation cost of each denom, in its atomic units, as `(denom, max)`.
+/// Vault creation costs cant be paid in any other denom.
+pub const MAX_VAULT_CREATION_COSTS: &[(&str, Uint128)] = &[
+    // 20 USDC, as it has 6 decimals.
+    (MAINNET_USDC_DENOM, Uint128::new(20_000_000)),
+    // 100 OSMO, as it has 6 decimals.
+    ("uosmo", Uint128::new(100_000_000)),
+];
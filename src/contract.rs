@@ -10,10 +10,16 @@ use cw20_base::contract::{
     execute_burn, execute_send, execute_transfer, query_balance, query_token_info,
 };
 use cw20_base::state::{MinterData, TokenInfo, TOKEN_INFO};
-use osmosis_std::types::osmosis::concentratedliquidity::v1beta1::MsgCreatePositionResponse;
+use osmosis_std::types::osmosis::{
+    concentratedliquidity::v1beta1::MsgCreatePositionResponse,
+    poolmanager::v1beta1::MsgSwapExactAmountInResponse,
+};
+use std::str::FromStr;
 
-use crate::msg::QueryMsg;
-use crate::state::{FeesInfo, FundsInfo, FEES_INFO, FUNDS_INFO};
+use crate::msg::{QueryMsg, VaultBalancesResponse};
+use crate::state::{
+    FeesInfo, FundsInfo, ProtocolInfo, VaultCreationCost, FEES_INFO, FUNDS_INFO, PROTOCOL_INFO,
+};
 use crate::{do_me, execute, query};
 use crate::{
     error::ContractError,
@@ -23,16 +29,30 @@ use crate::{
 
 #[entry_point]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, ContractError> {
 
-    let vault_info = VaultInfo::new(msg.vault_info.clone(), deps.as_ref())?;
+    let vault_info = VaultInfo::new(msg.vault_info.clone(), deps.as_ref())?
+        .change_access_list(msg.vault_info.access_list.clone(), deps.branch())?;
     let vault_parameters = VaultParameters::new(msg.vault_parameters.clone())?;
     let vault_state = VaultState::default();
-    let fees_info = FeesInfo::new(msg.vault_info.admin_fee, &vault_info, &info)?;
+    let protocol_info = ProtocolInfo::new(&msg.protocol, deps.as_ref())?;
+    let vault_creation_costs = msg.vault_creation_costs
+        .into_iter()
+        .map(VaultCreationCost::new)
+        .collect::<Result<Vec<_>, _>>()?;
+    let fees_info = FeesInfo::new(
+        msg.vault_info.admin_fee,
+        msg.vault_info.keeper_reward,
+        msg.vault_info.performance_fee,
+        msg.vault_info.management_fee,
+        msg.vault_info.entry_exit_fees,
+        VaultCreationCost::paid(&vault_creation_costs, &info)?,
+        &vault_info
+    )?;
     let funds_info = FundsInfo::default();
     let token_info = TokenInfo {
         name: msg.vault_info.vault_name,
@@ -53,35 +73,62 @@ pub fn instantiate(
         VAULT_STATE.save(deps.storage, &vault_state)?;
         FEES_INFO.save(deps.storage, &fees_info)?;
         FUNDS_INFO.save(deps.storage, &funds_info)?;
+        PROTOCOL_INFO.save(deps.storage, &protocol_info)?;
         TOKEN_INFO.save(deps.storage, &token_info)?;
     }.unwrap();
 
+    if let Some(cost) = &fees_info.protocol_vault_creation_cost {
+        execute::record_revenue(
+            &cost.denom,
+            cost.amount,
+            |x, amount| x.creation_costs_collected += amount,
+            deps.storage
+        );
+    }
+
     Ok(Response::new())
 }
 
 #[entry_point]
-pub fn query(deps: Deps, _env: Env, msg: QueryMsg) -> StdResult<Binary> {
+pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     use QueryMsg::*;
     match msg {
         PositionBalancesWithFees { position_type } => 
             to_json_binary( &query::position_balances_with_fees(position_type, deps),),
         CalcSharesAndUsableAmounts { for_amount0, for_amount1 } => 
-            to_json_binary(&query::calc_shares_and_usable_amounts(for_amount0, for_amount1, deps)),
-        VaultBalances {} => to_json_binary(&query::vault_balances(deps)),
+            to_json_binary(&query::calc_shares_and_usable_amounts(for_amount0, for_amount1, deps, &env)),
+        VaultBalances {} => to_json_binary(&query::vault_balances(deps, &env)),
         Balance { address } => to_json_binary(&query_balance(deps, address)?),
         Allowance { owner, spender } => to_json_binary(&query_allowance(deps, owner, spender)?),
         // Invariant: Any state is present after instantiation.
         VaultState {} => to_json_binary(&VAULT_STATE.load(deps.storage).unwrap()),
         VaultParameters {} => to_json_binary(&VAULT_PARAMETERS.load(deps.storage).unwrap()),
-        VaultInfo {} => to_json_binary(&VAULT_INFO.load(deps.storage).unwrap()),
-        FeesInfo {} => to_json_binary(&FEES_INFO.load(deps.storage).unwrap()),
-        TokenInfo {} => to_json_binary(&query_token_info(deps)?)
+        VaultInfo {} => to_json_binary(&query::vault_info(deps, env)),
+        FeesInfo {} => to_json_binary(
+            &FEES_INFO.load(deps.storage).unwrap().apply_pending_admin_fee(env.block.time)
+        ),
+        ProtocolInfo {} => to_json_binary(&PROTOCOL_INFO.load(deps.storage).unwrap()),
+        TokenInfo {} => to_json_binary(&query_token_info(deps)?),
+        DelegatedRebalancers { start_after, limit } =>
+            to_json_binary(&query::delegated_rebalancers(start_after, limit, deps)?),
+        CanRebalance { sender } => to_json_binary(&query::can_rebalance(sender, deps, env)?),
+        SimulateRebalance { price } => to_json_binary(&query::simulate_rebalance(price, deps, env)?),
+        RebalanceHistory { start_after, limit } =>
+            to_json_binary(&query::rebalance_history(start_after, limit, deps)?),
+        PendingRewards { address } => to_json_binary(&query::pending_rewards(address, deps, env)?),
+        ManagementFee {} => to_json_binary(&query::management_fee(deps, env)),
+        SimulateWithdraw { shares } => to_json_binary(&query::simulate_withdraw(shares, deps, &env)),
+        AdminFeesOwed {} => to_json_binary(&query::admin_fees_owed(deps)),
+        AdminFee {} => to_json_binary(&query::admin_fee(deps, env)),
+        RevenueStats {} => to_json_binary(&query::revenue_stats(deps)),
+        IsAllowed { address } => to_json_binary(&query::is_allowed(address, deps)),
+        SharesUnlock { address } => to_json_binary(&query::shares_unlock(address, deps, env))
     }
 }
 
 #[entry_point]
 pub fn execute(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: ExecuteMsg,
@@ -92,22 +139,99 @@ pub fn execute(
         return Err(ContractError::NonPayable(format!("{:?}", msg)))
     }
 
+    execute::apply_pending_admin_fee(deps.branch(), &env);
+
+    let moved_shares = match &msg {
+        Transfer { recipient, amount } | Send { contract: recipient, amount, .. } =>
+            Some((info.sender.as_str(), recipient.as_str(), *amount)),
+        TransferFrom { owner, recipient, amount } |
+        SendFrom { owner, contract: recipient, amount, .. } =>
+            Some((owner.as_str(), recipient.as_str(), *amount)),
+        _ => None
+    };
+
+    if let Some((from, recipient, amount)) = moved_shares {
+        // NOTE: Shares cant be moved out of, or into, the set of allowed holders.
+        if let Some(address) = execute::not_allowed_holder(&[from, recipient], deps.as_ref()) {
+            return Err(ContractError::AddressNotAllowed(address))
+        }
+
+        // NOTE: Otherwise the per address deposit cap could be skipped by moving the shares.
+        if let Some(max) = execute::exceeded_shares_cap(recipient, amount, deps.as_ref()) {
+            return Err(ContractError::MaxSharesPerAddressReached { address: recipient.into(), max })
+        }
+
+        // NOTE: Otherwise the withdrawal cooldown could be skipped by moving the shares.
+        if let Some(unlocks_at) = query::shares_locked_until(from, amount, deps.as_ref(), &env) {
+            return Err(ContractError::SharesLocked {
+                address: from.into(),
+                unlocks_at: unlocks_at.seconds()
+            })
+        }
+    }
+
+    // NOTE: Rewards must be settled before any share balance change.
+    match &msg {
+        Transfer { recipient, .. } | Send { contract: recipient, .. } =>
+            execute::sync_rewards(&[info.sender.as_str(), recipient.as_str()], deps.branch(), &env),
+        TransferFrom { owner, recipient, .. } | SendFrom { owner, contract: recipient, .. } =>
+            execute::sync_rewards(&[owner.as_str(), recipient.as_str()], deps.branch(), &env),
+        Burn { .. } => execute::sync_rewards(&[info.sender.as_str()], deps.branch(), &env),
+        BurnFrom { owner, .. } => execute::sync_rewards(&[owner.as_str()], deps.branch(), &env),
+        _ => {}
+    }
+
     match msg {
         // Core Logic.
         Deposit(deposit_msg) => Ok(execute::deposit(deposit_msg, deps, env, info)?),
         Rebalance {} => Ok(execute::rebalance(deps, env, info)?),
         Withdraw(withdraw_msg) => Ok(execute::withdraw(withdraw_msg, deps, env, info)?),
+        RebalanceLimit {} => Ok(execute::rebalance_limit(deps, env, info)?),
+        Compound {} => Ok(execute::compound(deps, env, info)?),
+        ClaimRewards {} => Ok(execute::claim_rewards(deps, env, info)),
+        HarvestIncentives {} => Ok(execute::harvest_incentives(deps, env, info)?),
 
         // Admin/Protocol operations.
-        WithdrawProtocolFees {} => Ok(execute::withdraw_protocol_fees(deps, info)?),
-        WithdrawAdminFees {} => Ok(execute::withdraw_admin_fees(deps, info)?),
+        WithdrawProtocolFees(withdrawal) =>
+            Ok(execute::withdraw_protocol_fees(withdrawal, deps, env, info)?),
+        WithdrawAdminFees(withdrawal) => Ok(execute::withdraw_admin_fees(withdrawal, deps, env, info)?),
         ProposeNewAdmin { new_admin } => Ok(execute::propose_new_admin(deps, info, new_admin)?),
         AcceptNewAdmin {} => Ok(execute::accept_new_admin(deps, info)?),
         BurnVaultAdmin {} => Ok(execute::burn_vault_admin(deps, info)?),
         ChangeVaultRebalancer(rebalancer) => Ok(execute::change_vault_rebalancer(rebalancer, deps, info)?),
+        AddDelegatedRebalancer { address, permission, min_seconds_between_calls } =>
+            Ok(execute::add_delegated_rebalancer(address, permission, min_seconds_between_calls, deps, info)?),
+        RemoveDelegatedRebalancer { address } => Ok(execute::remove_delegated_rebalancer(address, deps, info)?),
         ChangeVaultParameters(parameters) => Ok(execute::change_vault_parameters(parameters, deps, info)?),
-        ChangeAdminFee { new_admin_fee } => Ok(execute::change_admin_fee(new_admin_fee, deps, info)?),
+        ChangeTwapConfig(twap_config) => Ok(execute::change_twap_config(twap_config, deps, env, info)?),
+        ChangeInactivityTimeout { inactivity_timeout } => 
+            Ok(execute::change_inactivity_timeout(inactivity_timeout, deps, info)?),
+        ChangeRebalanceLimits(limits) => Ok(execute::change_rebalance_limits(limits, deps, env, info)?),
+        ChangeCircuitBreaker(circuit_breaker) => Ok(execute::change_circuit_breaker(circuit_breaker, deps, info)?),
+        ClearCircuitBreaker {} => Ok(execute::clear_circuit_breaker(deps, info)?),
+        ChangeDepositCaps(deposit_caps) => Ok(execute::change_deposit_caps(deposit_caps, deps, info)?),
+        ChangeAccessList(access_list) => Ok(execute::change_access_list(access_list, deps, info)?),
+        ChangeWithdrawalCooldown { withdrawal_cooldown } =>
+            Ok(execute::change_withdrawal_cooldown(withdrawal_cooldown, deps, info)?),
+        ChangeRewardDenom { denom, distribute } =>
+            Ok(execute::change_reward_denom(denom, distribute, deps, info)?),
+        ChangeHarvestRoute { denom, route } => Ok(execute::change_harvest_route(denom, route, deps, info)?),
+        CancelRebalance {} => Ok(execute::cancel_rebalance(deps, info)?),
+        ChangeAdminFee { new_admin_fee } => Ok(execute::change_admin_fee(new_admin_fee, deps, env, info)?),
+        ChangeKeeperReward(keeper_reward) => Ok(execute::change_keeper_reward(keeper_reward, deps, info)?),
+        ChangePerformanceFee(performance_fee) =>
+            Ok(execute::change_performance_fee(performance_fee, deps, info)?),
+        ChangeManagementFee { new_management_fee } =>
+            Ok(execute::change_management_fee(new_management_fee, deps, env, info)?),
+        ChangeEntryExitFees(entry_exit_fees) =>
+            Ok(execute::change_entry_exit_fees(entry_exit_fees, deps, info)?),
+        ChangeAdminFeeBeneficiaries { beneficiaries } =>
+            Ok(execute::change_admin_fee_beneficiaries(beneficiaries, deps, info)?),
         ChangeProtocolFee { new_protocol_fee } => Ok(execute::change_protocol_fee(new_protocol_fee, deps, info)?),
+        ProposeNewProtocol { new_protocol } => Ok(execute::propose_new_protocol(deps, info, new_protocol)?),
+        AcceptNewProtocol {} => Ok(execute::accept_new_protocol(deps, info)?),
+        ChangeProtocolFeeRecipient { recipient } =>
+            Ok(execute::change_protocol_fee_recipient(deps, info, recipient)?),
 
         // Cw20 Realization.
         Transfer { recipient, amount } => Ok(execute_transfer(deps, env, info, recipient, amount)?),
@@ -122,8 +246,22 @@ pub fn execute(
 }
 
 #[entry_point]
-pub fn reply(deps: DepsMut, _env: Env, msg: Reply) -> Result<Response, ContractError> {
-    // Invariant: We only use position creation submessages.
+pub fn reply(mut deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
+    // NOTE: Ids 3 and 4 are harvest swaps into token0 and token1 respectively.
+    if let 3 | 4 = msg.id {
+        // Invariant: We only use swap submessages for harvesting.
+        let swap: MsgSwapExactAmountInResponse = msg.result.try_into().unwrap();
+        // Invariant: Swaps always return a valid amount.
+        let amount = Uint128::from_str(&swap.token_out_amount).unwrap();
+        execute::credit_harvest(amount, msg.id == 3, deps.branch());
+
+        let VaultBalancesResponse { bal0, bal1, .. } = query::vault_balances(deps.as_ref(), &env);
+        // Invariant: Any state will always be present after instantiation.
+        let price = VAULT_INFO.load(deps.storage).unwrap().pool_id.price(&deps.querier);
+        return Ok(execute::charge_performance_fee(bal0, bal1, &price, deps.branch(), &env))
+    }
+
+    // Invariant: We only use position creation submessages otherwise.
     let new_position: MsgCreatePositionResponse = msg.result.try_into().unwrap();
     // Invariant: Any state will always be present after instantiation.
     let mut vault_state = VAULT_STATE.load(deps.storage).unwrap();
@@ -148,18 +286,31 @@ pub mod test {
 
     use crate::{
         assert_approx_eq,
-        constants::{MIN_LIQUIDITY, PROTOCOL_ADDR},
+        constants::{
+            ADMIN_FEE_INCREASE_NOTICE_SECONDS, MAX_WITHDRAWAL_COOLDOWN_SECONDS, MIN_LIQUIDITY,
+            REBALANCE_LIMITS_TIMELOCK_SECONDS, SECONDS_IN_A_YEAR,
+        },
         mock::mock::{
-            deposit_msg, rebalancer_anyone, vault_params, PoolMockup, VaultMockup, OSMO_DENOM,
-            USDC_DENOM,
+            deposit_msg, gradual_vault_params, rebalancer_anyone, rebalancer_anyone_with_policy,
+            vault_params, PoolMockup, VaultMockup, OSMO_DENOM, REWARD_DENOM, USDC_DENOM,
+            VAULT_CREATION_COST,
+        },
+        msg::{
+            AccessListInstantiateMsg, CantRebalanceReason, CircuitBreakerInstantiateMsg,
+            DepositCapsInstantiateMsg, DepositMsg, EntryExitFeesInstantiateMsg, FeesWithdrawalMsg,
+            HarvestHop, HarvestRouteInstantiateMsg, KeeperRewardInstantiateMsg,
+            PerformanceFeeInstantiateMsg, RebalancePolicyInstantiateMsg, TwapConfigInstantiateMsg,
+            VaultRebalancerInstantiateMsg, WithdrawMsg,
         },
-        msg::{DepositMsg, WithdrawMsg},
-        state::PositionType,
-        utils::price_function_inv,
+        state::{
+            FeeRecipient, PositionSnapshot, PositionType, RebalancerPermission, ReferenceToken,
+            TwapFallback, VaultCreationCost,
+        },
+        utils::{price_function, price_function_inv},
     };
 
     use super::*;
-    use cosmwasm_std::{coin, testing::{mock_dependencies, mock_env}, Addr, Api, Coin, Decimal};
+    use cosmwasm_std::{coin, testing::mock_dependencies, Addr, Coin, Decimal, Decimal256};
     use osmosis_test_tube::Account;
 
     #[test]
@@ -189,6 +340,17 @@ pub mod test {
         }
     }
 
+    #[test]
+    fn price_function_test() {
+        let ticks = &[-9000200, -500100, -100, 0, 100, 8999900, 9000200];
+
+        for tick in ticks {
+            let price = price_function(*tick);
+            assert_eq!(price_function_inv(&Decimal::try_from(price).unwrap()), *tick);
+        }
+        assert_eq!(price_function(0), Decimal256::one());
+    }
+
     #[test]
     fn normal_rebalances() {
         let pool_mockup = PoolMockup::new(100_000, 200_000);
@@ -212,6 +374,63 @@ pub mod test {
         assert_approx_eq!(full_range_position.bal1, Uint128::new(605 * 2), Uint128::new(5));
     }
 
+    #[test]
+    fn simulate_rebalance() {
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+
+        vault_mockup.deposit(1_000, 1_501, &pool_mockup.user1).unwrap();
+        let simulation = vault_mockup.simulate_rebalance_query(None).unwrap();
+        assert!(simulation.gradual_step.is_none());
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+
+        let state = vault_mockup.vault_state_query();
+        for planned in simulation.positions {
+            let position_id = state.from_position_type(planned.position_type).unwrap();
+            let position = pool_mockup.position_query(position_id).unwrap().position.unwrap();
+            assert_eq!((planned.lower_tick, planned.upper_tick), (position.lower_tick, position.upper_tick));
+        }
+
+        let simulation = vault_mockup.simulate_rebalance_query(Some(Decimal::percent(150))).unwrap();
+        assert_eq!(simulation.price, Decimal::percent(150));
+        assert!(!simulation.positions.is_empty());
+
+        // Prices outside of the pool tick range cant be simulated.
+        assert!(vault_mockup.simulate_rebalance_query(Some(Decimal::zero())).is_err());
+        assert!(vault_mockup.simulate_rebalance_query(Some(Decimal::raw(1))).is_err());
+    }
+
+    #[test]
+    fn rebalance_history() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let admin = &pool_mockup.deployer;
+
+        vault_mockup.deposit(50_000, 50_000, &pool_mockup.user1).unwrap();
+        vault_mockup.rebalance(admin).unwrap();
+        pool_mockup.swap_usdc_for_osmo(&pool_mockup.user1, 10_000).unwrap();
+        pool_mockup.app.increase_time(60);
+        vault_mockup.rebalance(admin).unwrap();
+
+        let history = vault_mockup.rebalance_history_query(None).rebalances;
+        assert_eq!(history.len(), 2);
+        let (first, second) = (&history[0].1, &history[1].1);
+        assert!(first.closed_positions.is_empty());
+        assert!(!first.opened_positions.is_empty());
+        let ranges = |positions: &Vec<PositionSnapshot>| positions
+            .iter()
+            .map(|x| (x.position_type.clone(), x.lower_tick, x.upper_tick))
+            .collect::<Vec<_>>();
+        assert_eq!(ranges(&first.opened_positions), ranges(&second.closed_positions));
+        assert_eq!(second.caller.as_str(), admin.address());
+        assert!(second.price < first.price);
+        assert!(!second.share_price.is_zero());
+
+        let history = vault_mockup.rebalance_history_query(Some(0)).rebalances;
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].0, 1);
+    }
+
     #[test]
     fn normal_rebalance_dual() {
         let pool_mockup = PoolMockup::new(100_000, 200_000);
@@ -331,8 +550,10 @@ pub mod test {
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
 
         let usdc_got = pool_mockup.swap_osmo_for_usdc(&pool_mockup.user1, vault_y/10).unwrap();
+        pool_mockup.app.increase_time(60);
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
         pool_mockup.swap_usdc_for_osmo(&pool_mockup.user1, usdc_got.into()).unwrap();
+        pool_mockup.app.increase_time(60);
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
     }
 
@@ -476,6 +697,7 @@ pub mod test {
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
 
         pool_mockup.swap_osmo_for_usdc(&pool_mockup.user2, 20_000).unwrap();
+        pool_mockup.app.increase_time(60);
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
 
         let fees = vault_mockup.vault_fees_query();
@@ -499,6 +721,110 @@ pub mod test {
         // vault_mockup.protocol_withdraw().unwrap();
     }
 
+    #[test]
+    fn revenue_stats() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let stats = |denom: &str| vault_mockup
+            .revenue_stats_query()
+            .stats
+            .into_iter()
+            .find(|(x, _)| x == denom)
+            .map(|(_, x)| x)
+            .unwrap_or_default();
+
+        assert_eq!(stats(USDC_DENOM).creation_costs_collected.u128(), VAULT_CREATION_COST);
+
+        vault_mockup.deposit(100_000, 50_000, &pool_mockup.user1).unwrap();
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+        pool_mockup.swap_osmo_for_usdc(&pool_mockup.user2, 20_000).unwrap();
+        pool_mockup.app.increase_time(60);
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+
+        let fees = vault_mockup.vault_fees_query();
+        let osmo_stats = stats(OSMO_DENOM);
+        assert!(osmo_stats.spread_fees > fees.protocol_tokens1_owned + fees.admin_tokens1_owned);
+        assert_eq!(osmo_stats.protocol_fees_accrued, fees.protocol_tokens1_owned);
+        assert_eq!(osmo_stats.admin_fees_accrued, fees.admin_tokens1_owned);
+        assert!(osmo_stats.admin_fees_paid.is_zero());
+
+        vault_mockup.admin_withdraw(&pool_mockup.deployer).unwrap();
+        let osmo_stats = stats(OSMO_DENOM);
+        assert_eq!(osmo_stats.admin_fees_paid, fees.admin_tokens1_owned);
+        assert_eq!(osmo_stats.admin_fees_accrued, fees.admin_tokens1_owned);
+        assert!(vault_mockup.vault_fees_query().admin_tokens1_owned.is_zero());
+
+        // Withdrawals also commit the fees accrued since the last rebalance.
+        pool_mockup.swap_osmo_for_usdc(&pool_mockup.user2, 20_000).unwrap();
+        let unclaimed = vault_mockup.vault_balances_query();
+        assert!(!unclaimed.spread_fees1.is_zero());
+        let shares = vault_mockup.shares_query(&pool_mockup.user1.address());
+        vault_mockup.withdraw(shares.multiply_ratio(1u128, 2u128), &pool_mockup.user1).unwrap();
+
+        let new_osmo_stats = stats(OSMO_DENOM);
+        assert_eq!(new_osmo_stats.spread_fees, osmo_stats.spread_fees + unclaimed.spread_fees1);
+        assert_eq!(
+            new_osmo_stats.admin_fees_accrued,
+            osmo_stats.admin_fees_accrued + unclaimed.admin_unclaimed_fees1
+        );
+
+        assert!(stats(USDC_DENOM).creation_costs_paid.is_zero());
+        vault_mockup.protocol_withdraw(&pool_mockup.protocol).unwrap();
+        assert_eq!(stats(USDC_DENOM).creation_costs_paid.u128(), VAULT_CREATION_COST);
+        // Creation tokens are only paid once.
+        vault_mockup.protocol_withdraw(&pool_mockup.protocol).unwrap();
+        let usdc_stats = stats(USDC_DENOM);
+        assert_eq!(usdc_stats.creation_costs_paid, usdc_stats.creation_costs_collected);
+    }
+
+    #[test]
+    fn partial_and_redirected_fees_withdrawals() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1) = (&pool_mockup.deployer, &pool_mockup.user1);
+        let admin_fees_paid = || vault_mockup
+            .revenue_stats_query()
+            .stats
+            .into_iter()
+            .find(|(denom, _)| denom == OSMO_DENOM)
+            .map_or(Uint128::zero(), |(_, x)| x.admin_fees_paid);
+        vault_mockup.deposit(100_000, 50_000, user1).unwrap();
+        vault_mockup.rebalance(admin).unwrap();
+        pool_mockup.swap_osmo_for_usdc(&pool_mockup.user2, 20_000).unwrap();
+
+        // NOTE: The fees are still in the positions, so only collecting them pays anything.
+        assert!(vault_mockup.vault_fees_query().admin_tokens1_owned.is_zero());
+        let osmo_before = pool_mockup.osmo_balance_query(&user1.address());
+        vault_mockup.admin_withdraw_with(admin, FeesWithdrawalMsg {
+            recipient: Some(user1.address()),
+            collect_spread_rewards: true,
+            ..Default::default()
+        }).unwrap();
+        let osmo_got = pool_mockup.osmo_balance_query(&user1.address()) - osmo_before;
+        assert!(!osmo_got.is_zero());
+        assert_eq!(admin_fees_paid(), osmo_got);
+        assert!(vault_mockup.vault_fees_query().admin_tokens1_owned.is_zero());
+
+        pool_mockup.swap_osmo_for_usdc(&pool_mockup.user2, 20_000).unwrap();
+        pool_mockup.app.increase_time(60);
+        vault_mockup.rebalance(admin).unwrap();
+        let owned = vault_mockup.vault_fees_query().admin_tokens1_owned;
+        let partial = FeesWithdrawalMsg { amount0: Some(Uint128::zero()), ..Default::default() };
+
+        assert!(vault_mockup.admin_withdraw_with(admin, FeesWithdrawalMsg {
+            amount1: Some(owned + Uint128::one()),
+            ..partial.clone()
+        }).is_err());
+        vault_mockup.admin_withdraw_with(admin, FeesWithdrawalMsg {
+            amount1: Some(owned / Uint128::new(2)),
+            ..partial
+        }).unwrap();
+        assert_eq!(
+            vault_mockup.vault_fees_query().admin_tokens1_owned,
+            owned - owned / Uint128::new(2)
+        );
+    }
+
     #[test]
     fn fees_withdrawals_on_withdrawal() {
         let pool_mockup = PoolMockup::new(200_000, 100_000);
@@ -673,6 +999,165 @@ pub mod test {
         vault_mockup.rebalance(&pool_mockup.user1).unwrap();
     }
 
+    #[test]
+    fn public_rebalancing_with_policy() {
+        use RebalancePolicyInstantiateMsg::*;
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new_with_rebalancer(
+            &pool_mockup,
+            vault_params("2", "1.45", "0.55"),
+            rebalancer_anyone_with_policy(Or(vec![
+                TimePassed { seconds: 3600 },
+                IdleBalanceAbove { share: Decimal::percent(50).atomics() }
+            ]))
+        );
+        vault_mockup.deposit(10_000, 10_000, &pool_mockup.user1).unwrap();
+        vault_mockup.rebalance(&pool_mockup.user2).unwrap();
+        pool_mockup.app.increase_time(1);
+        let reason = vault_mockup.rebalance(&pool_mockup.user2).unwrap_err().to_string();
+        assert!(reason.contains("none of (") && reason.contains("; "));
+        assert!(!reason.contains(" and "));
+
+        // Idle balances are now most of the vault value.
+        vault_mockup.deposit(30_000, 30_000, &pool_mockup.user1).unwrap();
+        vault_mockup.rebalance(&pool_mockup.user2).unwrap();
+        pool_mockup.app.increase_time(1);
+        assert!(vault_mockup.rebalance(&pool_mockup.user2).is_err());
+        pool_mockup.app.increase_time(3600);
+        vault_mockup.rebalance(&pool_mockup.user2).unwrap();
+
+        assert!(vault_mockup.change_vault_rebalancer(
+            &pool_mockup.deployer, rebalancer_anyone_with_policy(And(vec![]))
+        ).is_err());
+    }
+
+    #[test]
+    fn public_rebalancing_pays_keeper_reward() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new_with_rebalancer(
+            &pool_mockup,
+            vault_params("2", "1.45", "0.55"),
+            rebalancer_anyone("1", 0)
+        );
+        let keeper_reward = |share| KeeperRewardInstantiateMsg {
+            share,
+            max_amount0: Some(Uint128::one()),
+            max_amount1: None
+        };
+
+        assert!(vault_mockup.change_keeper_reward(
+            &pool_mockup.deployer, Some(keeper_reward(Decimal::percent(50).atomics()))
+        ).is_err());
+        assert!(vault_mockup.change_keeper_reward(
+            &pool_mockup.user1, Some(keeper_reward(Decimal::percent(10).atomics()))
+        ).is_err());
+        vault_mockup.change_keeper_reward(
+            &pool_mockup.deployer, Some(keeper_reward(Decimal::percent(10).atomics()))
+        ).unwrap();
+
+        vault_mockup.deposit(50_000, 50_000, &pool_mockup.user1).unwrap();
+        vault_mockup.rebalance(&pool_mockup.user2).unwrap();
+        pool_mockup.swap_usdc_for_osmo(&pool_mockup.user1, 20_000).unwrap();
+        pool_mockup.app.increase_time(60);
+
+        let keeper = pool_mockup.user2.address();
+        let usdc_before = pool_mockup.usdc_balance_query(&keeper);
+        vault_mockup.rebalance(&pool_mockup.user2).unwrap();
+        let usdc_after = pool_mockup.usdc_balance_query(&keeper);
+
+        // The reward is capped per call, the rest is kept for the next keepers.
+        assert_eq!(usdc_after - usdc_before, Uint128::one());
+        assert!(!vault_mockup.vault_fees_query().keeper_tokens0_owned.is_zero());
+
+        // The rewards left arent paid to privileged rebalancers.
+        vault_mockup.change_vault_rebalancer(
+            &pool_mockup.deployer, VaultRebalancerInstantiateMsg::Admin {}
+        ).unwrap();
+        let admin = pool_mockup.deployer.address();
+        let usdc_before = pool_mockup.usdc_balance_query(&admin);
+        pool_mockup.app.increase_time(60);
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+        assert_eq!(pool_mockup.usdc_balance_query(&admin), usdc_before);
+    }
+
+    #[test]
+    fn can_rebalance_query() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new_with_rebalancer(
+            &pool_mockup,
+            vault_params("2", "1.45", "0.55"),
+            rebalancer_anyone("1.1", 60)
+        );
+        let keeper = &pool_mockup.user2;
+
+        let res = vault_mockup.can_rebalance_query(keeper);
+        assert_eq!(res.reason, Some(CantRebalanceReason::NothingToRebalance {}));
+
+        vault_mockup.deposit(50_000, 50_000, &pool_mockup.user1).unwrap();
+        assert_eq!(vault_mockup.can_rebalance_query(keeper).reason, None);
+        vault_mockup.rebalance(keeper).unwrap();
+
+        pool_mockup.app.increase_time(5);
+        let res = vault_mockup.can_rebalance_query(keeper);
+        assert!(matches!(res.reason, Some(CantRebalanceReason::NotEnoughTimePassed { .. })));
+        assert!(res.next_allowed_time.is_some());
+        assert!(vault_mockup.rebalance(keeper).is_err());
+
+        pool_mockup.app.increase_time(60);
+        let res = vault_mockup.can_rebalance_query(keeper);
+        assert!(matches!(res.reason, Some(CantRebalanceReason::PriceHasntMovedEnough { .. })));
+        let (lower_bound, upper_bound) = res.next_allowed_price_range.unwrap();
+        assert!((lower_bound..=upper_bound).contains(&res.price));
+    }
+
+    #[test]
+    fn admin_rebalancing_with_twap_config() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let twap_config = |seconds| TwapConfigInstantiateMsg {
+            seconds,
+            max_deviation: Decimal::percent(1).atomics(),
+            on_failure: TwapFallback::Reject {}
+        };
+
+        assert!(vault_mockup.change_twap_config(&pool_mockup.user1, Some(twap_config(60))).is_err());
+        assert!(vault_mockup.change_twap_config(&pool_mockup.deployer, Some(twap_config(0))).is_err());
+        vault_mockup.change_twap_config(&pool_mockup.deployer, Some(twap_config(60))).unwrap();
+        assert!(vault_mockup.vault_info_query().info.pending_twap_config.is_none());
+
+        vault_mockup.deposit(10_000, 10_000, &pool_mockup.user1).unwrap();
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+        pool_mockup.swap_osmo_for_usdc(&pool_mockup.user2, 10_000).unwrap();
+        assert!(vault_mockup.rebalance(&pool_mockup.deployer).is_err());
+        pool_mockup.app.increase_time(60);
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+
+        // The pool has no TWAP this far back.
+        vault_mockup.change_twap_config(&pool_mockup.deployer, Some(twap_config(86_400))).unwrap();
+        pool_mockup.app.increase_time(1);
+        assert!(vault_mockup.rebalance(&pool_mockup.deployer).is_err());
+
+        // Looser configs, like a shorter window, only apply after the timelock.
+        vault_mockup.change_twap_config(&pool_mockup.deployer, None).unwrap();
+        assert!(vault_mockup.vault_info_query().info.pending_twap_config.is_some());
+        assert!(vault_mockup.rebalance(&pool_mockup.deployer).is_err());
+        pool_mockup.app.increase_time(REBALANCE_LIMITS_TIMELOCK_SECONDS);
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+
+        // Without a config, the default TWAP check still applies.
+        pool_mockup.swap_osmo_for_usdc(&pool_mockup.user2, 10_000).unwrap();
+        pool_mockup.app.increase_time(1);
+        assert!(vault_mockup.rebalance(&pool_mockup.deployer).is_err());
+
+        // And a huge max deviation doesnt loosen it right away either.
+        vault_mockup.change_twap_config(&pool_mockup.deployer, Some(TwapConfigInstantiateMsg {
+            seconds: 60,
+            max_deviation: Decimal::percent(50).atomics(),
+            on_failure: TwapFallback::SkipCheck {}
+        })).unwrap();
+        assert!(vault_mockup.rebalance(&pool_mockup.deployer).is_err());
+    }
+
     #[test]
     fn cant_deposit_improper_tokens() {
         let pool_mockup = PoolMockup::new(200_000, 100_000);
@@ -735,6 +1220,7 @@ pub mod test {
         vault_mockup.deposit(60_000, 60_000, &pool_mockup.user1).unwrap();
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
         pool_mockup.swap_osmo_for_usdc(&pool_mockup.user1, 30_000).unwrap();
+        pool_mockup.app.increase_time(60);
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
         vault_mockup.propose_new_admin(&pool_mockup.deployer, Some(&pool_mockup.user1)).unwrap();
 
@@ -824,6 +1310,7 @@ pub mod test {
 
         // NOTE: We subtract 1 for atomic reasons.
         pool_mockup.swap_usdc_for_osmo(&pool_mockup.user2, delta_x.u128() - 1).unwrap();
+        pool_mockup.app.increase_time(60);
         vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
 
         let position_ids = vault_mockup.vault_state_query();
@@ -832,13 +1319,730 @@ pub mod test {
         assert!(position_ids.limit_position_id.is_none());
     }
 
+    #[test]
+    fn gradual_rebalance() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(
+            &pool_mockup, gradual_vault_params("2", "1.45", "0.55", "0.5")
+        );
+        vault_mockup.deposit(10_000, 10_000, &pool_mockup.user1).unwrap();
+        // NOTE: The steps of a gradual rebalance count as a single rebalance.
+        vault_mockup.change_rebalance_limits(&pool_mockup.deployer, 0, Some(3)).unwrap();
+
+        // Without positions, theres no liquidity to move gradually.
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+        let state = vault_mockup.vault_state_query();
+        assert!(state.rebalance_in_progress.is_none());
+        let base_position_id = state.base_position_id;
+
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+        let state = vault_mockup.vault_state_query();
+        assert!(state.rebalance_in_progress.is_some());
+        assert_eq!(state.base_position_id, base_position_id);
+
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+        let state = vault_mockup.vault_state_query();
+        assert!(state.rebalance_in_progress.is_none());
+        assert_ne!(state.base_position_id, base_position_id);
+
+        vault_mockup.rebalance(&pool_mockup.deployer).unwrap();
+        assert!(vault_mockup.cancel_rebalance(&pool_mockup.user1).is_err());
+        vault_mockup.cancel_rebalance(&pool_mockup.deployer).unwrap();
+        assert!(vault_mockup.vault_state_query().rebalance_in_progress.is_none());
+        assert!(vault_mockup.cancel_rebalance(&pool_mockup.deployer).is_err());
+
+        let shares = vault_mockup.shares_query(&pool_mockup.user1.address());
+        vault_mockup.withdraw(shares, &pool_mockup.user1).unwrap();
+    }
+
+    #[test]
+    fn delegated_rebalancers() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (user1, user2) = (&pool_mockup.user1, &pool_mockup.user2);
+
+        assert!(vault_mockup.add_delegated_rebalancer(
+            user1, user1, RebalancerPermission::Full {}, 0
+        ).is_err());
+        vault_mockup.add_delegated_rebalancer(
+            &pool_mockup.deployer, user1, RebalancerPermission::Full {}, 3600
+        ).unwrap();
+        vault_mockup.add_delegated_rebalancer(
+            &pool_mockup.deployer, user2, RebalancerPermission::LimitOnly {}, 0
+        ).unwrap();
+        assert_eq!(vault_mockup.delegated_rebalancers_query().rebalancers.len(), 2);
+
+        vault_mockup.deposit(10_000, 10_000, user1).unwrap();
+        assert_eq!(vault_mockup.can_rebalance_query(user1).reason, None);
+        assert_eq!(
+            vault_mockup.can_rebalance_query(user2).reason,
+            Some(CantRebalanceReason::Unauthorized {})
+        );
+        vault_mockup.rebalance(user1).unwrap();
+        assert!(vault_mockup.rebalance(user1).is_err());
+        assert!(vault_mockup.rebalance(user2).is_err());
+        assert!(vault_mockup.compound(user2).is_err());
+
+        let limit_position_id = vault_mockup.vault_state_query().limit_position_id;
+        vault_mockup.rebalance_limit(user2).unwrap();
+        let state = vault_mockup.vault_state_query();
+        assert!(state.limit_position_id.is_some());
+        assert_ne!(state.limit_position_id, limit_position_id);
+
+        vault_mockup.compound(&pool_mockup.deployer).unwrap();
+        assert!(vault_mockup.burn_vault_admin(&pool_mockup.deployer).is_err());
+
+        vault_mockup.remove_delegated_rebalancer(&pool_mockup.deployer, user2).unwrap();
+        assert!(vault_mockup.remove_delegated_rebalancer(&pool_mockup.deployer, user2).is_err());
+        assert!(vault_mockup.rebalance_limit(user2).is_err());
+        assert_eq!(vault_mockup.delegated_rebalancers_query().rebalancers.len(), 1);
+    }
+
+    #[test]
+    fn inactive_rebalancer_fallback() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user) = (&pool_mockup.deployer, &pool_mockup.user1);
+
+        assert!(vault_mockup.change_inactivity_timeout(user, Some(3600)).is_err());
+        assert!(vault_mockup.change_inactivity_timeout(admin, Some(0)).is_err());
+        vault_mockup.change_inactivity_timeout(admin, Some(3600)).unwrap();
+
+        vault_mockup.deposit(50_000, 50_000, user).unwrap();
+        assert!(vault_mockup.rebalance(user).is_err());
+        vault_mockup.rebalance(admin).unwrap();
+
+        pool_mockup.swap_usdc_for_osmo(user, 20_000).unwrap();
+        pool_mockup.app.increase_time(60);
+        assert!(!vault_mockup.vault_info_query().fallback_rebalancer_active);
+        assert!(vault_mockup.rebalance(user).is_err());
+
+        pool_mockup.app.increase_time(3600);
+        assert!(vault_mockup.vault_info_query().fallback_rebalancer_active);
+        vault_mockup.rebalance(user).unwrap();
+        assert!(vault_mockup.vault_state_query().last_rebalance_by_fallback);
+        assert!(vault_mockup.vault_info_query().fallback_rebalancer_active);
+
+        // Once the admin is back, the fallback is disabled again.
+        pool_mockup.app.increase_time(5);
+        vault_mockup.rebalance(admin).unwrap();
+        assert!(!vault_mockup.vault_state_query().last_rebalance_by_fallback);
+        assert!(!vault_mockup.vault_info_query().fallback_rebalancer_active);
+    }
+
+    #[test]
+    fn privileged_rebalance_limits() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let admin = &pool_mockup.deployer;
+
+        assert!(vault_mockup.change_rebalance_limits(admin, 60, Some(0)).is_err());
+        vault_mockup.change_rebalance_limits(admin, 60, Some(2)).unwrap();
+        assert!(vault_mockup.vault_info_query().info.pending_rebalance_limits.is_none());
+
+        vault_mockup.deposit(50_000, 50_000, &pool_mockup.user1).unwrap();
+        vault_mockup.rebalance(admin).unwrap();
+        pool_mockup.app.increase_time(5);
+        assert!(vault_mockup.rebalance(admin).is_err());
+        pool_mockup.app.increase_time(60);
+        vault_mockup.rebalance(admin).unwrap();
+        pool_mockup.app.increase_time(60);
+        assert!(vault_mockup.rebalance(admin).is_err());
+        assert!(vault_mockup.compound(admin).is_err());
+
+        // Looser limits only apply after the timelock.
+        vault_mockup.change_rebalance_limits(admin, 60, Some(3)).unwrap();
+        assert!(vault_mockup.vault_info_query().info.pending_rebalance_limits.is_some());
+        assert!(vault_mockup.rebalance(admin).is_err());
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user) = (&pool_mockup.deployer, &pool_mockup.user1);
+        let circuit_breaker = |trip, settle| CircuitBreakerInstantiateMsg {
+            twap_seconds: 60,
+            trip_deviation: Decimal::percent(trip).atomics(),
+            settle_deviation: Decimal::percent(settle).atomics()
+        };
+
+        assert!(vault_mockup.change_circuit_breaker(admin, Some(circuit_breaker(1, 5))).is_err());
+        vault_mockup.change_circuit_breaker(admin, Some(circuit_breaker(5, 1))).unwrap();
+        assert!(vault_mockup.clear_circuit_breaker(admin).is_err());
+
+        vault_mockup.deposit(50_000, 50_000, user).unwrap();
+        vault_mockup.rebalance(admin).unwrap();
+        pool_mockup.swap_usdc_for_osmo(user, 30_000).unwrap();
+
+        // The deposit that trips the circuit breaker is refunded.
+        let usdc_before = pool_mockup.usdc_balance_query(&user.address());
+        vault_mockup.deposit(10_000, 10_000, user).unwrap();
+        assert_eq!(pool_mockup.usdc_balance_query(&user.address()), usdc_before);
+        assert!(vault_mockup.vault_state_query().circuit_breaker_tripped);
+
+        assert!(vault_mockup.deposit(10_000, 10_000, user).is_err());
+        assert!(vault_mockup.rebalance(admin).is_err());
+        let shares = vault_mockup.shares_query(&user.address());
+        vault_mockup.withdraw(Uint128::new(shares.u128() / 2), user).unwrap();
+
+        // Once the TWAP catches up with the price, it settles by itself.
+        pool_mockup.app.increase_time(120);
+        vault_mockup.rebalance(admin).unwrap();
+        assert!(!vault_mockup.vault_state_query().circuit_breaker_tripped);
+    }
+
+    #[test]
+    fn deposit_caps() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user) = (&pool_mockup.deployer, &pool_mockup.user1);
+        let deposit_caps = |max_total_shares: u128, min_deposit_value: u128| DepositCapsInstantiateMsg {
+            max_total_shares: Some(max_total_shares.into()),
+            max_total_value: Some(1_000_000u128.into()),
+            max_shares_per_address: None,
+            min_deposit_value: Some(min_deposit_value.into())
+        };
+
+        assert!(vault_mockup.change_deposit_caps(user, Some(deposit_caps(50_000, 1_000))).is_err());
+        assert!(vault_mockup.change_deposit_caps(admin, Some(deposit_caps(50_000, 2_000_000))).is_err());
+        vault_mockup.change_deposit_caps(admin, Some(deposit_caps(50_000, 1_000))).unwrap();
+        assert_eq!(vault_mockup.calc_shares_query(10_000, 10_000).remaining_shares, Some(50_000u128.into()));
+
+        // Deposits above the cap are partially accepted, refunding the rest.
+        let usdc_before = pool_mockup.usdc_balance_query(&user.address());
+        vault_mockup.deposit(100_000, 100_000, user).unwrap();
+        let total_supply = vault_mockup.token_info_query().total_supply;
+        assert!(total_supply <= Uint128::new(50_000));
+        assert!(usdc_before - pool_mockup.usdc_balance_query(&user.address()) < Uint128::new(100_000));
+        assert_eq!(
+            vault_mockup.calc_shares_query(10_000, 10_000).remaining_shares,
+            Some(Uint128::new(50_000) - total_supply)
+        );
+
+        assert!(vault_mockup.deposit(100, 100, user).is_err());
+
+        vault_mockup.change_deposit_caps(admin, Some(deposit_caps(total_supply.u128(), 1_000))).unwrap();
+        assert!(vault_mockup.calc_shares_query(10_000, 10_000).shares.is_zero());
+        assert!(vault_mockup.deposit(10_000, 10_000, user).is_err());
+
+        vault_mockup.change_deposit_caps(admin, None).unwrap();
+        vault_mockup.deposit(10_000, 10_000, user).unwrap();
+    }
+
+    #[test]
+    fn deposit_caps_first_deposit() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1, user2) = (&pool_mockup.deployer, &pool_mockup.user1, &pool_mockup.user2);
+
+        // A first deposit capped to at most the min liquidity is rejected.
+        vault_mockup.change_deposit_caps(admin, Some(DepositCapsInstantiateMsg {
+            max_total_shares: Some(MIN_LIQUIDITY),
+            max_total_value: None,
+            max_shares_per_address: None,
+            min_deposit_value: None
+        })).unwrap();
+        assert!(vault_mockup.calc_shares_query(10_000, 10_000).shares.is_zero());
+        let reason = vault_mockup.deposit(10_000, 10_000, user1).unwrap_err().to_string();
+        assert!(reason.contains("deposit caps are reached"));
+        assert!(vault_mockup.token_info_query().total_supply.is_zero());
+
+        let max_shares = Uint128::new(50_000);
+        vault_mockup.change_deposit_caps(admin, Some(DepositCapsInstantiateMsg {
+            max_total_shares: Some(max_shares),
+            max_total_value: None,
+            max_shares_per_address: Some(Uint128::new(40_000)),
+            min_deposit_value: None
+        })).unwrap();
+
+        // The min liquidity minted on the first deposit also counts towards the cap.
+        let amount = (max_shares + MIN_LIQUIDITY).u128();
+        vault_mockup.deposit(amount, amount, user1).unwrap();
+        assert!(vault_mockup.token_info_query().total_supply <= max_shares);
+        assert!(vault_mockup.shares_query(&user1.address()) <= Uint128::new(40_000));
+
+        // The per address cap also holds for shares moved by transfers.
+        let shares = vault_mockup.shares_query(&user1.address());
+        assert!(vault_mockup.transfer_shares(shares, &user2.address(), user1).is_ok());
+        vault_mockup.change_deposit_caps(admin, Some(DepositCapsInstantiateMsg {
+            max_total_shares: None,
+            max_total_value: None,
+            max_shares_per_address: Some(shares - Uint128::one()),
+            min_deposit_value: None
+        })).unwrap();
+        assert!(vault_mockup.transfer_shares(shares, &user1.address(), user2).is_err());
+        vault_mockup.transfer_shares(shares - Uint128::one(), &user1.address(), user2).unwrap();
+    }
+
+    #[test]
+    fn access_list() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1, user2) = (&pool_mockup.deployer, &pool_mockup.user1, &pool_mockup.user2);
+        let allowlist = AccessListInstantiateMsg::Allowlist {
+            addresses: vec![admin.address(), user1.address()]
+        };
+
+        assert!(vault_mockup.is_allowed_query(&user2.address()));
+        assert!(vault_mockup.change_access_list(user1, Some(allowlist.clone())).is_err());
+        assert!(vault_mockup.change_access_list(admin, Some(AccessListInstantiateMsg::Blocklist {
+            addresses: vec!["invalid".into()]
+        })).is_err());
+        vault_mockup.change_access_list(admin, Some(allowlist)).unwrap();
+        assert!(vault_mockup.is_allowed_query(&user1.address()));
+        assert!(!vault_mockup.is_allowed_query(&user2.address()));
+
+        assert!(vault_mockup.deposit(10_000, 10_000, user2).is_err());
+        vault_mockup.deposit(10_000, 10_000, user1).unwrap();
+        let shares = vault_mockup.shares_query(&user1.address());
+        assert!(vault_mockup.transfer_shares(shares, &user2.address(), user1).is_err());
+        vault_mockup.transfer_shares(Uint128::new(100), &admin.address(), user1).unwrap();
+
+        // Blocked holders keep their shares and can still withdraw.
+        vault_mockup.change_access_list(admin, Some(AccessListInstantiateMsg::Blocklist {
+            addresses: vec![user1.address()]
+        })).unwrap();
+        assert!(vault_mockup.is_allowed_query(&user2.address()));
+        assert!(vault_mockup.transfer_shares(Uint128::new(100), &user2.address(), user1).is_err());
+        assert!(vault_mockup.deposit(10_000, 10_000, user1).is_err());
+        vault_mockup.withdraw(vault_mockup.shares_query(&user1.address()), user1).unwrap();
+
+        // Fee shares arent minted to an admin not allowed to hold them.
+        vault_mockup.change_access_list(admin, Some(AccessListInstantiateMsg::Blocklist {
+            addresses: vec![admin.address()]
+        })).unwrap();
+        vault_mockup.change_management_fee(admin, Some("0.1")).unwrap();
+        let admin_shares = vault_mockup.shares_query(&admin.address());
+        vault_mockup.deposit(10_000, 10_000, user2).unwrap();
+        pool_mockup.app.increase_time(SECONDS_IN_A_YEAR / 10);
+        vault_mockup.rebalance(admin).unwrap();
+        assert_eq!(vault_mockup.shares_query(&admin.address()), admin_shares);
+
+        vault_mockup.change_access_list(admin, None).unwrap();
+        assert!(vault_mockup.is_allowed_query(&admin.address()));
+        vault_mockup.deposit(10_000, 10_000, user1).unwrap();
+    }
+
+    #[test]
+    fn withdrawal_cooldown() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1, user2) = (&pool_mockup.deployer, &pool_mockup.user1, &pool_mockup.user2);
+
+        assert!(vault_mockup.change_withdrawal_cooldown(user1, Some(3600)).is_err());
+        assert!(vault_mockup.change_withdrawal_cooldown(admin, Some(0)).is_err());
+        assert!(vault_mockup
+            .change_withdrawal_cooldown(admin, Some(MAX_WITHDRAWAL_COOLDOWN_SECONDS as u32 + 1))
+            .is_err());
+        vault_mockup.change_withdrawal_cooldown(admin, Some(3600)).unwrap();
+
+        vault_mockup.deposit(10_000, 10_000, user1).unwrap();
+        let unlocks_at = vault_mockup.shares_unlock_query(&user1.address()).unlocks_at.unwrap();
+        assert!(vault_mockup.shares_unlock_query(&user2.address()).unlocks_at.is_none());
+
+        let shares = vault_mockup.shares_query(&user1.address());
+        assert!(vault_mockup.withdraw(shares, user1).is_err());
+        assert!(vault_mockup.transfer_shares(shares, &user2.address(), user1).is_err());
+
+        // Dust deposits barely extend the lockup of already locked shares.
+        pool_mockup.app.increase_time(1800);
+        vault_mockup.deposit(1_001, 1_001, user1).unwrap();
+        let new_unlocks_at = vault_mockup.shares_unlock_query(&user1.address()).unlocks_at.unwrap();
+        assert!(new_unlocks_at >= unlocks_at && new_unlocks_at < unlocks_at.plus_seconds(1800));
+
+        pool_mockup.app.increase_time(3600);
+        assert!(vault_mockup.shares_unlock_query(&user1.address()).unlocks_at.is_none());
+        vault_mockup.transfer_shares(Uint128::new(100), &user2.address(), user1).unwrap();
+        vault_mockup.withdraw(Uint128::new(100), user1).unwrap();
+
+        // Deposits to an address only lock the new shares, never its unlocked ones.
+        let unlocked_shares = vault_mockup.shares_query(&user1.address());
+        vault_mockup.wasm.execute(
+            vault_mockup.vault_addr.as_ref(),
+            &deposit_msg(user1.address()),
+            &[Coin::new(10_000, USDC_DENOM), Coin::new(10_000, OSMO_DENOM)],
+            admin
+        ).unwrap();
+        let locked_shares = vault_mockup.shares_unlock_query(&user1.address()).locked_shares;
+        assert_eq!(vault_mockup.shares_query(&user1.address()), unlocked_shares + locked_shares);
+        assert!(vault_mockup.withdraw(unlocked_shares + Uint128::one(), user1).is_err());
+        vault_mockup.withdraw(unlocked_shares, user1).unwrap();
+
+        // Removing the cooldown unlocks all shares right away.
+        vault_mockup.deposit(10_000, 10_000, user2).unwrap();
+        let shares = vault_mockup.shares_query(&user2.address());
+        assert!(vault_mockup.withdraw(shares, user2).is_err());
+        vault_mockup.change_withdrawal_cooldown(admin, None).unwrap();
+        vault_mockup.withdraw(shares, user2).unwrap();
+    }
+
+    #[test]
+    fn reward_index() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1, user2) = (&pool_mockup.deployer, &pool_mockup.user1, &pool_mockup.user2);
+        let pending = |address: &str| vault_mockup
+            .pending_rewards_query(address)
+            .rewards
+            .iter()
+            .find(|x| x.denom == REWARD_DENOM)
+            .map(|x| x.amount)
+            .unwrap_or_default();
+
+        vault_mockup.deposit(100_000, 100_000, user1).unwrap();
+        vault_mockup.deposit(100_000, 100_000, user2).unwrap();
+
+        // Only the reward denoms registered by the admin are distributed.
+        pool_mockup.send_rewards(100_000, vault_mockup.vault_addr.as_str(), admin);
+        assert!(vault_mockup.pending_rewards_query(&user1.address()).rewards.is_empty());
+        assert!(vault_mockup.change_reward_denom(user1, REWARD_DENOM, true).is_err());
+        assert!(vault_mockup.change_reward_denom(admin, USDC_DENOM, true).is_err());
+        vault_mockup.change_reward_denom(admin, REWARD_DENOM, true).unwrap();
+
+        // Pool tokens are never considered rewards.
+        assert_eq!(vault_mockup.pending_rewards_query(&user1.address()).rewards.len(), 1);
+        assert_approx_eq!(pending(&user1.address()), Uint128::new(50_000), Uint128::new(500));
+        assert_approx_eq!(pending(&user2.address()), Uint128::new(50_000), Uint128::new(500));
+
+        // Transfered shares only earn rewards received afterwards.
+        let shares = vault_mockup.shares_query(&user1.address());
+        vault_mockup.transfer_shares(shares, &user2.address(), user1).unwrap();
+        let pending1 = pending(&user1.address());
+        pool_mockup.send_rewards(100_000, vault_mockup.vault_addr.as_str(), admin);
+        assert_eq!(pending(&user1.address()), pending1);
+        assert_approx_eq!(pending(&user2.address()), Uint128::new(150_000), Uint128::new(1_000));
+
+        let balance_before = pool_mockup.reward_balance_query(&user1.address());
+        vault_mockup.claim_rewards(user1).unwrap();
+        assert_eq!(pool_mockup.reward_balance_query(&user1.address()), balance_before + pending1);
+        assert!(pending(&user1.address()).is_zero());
+
+        // Claimed rewards dont get distributed again, only the rounding dust and
+        // the `MIN_LIQUIDITY` shares rewards are left in the vault.
+        vault_mockup.claim_rewards(user2).unwrap();
+        assert!(pending(&user2.address()).is_zero());
+        assert!(pool_mockup.reward_balance_query(vault_mockup.vault_addr.as_str()) < Uint128::new(1_000));
+    }
+
+    #[test]
+    fn harvest_incentives() {
+        let pool_mockup = PoolMockup::new(200_000, 100_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user) = (&pool_mockup.deployer, &pool_mockup.user1);
+        let reward_pool_id = pool_mockup.create_reward_pool(1_000_000, 1_000_000);
+        let route = |token_out_denom: &str| HarvestRouteInstantiateMsg {
+            hops: vec![HarvestHop { pool_id: reward_pool_id, token_out_denom: token_out_denom.into() }],
+            twap_seconds: 60,
+            max_slippage: Decimal::percent(5).atomics()
+        };
+
+        assert!(vault_mockup.change_harvest_route(admin, REWARD_DENOM, Some(route(REWARD_DENOM))).is_err());
+        assert!(vault_mockup.change_harvest_route(admin, USDC_DENOM, Some(route(USDC_DENOM))).is_err());
+        // The reward pool cant swap into OSMO.
+        assert!(vault_mockup.change_harvest_route(admin, REWARD_DENOM, Some(route(OSMO_DENOM))).is_err());
+        assert!(vault_mockup.change_harvest_route(user, REWARD_DENOM, Some(route(USDC_DENOM))).is_err());
+        vault_mockup.change_harvest_route(admin, REWARD_DENOM, Some(route(USDC_DENOM))).unwrap();
+        vault_mockup.change_reward_denom(admin, REWARD_DENOM, true).unwrap();
+
+        vault_mockup.deposit(100_000, 100_000, user).unwrap();
+        assert!(vault_mockup.harvest_incentives(admin).is_err());
+        pool_mockup.send_rewards(10_000, vault_mockup.vault_addr.as_str(), admin);
+
+        // Routed rewards are not distributed.
+        assert!(vault_mockup.pending_rewards_query(&user.address()).rewards.is_empty());
+        assert!(vault_mockup.harvest_incentives(user).is_err());
+
+        let balances_before = vault_mockup.vault_balances_query();
+        let fees_before = vault_mockup.vault_fees_query();
+        vault_mockup.harvest_incentives(admin).unwrap();
+        let balances_after = vault_mockup.vault_balances_query();
+        let fees_after = vault_mockup.vault_fees_query();
+
+        assert!(pool_mockup.reward_balance_query(vault_mockup.vault_addr.as_str()).is_zero());
+        assert_eq!(balances_after.bal1, balances_before.bal1);
+        // NOTE: About 10% of the proceeds are the protocol and admin cuts.
+        assert_approx_eq!(balances_after.bal0, balances_before.bal0 + Uint128::new(9_000), Uint128::new(500));
+        assert!(fees_after.protocol_tokens0_owned > fees_before.protocol_tokens0_owned);
+    }
+
+    #[test]
+    fn performance_fee() {
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user) = (&pool_mockup.deployer, &pool_mockup.user1);
+        let performance_fee = |fee| Some(PerformanceFeeInstantiateMsg {
+            fee: Decimal::percent(fee).atomics(),
+            reference_token: ReferenceToken::Token1 {}
+        });
+        let high_water_mark = || vault_mockup
+            .vault_fees_query()
+            .performance_fee
+            .and_then(|x| x.high_water_mark);
+
+        assert!(vault_mockup.change_performance_fee(user, performance_fee(20)).is_err());
+        assert!(vault_mockup.change_performance_fee(admin, performance_fee(50)).is_err());
+        vault_mockup.change_performance_fee(admin, performance_fee(20)).unwrap();
+
+        // The first rebalance only sets the high-water mark.
+        vault_mockup.deposit(10_000, 20_000, user).unwrap();
+        vault_mockup.rebalance(admin).unwrap();
+        let first_mark = high_water_mark().unwrap();
+        assert!(vault_mockup.shares_query(&admin.address()).is_zero());
+
+        // Round trips leave the price in place, but the vault earns spread fees.
+        for _ in 0..3 {
+            let usdc_got = pool_mockup.swap_osmo_for_usdc(user, 20_000).unwrap();
+            pool_mockup.swap_usdc_for_osmo(user, usdc_got.into()).unwrap();
+        }
+
+        pool_mockup.app.increase_time(60);
+        vault_mockup.rebalance(admin).unwrap();
+        let admin_shares = vault_mockup.shares_query(&admin.address());
+        assert!(!admin_shares.is_zero());
+        assert!(high_water_mark().unwrap() > first_mark);
+
+        // Changing the fee keeps the mark, so the same gains arent charged again.
+        let mark = high_water_mark();
+        vault_mockup.change_performance_fee(admin, performance_fee(10)).unwrap();
+        assert_eq!(high_water_mark(), mark);
+        vault_mockup.rebalance(admin).unwrap();
+        assert_eq!(vault_mockup.shares_query(&admin.address()), admin_shares);
+    }
+
+    #[test]
+    fn management_fee() {
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user) = (&pool_mockup.deployer, &pool_mockup.user1);
+
+        assert!(vault_mockup.change_management_fee(user, Some("0.1")).is_err());
+        assert!(vault_mockup.change_management_fee(admin, Some("0.11")).is_err());
+        vault_mockup.change_management_fee(admin, Some("0.1")).unwrap();
+
+        vault_mockup.deposit(10_000, 20_000, user).unwrap();
+        let total_supply = vault_mockup.token_info_query().total_supply;
+        pool_mockup.app.increase_time(SECONDS_IN_A_YEAR / 10);
+
+        // A tenth of a year at 10% per year is 1% of the vault value.
+        let expected_shares = total_supply.multiply_ratio(1u128, 99u128);
+        let accrued_shares = vault_mockup.management_fee_query().accrued_shares;
+        assert_approx_eq!(accrued_shares, expected_shares, Uint128::new(5));
+
+        vault_mockup.rebalance(admin).unwrap();
+        let admin_shares = vault_mockup.shares_query(&admin.address());
+        assert!(admin_shares >= accrued_shares);
+        assert_approx_eq!(admin_shares, expected_shares, Uint128::new(5));
+        assert!(vault_mockup.management_fee_query().accrued_shares < Uint128::new(5));
+
+        // Long inactivity accrues every year, each on what the previous ones left.
+        let total_supply = vault_mockup.token_info_query().total_supply;
+        pool_mockup.app.increase_time(2 * SECONDS_IN_A_YEAR);
+        let expected_shares = total_supply.multiply_ratio(19u128, 81u128);
+        let accrued_shares = vault_mockup.management_fee_query().accrued_shares;
+        assert_approx_eq!(accrued_shares, expected_shares, Uint128::new(5));
+
+        // Removing the fee accrues whats pending first.
+        pool_mockup.app.increase_time(SECONDS_IN_A_YEAR / 10);
+        vault_mockup.change_management_fee(admin, None).unwrap();
+        assert!(vault_mockup.shares_query(&admin.address()) > admin_shares);
+        assert!(vault_mockup.management_fee_query().management_fee.is_none());
+    }
+
+    #[test]
+    fn admin_fee_increase_notice() {
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let admin = &pool_mockup.deployer;
+        let admin_fee = || vault_mockup.admin_fee_query().admin_fee.0.0;
+
+        vault_mockup.change_admin_fee(admin, "0.02").unwrap();
+        assert_eq!(admin_fee(), Decimal::percent(2));
+
+        assert!(vault_mockup.change_admin_fee(admin, "0.04").is_err());
+        vault_mockup.change_admin_fee(admin, "0.03").unwrap();
+        let pending = vault_mockup.admin_fee_query().pending_admin_fee.unwrap();
+        assert_eq!(pending.admin_fee.0.0, Decimal::percent(3));
+        assert_eq!(admin_fee(), Decimal::percent(2));
+        assert_eq!(vault_mockup.vault_fees_query().admin_fee.0.0, Decimal::percent(2));
+
+        // Queries use the new fee right away, before any execute stores it.
+        pool_mockup.app.increase_time(ADMIN_FEE_INCREASE_NOTICE_SECONDS);
+        assert_eq!(admin_fee(), Decimal::percent(3));
+        assert_eq!(vault_mockup.vault_fees_query().admin_fee.0.0, Decimal::percent(3));
+        assert!(vault_mockup.admin_fee_query().pending_admin_fee.is_none());
+
+        vault_mockup.change_admin_fee(admin, "0.04").unwrap();
+        vault_mockup.change_admin_fee(admin, "0.01").unwrap();
+        assert_eq!(admin_fee(), Decimal::percent(1));
+        assert!(vault_mockup.admin_fee_query().pending_admin_fee.is_none());
+    }
+
+    #[test]
+    fn entry_exit_fees() {
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1, user2) = (&pool_mockup.deployer, &pool_mockup.user1, &pool_mockup.user2);
+        let fees = |entry, exit| Some(EntryExitFeesInstantiateMsg {
+            entry_fee: Decimal::percent(entry).atomics(),
+            exit_fee: Decimal::percent(exit).atomics(),
+            recipient: FeeRecipient::Protocol {}
+        });
+
+        assert!(vault_mockup.change_entry_exit_fees(admin, fees(3, 1)).is_err());
+        assert!(vault_mockup.change_entry_exit_fees(user1, fees(1, 2)).is_err());
+        vault_mockup.change_entry_exit_fees(admin, fees(1, 2)).unwrap();
+
+        vault_mockup.deposit(10_000, 20_000, user1).unwrap();
+        vault_mockup.rebalance(admin).unwrap();
+
+        let preview = vault_mockup.calc_shares_query(10_000, 20_000);
+        assert_eq!(preview.entry_fee0, preview.usable_amount0 / Uint128::new(100));
+        let protocol_owned_before = vault_mockup.vault_fees_query().protocol_tokens0_owned;
+        vault_mockup.deposit(10_000, 20_000, user2).unwrap();
+        let shares = vault_mockup.shares_query(&user2.address());
+        assert_eq!(shares, preview.shares);
+        assert_eq!(
+            vault_mockup.vault_fees_query().protocol_tokens0_owned,
+            protocol_owned_before + preview.entry_fee0
+        );
+
+        let preview = vault_mockup.simulate_withdraw_query(shares);
+        assert!(!preview.exit_fee0.is_zero() && !preview.exit_fee1.is_zero());
+        let usdc_before = pool_mockup.usdc_balance_query(&user2.address());
+        vault_mockup.withdraw(shares, user2).unwrap();
+        let usdc_got = pool_mockup.usdc_balance_query(&user2.address()) - usdc_before;
+        assert_approx_eq!(usdc_got, preview.amount0, Uint128::new(2));
+    }
+
+    #[test]
+    fn admin_fee_beneficiaries() {
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1, user2) = (&pool_mockup.deployer, &pool_mockup.user1, &pool_mockup.user2);
+
+        assert!(vault_mockup.change_admin_fee_beneficiaries(user1, vec![(user1, "1")]).is_err());
+        assert!(vault_mockup.change_admin_fee_beneficiaries(admin, vec![(user1, "0.7")]).is_err());
+        assert!(vault_mockup
+            .change_admin_fee_beneficiaries(admin, vec![(user1, "0.5"), (user1, "0.5")])
+            .is_err());
+        vault_mockup
+            .change_admin_fee_beneficiaries(admin, vec![(user1, "0.7"), (user2, "0.3")])
+            .unwrap();
+
+        vault_mockup.change_entry_exit_fees(admin, Some(EntryExitFeesInstantiateMsg {
+            entry_fee: Decimal::percent(2).atomics(),
+            exit_fee: Uint128::zero(),
+            recipient: FeeRecipient::Admin {}
+        })).unwrap();
+        vault_mockup.deposit(10_000, 20_000, admin).unwrap();
+
+        let owned = vault_mockup.vault_fees_query().admin_tokens0_owned;
+        assert!(!owned.is_zero());
+        let owed = vault_mockup.admin_fees_owed_query().owed;
+        assert_eq!(owed.len(), 2);
+        assert_eq!(owed[0].1[0].amount, owned * Decimal::percent(70));
+        assert_eq!(owed[0].1[0].amount + owed[1].1[0].amount, owned);
+
+        let usdc_before1 = pool_mockup.usdc_balance_query(&user1.address());
+        let usdc_before2 = pool_mockup.usdc_balance_query(&user2.address());
+        vault_mockup.admin_withdraw(admin).unwrap();
+        assert_eq!(
+            pool_mockup.usdc_balance_query(&user1.address()) - usdc_before1,
+            owed[0].1[0].amount
+        );
+        assert_eq!(
+            pool_mockup.usdc_balance_query(&user2.address()) - usdc_before2,
+            owed[1].1[0].amount
+        );
+        assert!(vault_mockup.vault_fees_query().admin_tokens0_owned.is_zero());
+    }
+
+    #[test]
+    fn protocol_role() {
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let vault_mockup = VaultMockup::new(&pool_mockup, vault_params("2", "1.45", "0.55"));
+        let (admin, user1) = (&pool_mockup.deployer, &pool_mockup.user1);
+
+        let protocol = &pool_mockup.protocol;
+
+        let protocol_info = vault_mockup.protocol_info_query();
+        assert_eq!(protocol_info.protocol, Addr::unchecked(protocol.address()));
+        assert_eq!(protocol_info.fee_recipient, Addr::unchecked(protocol.address()));
+
+        assert!(vault_mockup.propose_new_protocol(admin, Some(user1)).is_err());
+        assert!(vault_mockup.propose_new_protocol(user1, Some(user1)).is_err());
+        assert!(vault_mockup.accept_new_protocol(user1).is_err());
+        assert!(vault_mockup.change_protocol_fee_recipient(admin, admin).is_err());
+        assert!(vault_mockup.protocol_withdraw(admin).is_err());
+        assert_eq!(vault_mockup.protocol_info_query(), protocol_info);
+
+        // The new protocol keeps the old fee recipient until it changes it.
+        vault_mockup.propose_new_protocol(protocol, Some(user1)).unwrap();
+        assert!(vault_mockup.accept_new_protocol(admin).is_err());
+        vault_mockup.accept_new_protocol(user1).unwrap();
+        let protocol_info = vault_mockup.protocol_info_query();
+        assert_eq!(protocol_info.protocol, Addr::unchecked(user1.address()));
+        assert_eq!(protocol_info.fee_recipient, Addr::unchecked(protocol.address()));
+
+        assert!(vault_mockup.change_protocol_fee_recipient(protocol, admin).is_err());
+        vault_mockup.change_protocol_fee_recipient(user1, admin).unwrap();
+        assert_eq!(vault_mockup.protocol_info_query().fee_recipient, Addr::unchecked(admin.address()));
+    }
+
+    #[test]
+    fn vault_creation_costs() {
+        let usdc_max = VaultCreationCost::max(USDC_DENOM).unwrap();
+        let osmo_max = VaultCreationCost::max(OSMO_DENOM).unwrap();
+        assert!(VaultCreationCost::max(REWARD_DENOM).is_none());
+        assert!(VaultCreationCost::new(coin(usdc_max.u128() + 1, USDC_DENOM)).is_err());
+        assert!(VaultCreationCost::new(coin(1, REWARD_DENOM)).is_err());
+        // NOTE: Maxes are per denom, so a cost can be fine in one denom but not in other.
+        assert!(osmo_max > usdc_max);
+        VaultCreationCost::new(coin(osmo_max.u128(), OSMO_DENOM)).unwrap();
+
+        let pool_mockup = PoolMockup::new(100_000, 200_000);
+        let new_vault = |costs: Vec<Coin>, paid: &[Coin]| VaultMockup::new_with_creation_costs(
+            &pool_mockup, vault_params("2", "1.45", "0.55"), VaultRebalancerInstantiateMsg::Admin {},
+            costs, paid
+        );
+        let stats = |vault_mockup: &VaultMockup, denom: &str| vault_mockup
+            .revenue_stats_query()
+            .stats
+            .into_iter()
+            .find(|(x, _)| x == denom)
+            .map(|(_, x)| x)
+            .unwrap_or_default();
+
+        let usdc_cost = coin(usdc_max.u128(), USDC_DENOM);
+        let osmo_cost = coin(osmo_max.u128(), OSMO_DENOM);
+        let costs = vec![usdc_cost.clone(), osmo_cost.clone()];
+        assert!(new_vault(vec![coin(usdc_max.u128() + 1, USDC_DENOM)], &[]).is_err());
+        assert!(new_vault(vec![coin(1, REWARD_DENOM)], &[coin(1, REWARD_DENOM)]).is_err());
+        assert!(new_vault(costs.clone(), &[]).is_err());
+        assert!(new_vault(costs.clone(), &[coin(usdc_max.u128() - 1, USDC_DENOM)]).is_err());
+        assert!(new_vault(costs.clone(), &[usdc_cost.clone(), osmo_cost.clone()]).is_err());
+
+        // Any one of the accepted costs can be paid.
+        let vault_mockup = new_vault(costs.clone(), &[osmo_cost]).unwrap();
+        assert_eq!(stats(&vault_mockup, OSMO_DENOM).creation_costs_collected, osmo_max);
+        assert!(stats(&vault_mockup, USDC_DENOM).creation_costs_collected.is_zero());
+        let vault_mockup = new_vault(costs, &[usdc_cost]).unwrap();
+        assert_eq!(stats(&vault_mockup, USDC_DENOM).creation_costs_collected, usdc_max);
+
+        // Vaults are free to create if no costs are accepted.
+        let vault_mockup = new_vault(vec![], &[]).unwrap();
+        assert!(stats(&vault_mockup, USDC_DENOM).creation_costs_collected.is_zero());
+    }
+
     #[test]
     fn protocol_address_is_valid() {
-        let a = Addr::unchecked(PROTOCOL_ADDR);
-        let b = mock_dependencies().api.addr_validate(PROTOCOL_ADDR).unwrap();
-        assert_eq!(a, b);
-        let c = Addr::unchecked(PROTOCOL_ADDR.to_uppercase());
-        assert_ne!(a, c);
+        let deps = mock_dependencies();
+        assert!(ProtocolInfo::new("", deps.as_ref()).is_err());
+        assert!(ProtocolInfo::new("Not An Address", deps.as_ref()).is_err());
+        let protocol_info = ProtocolInfo::new("protocol", deps.as_ref()).unwrap();
+        assert_eq!(protocol_info.protocol, Addr::unchecked("protocol"));
+        assert_eq!(protocol_info.fee_recipient, Addr::unchecked("protocol"));
     }
 
 }
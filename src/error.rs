@@ -1,12 +1,20 @@
 use cosmwasm_std::Uint128;
 use thiserror::Error;
-use crate::constants::TWAP_SECONDS;
 
 #[derive(Error, Debug, PartialEq)]
 pub enum ContractError {
     #[error("Entry point {0} is not payable")]
     NonPayable(String),
 
+    #[error("{0} is not allowed to hold vault shares")]
+    AddressNotAllowed(String),
+
+    #[error("Shares of {address} are locked by the withdrawal cooldown until {unlocks_at}")]
+    SharesLocked { address: String, unlocks_at: u64 },
+
+    #[error("{address} cant hold more than {max} vault shares")]
+    MaxSharesPerAddressReached { address: String, max: Uint128 },
+
     #[error("Instantiation error: {0}")]
     Instantiation(#[from] InstantiationError),
 
@@ -31,8 +39,8 @@ pub enum ContractError {
 
 #[derive(Error, Debug, PartialEq)]
 pub enum InstantiationError {
-    #[error("Vault creation costs {cost} of token {denom}, got: {got}")]
-    VaultCreationCostNotPaid { cost: String, denom: String, got: String },
+    #[error("Vault creation costs any one of: {accepted}; got: {got}")]
+    VaultCreationCostNotPaid { accepted: String, got: String },
 
     #[error("Invalid concentrated liquidity pool_id {0}")]
     InvalidPoolId(u64),
@@ -43,12 +51,42 @@ pub enum InstantiationError {
     #[error("Invalid vault admin address: {0}")]
     InvalidAdminAddress(String),
 
+    #[error("Invalid protocol address: {0}")]
+    InvalidProtocolAddress(String),
+
+    #[error("Vault creation costs cant be paid in {0}")]
+    UnknownVaultCreationCostDenom(String),
+
+    #[error("Invalid vault creation cost: max: {max}; got: {got}")]
+    InvalidVaultCreationCost { max: Uint128, got: Uint128 },
+
     #[error("Invalid vault admin fee: max: {max}; got: {got}")]
     InvalidAdminFee { max: Uint128, got: Uint128 },
 
+    #[error("Invalid vault keeper reward share: max: {max}; got: {got}")]
+    InvalidKeeperReward { max: Uint128, got: Uint128 },
+
     #[error("The vault admin cant have any fee if the vault doesnt have any admin")]
     AdminFeeWithoutAdmin { },
 
+    #[error("Invalid vault performance fee: max: {max}; got: {got}")]
+    InvalidPerformanceFee { max: Uint128, got: Uint128 },
+
+    #[error("Invalid vault management fee: max: {max}; got: {got}")]
+    InvalidManagementFee { max: Uint128, got: Uint128 },
+
+    #[error("Invalid admin fee beneficiary address: {0}")]
+    InvalidBeneficiaryAddress(String),
+
+    #[error("Invalid access list address: {0}")]
+    InvalidAccessListAddress(String),
+
+    #[error("Invalid withdrawal cooldown: max: {max}; got: {got}")]
+    InvalidWithdrawalCooldown { max: u64, got: u64 },
+
+    #[error("Invalid vault entry/exit fee: max: {max}; got: {got}")]
+    InvalidEntryExitFee { max: Uint128, got: Uint128 },
+
     #[error("Contradiction: {reason}")]
     ContradictoryConfig { reason: String },
 
@@ -70,11 +108,26 @@ pub enum DepositError {
     #[error("Shareholder address for the deposit is not a valid address: {0}")]
     InvalidShareholderAddress(String),
 
+    #[error("{0} is not allowed to hold vault shares")]
+    AddressNotAllowed(String),
+
     #[error("Used amounts below min wanted amounts: used: {used}, wanted: {wanted}")]
     DepositedAmountsBelowMin { used: String, wanted: String },
 
     #[error("Deposit must be above {min_liquidity}, got: {got}")]
-    DepositedAmountBelowMinLiquidity { min_liquidity: Uint128, got: String }
+    DepositedAmountBelowMinLiquidity { min_liquidity: Uint128, got: String },
+
+    #[error("The vault circuit breaker is tripped, only withdrawals are allowed")]
+    CircuitBreakerTripped {},
+
+    #[error("The vault deposit caps are reached, it cant accept more deposits")]
+    DepositCapReached {},
+
+    #[error("Deposit is too small to get any vault shares")]
+    ZeroSharesDeposit {},
+
+    #[error("Deposit value must be at least {min_value}, got: {got}")]
+    DepositBelowMinValue { min_value: Uint128, got: Uint128 }
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -85,26 +138,53 @@ pub enum RebalanceError {
     #[error("Only the delegate address {delegate} can rebalance, tried to do so from {got}")]
     UnauthorizedDelegateAccount { delegate: String, got: String },
 
+    #[error("{address} is not allowed to do {operation} operations on the vault positions")]
+    UnauthorizedRebalancer { address: String, operation: String },
+
     #[error("Rebalancing the same vault twice per block is not supported, wait for the next block")]
     CantRebalanceTwicePerBlock(),
 
-    #[error("Cant rebalance, price hasnt moved enough (price: {price}; movement_factor: {factor})")]
-    PriceHasntMovedEnough { price: Uint128, factor: Uint128 },
+    #[error("Cant rebalance, price hasnt moved enough (price: {price}; movement_factor: {factor}; bounds: [{lower_bound}, {upper_bound}])")]
+    PriceHasntMovedEnough { price: Uint128, factor: Uint128, lower_bound: Uint128, upper_bound: Uint128 },
 
-    #[error("Cant rebalance, the price {price} moved outside [{twap}*0.99, {twap}*1.01]")]
-    PriceMovedTooMuchInLastMinute { price: Uint128, twap: Uint128 },
+    #[error("Cant rebalance, the price {price} deviates more than {max_deviation} from the twap {twap}")]
+    PriceDeviatesFromTwap { price: Uint128, twap: Uint128, max_deviation: Uint128 },
 
-    #[error("Cant rebalance pools that were created less than {TWAP_SECONDS} seconds ago")]
-    PoolWasJustCreated(),
+    #[error("Cant rebalance, the pool has no TWAP for the last {seconds} seconds")]
+    TwapUnavailable { seconds: u64 },
 
     #[error("Not enough time passed since last rebalance, can rebalance in {time_left}")]
     NotEnoughTimePassed { time_left: u64 },
 
+    #[error("The vault was already rebalanced {max} times in the last day, can rebalance in {time_left}")]
+    MaxRebalancesPerDayReached { max: u32, time_left: u64 },
+
     #[error("You cant rebalance a vault without funds")]
     NothingToRebalance {},
 
     #[error("Pool with id {0} is empty, and thus has no price")]
     PoolWithoutPrice(u64),
+
+    #[error("Cant rebalance, the vault rebalance policy didnt pass: {reason}")]
+    RebalancePolicyNotMet { reason: String },
+
+    #[error("Cant do that while a gradual rebalance is in progress")]
+    RebalanceInProgress {},
+
+    #[error("The vault has no limit position to move")]
+    NoLimitPosition {},
+
+    #[error("Cant move the limit position while the price is inside its range")]
+    LimitPositionInRange {},
+
+    #[error("The vault circuit breaker is tripped, only withdrawals are allowed")]
+    CircuitBreakerTripped {},
+
+    #[error("There are no reward tokens with a harvest route to harvest")]
+    NothingToHarvest {},
+
+    #[error("Cant harvest {denom}, a pool in its route has no TWAP for the last {seconds} seconds")]
+    HarvestTwapUnavailable { denom: String, seconds: u64 },
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -118,6 +198,9 @@ pub enum WithdrawalError {
     #[error("Cant withdraw to itself ({0})")]
     CantWithdrawToContract(String),
 
+    #[error("Shares are locked by the withdrawal cooldown until {unlocks_at}")]
+    SharesLocked { unlocks_at: u64 },
+
     #[error("Trying to withdraw more shares than owned (owned: {owned}, withdrawn: {withdrawn})")]
     InvalidWithdrawalAmount { owned: Uint128, withdrawn: Uint128 },
 
@@ -132,6 +215,21 @@ pub enum ProtocolOperationError {
 
     #[error("Invalid protocol fee: max: {max}; got: {got}")]
     InvalidProtocolFee { max: Uint128, got: Uint128 },
+
+    #[error("Invalid new proposed protocol address: {0}")]
+    InvalidNewProposedProtocolAddress(String),
+
+    #[error("There is no protocol migration happening at this time")]
+    NewProposedProtocolIsNone(),
+
+    #[error("Only the new proposed protocol {expected} can take over, but {got} tried to")]
+    UnauthorizedNewProposedProtocol { expected: String, got: String },
+
+    #[error("Invalid protocol fee recipient address: {0}")]
+    InvalidProtocolFeeRecipient(String),
+
+    #[error("Trying to withdraw more protocol fees than owned (owned: {owned}, got: {got})")]
+    WithdrawingMoreFeesThanOwned { owned: String, got: String },
 }
 
 #[derive(Error, Debug, PartialEq)]
@@ -156,9 +254,27 @@ pub enum AdminOperationError {
     #[error("Tried to improperly reinstantiate state: {0}")]
     ReInstantiation(#[from] InstantiationError),
 
+    #[error("Admin fee can only increase up to {max} per notice period, got: {got}")]
+    AdminFeeIncreaseTooLarge { max: Uint128, got: Uint128 },
+
+    #[error("Trying to withdraw more admin fees than owned (owned: {owned}, got: {got})")]
+    WithdrawingMoreFeesThanOwned { owned: String, got: String },
+
+    #[error("Invalid admin fee recipient address: {0}")]
+    InvalidAdminFeeRecipient(String),
+
     #[error("Cant burn admin if the vault admin fee is not 0")]
     BurningAdminWithNonZeroAdminFee(),
 
+    #[error("Cant burn admin if the vault has a performance fee")]
+    BurningAdminWithPerformanceFee(),
+
+    #[error("Cant burn admin if the vault entry and exit fees go to the admin")]
+    BurningAdminWithNonZeroEntryExitFees(),
+
+    #[error("Cant burn admin if the vault has a management fee")]
+    BurningAdminWithManagementFee(),
+
     #[error("Tried to burn admin, but there are still uncollected admin fees")]
     BurningAdminWithUncollectedAdminFees(),
 
@@ -166,6 +282,18 @@ pub enum AdminOperationError {
     BurningAdminWithImproperRebalancer(),
 
     #[error("Cant burn admin if the vault has a proposed new admin")]
-    BurningAdminWithProposedNewAdmin()
+    BurningAdminWithProposedNewAdmin(),
+
+    #[error("There is no gradual rebalance happening at this time")]
+    NoRebalanceInProgress(),
+
+    #[error("{0} is not a delegated rebalancer")]
+    NonExistantDelegatedRebalancer(String),
+
+    #[error("Cant burn admin if the vault has delegated rebalancers")]
+    BurningAdminWithDelegatedRebalancers(),
+
+    #[error("The vault circuit breaker is not tripped")]
+    CircuitBreakerNotTripped()
 }
 
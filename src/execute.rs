@@ -1,34 +1,46 @@
 use std::str::FromStr;
 
 use cosmwasm_std::{
-    coin, Addr, BankMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Response, StdResult, SubMsg,
-    Uint128,
+    coin, Addr, BankMsg, Decimal, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult,
+    Storage, SubMsg, Uint128,
 };
 use cw20_base::{
     contract::{execute_burn, execute_mint, query_balance, query_token_info},
-    state::TOKEN_INFO,
+    state::{BALANCES, TOKEN_INFO},
 };
-use osmosis_std::types::osmosis::concentratedliquidity::v1beta1::{
-    MsgCollectSpreadRewards, MsgCreatePosition, MsgWithdrawPosition, PositionByIdRequest,
+use osmosis_std::types::osmosis::{
+    concentratedliquidity::v1beta1::{
+        MsgCollectSpreadRewards, MsgCreatePosition, MsgWithdrawPosition, PositionByIdRequest,
+    },
+    poolmanager::v1beta1::{MsgSwapExactAmountIn, SwapAmountInRoute},
 };
 
 use crate::{
     assert_approx_eq,
-    constants::{MIN_LIQUIDITY, POSITION_CREATION_SLIPPAGE, PROTOCOL_ADDR, VAULT_CREATION_COST_DENOM},
-    do_some,
+    constants::{MIN_LIQUIDITY, POSITION_CREATION_SLIPPAGE, SECONDS_IN_A_DAY},
+    do_me, do_some,
     error::{
-        AdminOperationError, DepositError, ProtocolOperationError, RebalanceError, WithdrawalError,
+        AdminOperationError, DepositError, InstantiationError, ProtocolOperationError,
+        RebalanceError, WithdrawalError,
     },
     msg::{
-        CalcSharesAndUsableAmountsResponse, DepositMsg, VaultBalancesResponse,
+        AccessListInstantiateMsg, CalcSharesAndUsableAmountsResponse, CircuitBreakerInstantiateMsg,
+        DepositCapsInstantiateMsg, DepositMsg, EntryExitFeesInstantiateMsg, FeesWithdrawalMsg,
+        HarvestRouteInstantiateMsg, KeeperRewardInstantiateMsg, PerformanceFeeInstantiateMsg,
+        RebalanceLimitsInstantiateMsg, TwapConfigInstantiateMsg, VaultBalancesResponse,
         VaultParametersInstantiateMsg, VaultRebalancerInstantiateMsg, WithdrawMsg,
     },
     query,
     state::{
-        FundsInfo, PositionType, StateSnapshot, VaultParameters, VaultRebalancer, VaultState,
-        Weight, FEES_INFO, FUNDS_INFO, VAULT_INFO, VAULT_PARAMETERS, VAULT_STATE,
+        DelegatedRebalancer, FeeRecipient, FeesInfo, FundsInfo, GradualRebalance, HarvestRoute,
+        PositionSnapshot, PositionType, PriceFactor, RebalancePolicy, RebalanceRecord,
+        RebalancerPermission, ReferenceToken, RevenueStats, StateSnapshot, TwapConfig, TwapFallback,
+        VaultInfo, VaultParameters, VaultRebalancer, VaultState, Weight, DELEGATED_REBALANCERS,
+        FEES_INFO, FUNDS_INFO, HARVEST_ROUTES, PROTOCOL_INFO, REBALANCE_HISTORY, REVENUE_STATS,
+        REWARD_DENOMS, REWARD_INDEXES, SHAREHOLDER_REWARDS, SHARES_LOCKS, VAULT_INFO,
+        VAULT_PARAMETERS, VAULT_STATE,
     },
-    utils::{calc_x0, price_function_inv, raw},
+    utils::{calc_x0, price_function_inv, raw, value_in_token0, value_in_token1},
 };
 
 pub fn deposit(
@@ -37,11 +49,23 @@ pub fn deposit(
         amount1_min,
         to,
     }: DepositMsg,
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
 ) -> Result<Response, DepositError> {
     use DepositError::*;
+
+    // NOTE: The deposit is refunded, so the tripped circuit breaker is persisted.
+    match check_circuit_breaker(deps.branch(), &env) {
+        CircuitBreakerCheck::Clear => {},
+        CircuitBreakerCheck::JustTripped => return Ok(circuit_breaker_tripped_response()
+            .add_message(BankMsg::Send { to_address: info.sender.into(), amount: info.funds })),
+        CircuitBreakerCheck::Tripped => return Err(CircuitBreakerTripped {})
+    }
+
+    // NOTE: Accrued first, so new shares dont pay for the time before the deposit.
+    let management_fee_res = accrue_management_fee(deps.branch(), &env);
+
     // Invariant: `VAULT_INFO` will always be present after instantiation.
     let vault_info = VAULT_INFO.load(deps.storage).unwrap();
     let contract_addr = env.contract.address.clone();
@@ -84,6 +108,11 @@ pub fn deposit(
         return Err(ShareholderCantBeContract(new_holder.into()));
     }
 
+    let holders = [info.sender.as_str(), new_holder.as_str()];
+    if let Some(address) = not_allowed_holder(&holders, deps.as_ref()) {
+        return Err(AddressNotAllowed(address))
+    }
+
     if !(amount0 > MIN_LIQUIDITY || amount1 > MIN_LIQUIDITY) {
         return Err(DepositedAmountBelowMinLiquidity { 
             min_liquidity: MIN_LIQUIDITY,
@@ -95,14 +124,45 @@ pub fn deposit(
         shares,
         usable_amount0: amount0_used,
         usable_amount1: amount1_used,
-    } = query::calc_shares_and_usable_amounts(amount0, amount1, deps.as_ref());
+        entry_fee0,
+        entry_fee1,
+        ..
+    } = query::calc_shares_and_usable_amounts_for(
+        amount0, amount1, Some(&new_holder), deps.as_ref(), &env
+    );
+
+    if shares.is_zero() {
+        let uncapped = query::calc_shares_and_usable_amounts(amount0, amount1, deps.as_ref(), &env);
+        return Err(if uncapped.shares.is_zero() { ZeroSharesDeposit {} } else { DepositCapReached {} })
+    }
+
+    if let Some(min_value) = vault_info.deposit_caps.as_ref().and_then(|x| x.min_deposit_value) {
+        let price = vault_info.pool_id.price(&deps.querier);
+        let value = value_in_token1(amount0_used, amount1_used, &price);
+        if value < min_value {
+            return Err(DepositBelowMinValue { min_value, got: value })
+        }
+    }
+
+    // Invariant: Any state is present after instantiation.
+    let mut fees = FEES_INFO.load(deps.storage).unwrap();
+    let vault_keeps_fees = fees.credit_entry_exit_fees(entry_fee0, entry_fee1);
+    FEES_INFO.save(deps.storage, &fees).unwrap();
+    record_entry_exit_fees(&fees, entry_fee0, entry_fee1, deps.branch());
+
+    // Invariant: Wont underflow, as the fees are a part of the used amounts.
+    let (funds_added0, funds_added1) = if vault_keeps_fees {
+        (amount0_used, amount1_used)
+    } else {
+        (amount0_used.checked_sub(entry_fee0).unwrap(), amount1_used.checked_sub(entry_fee1).unwrap())
+    };
 
     // Invariant: Wont overflow, as for that token balances would have to be above
     //            `Uint128::MAX`, but thats not possible.
     // NOTE: The update is sound as we refund unusued amounts later.
     FUNDS_INFO.update(deps.storage, |mut funds| -> StdResult<_>  {
-        funds.available_balance0 = funds.available_balance0.checked_add(amount0_used)?;
-        funds.available_balance1 = funds.available_balance1.checked_add(amount1_used)?;
+        funds.available_balance0 = funds.available_balance0.checked_add(funds_added0)?;
+        funds.available_balance1 = funds.available_balance1.checked_add(funds_added1)?;
         Ok(funds)
     }).unwrap();
 
@@ -117,6 +177,9 @@ pub fn deposit(
         });
     }
 
+    sync_rewards(&[new_holder.as_str(), contract_addr.as_str()], deps.branch(), &env);
+    lock_deposited_shares(&new_holder, shares, &vault_info, deps.branch(), &env);
+
     let res = {
         let mut info = info.clone();
         let mut deps = deps;
@@ -145,7 +208,7 @@ pub fn deposit(
     assert!(amount0_used <= amount0 && amount1_used <= amount1);
 
     // Invariant: Wont panic because of the invariant above.
-    Ok(res.add_message(BankMsg::Send {
+    Ok(res.add_attributes(management_fee_res.attributes).add_message(BankMsg::Send {
         to_address: info.sender.to_string(),
         amount: vec![
             coin(amount0.checked_sub(amount0_used).unwrap().into(), denom0),
@@ -154,9 +217,32 @@ pub fn deposit(
     }))
 }
 
-pub fn rebalance(deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Response, RebalanceError> {
+/// Locks the `shares` deposited to `holder` for the vault withdrawal cooldown, if any.
+/// Must be called before the shares are minted.
+fn lock_deposited_shares(
+    holder: &Addr,
+    shares: Uint128,
+    vault_info: &VaultInfo,
+    deps: DepsMut,
+    env: &Env
+) {
+    // Invariant: Wont panic as all types are proper.
+    let lock = SHARES_LOCKS.may_load(deps.storage, holder).unwrap();
+    if let Some(lock) = vault_info.shares_lock_after_deposit(lock, shares, env.block.time) {
+        SHARES_LOCKS.save(deps.storage, holder, &lock).unwrap();
+    }
+}
+
+pub fn rebalance(mut deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Response, RebalanceError> {
     use RebalanceError::*;
 
+    match check_circuit_breaker(deps_mut.branch(), &env) {
+        CircuitBreakerCheck::Clear => {},
+        CircuitBreakerCheck::JustTripped => return Ok(circuit_breaker_tripped_response()),
+        CircuitBreakerCheck::Tripped => return Err(CircuitBreakerTripped {})
+    }
+
+    let management_fee_res = accrue_management_fee(deps_mut.branch(), &env);
     let deps = deps_mut.as_ref();
 
     // Invariant: Any state will be initialized after instantation.
@@ -166,7 +252,13 @@ pub fn rebalance(deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Respo
     let pool_id = vault_info.pool_id.clone();
     let price = pool_id.price(&deps.querier);
 
-    can_rebalance(deps, env.clone(), info)?;
+    let keeper = info.sender.clone();
+    let RebalanceAllowance { passed_policy, by_fallback } = can_rebalance(&info.sender, deps, &env)?;
+    let res = management_fee_res
+        .add_attributes(passed_policy.map(|x| ("rebalance_policy", x)))
+        .add_attributes(by_fallback.then_some(("fallback_rebalancer", "active")));
+    vault_state.last_rebalance_by_fallback = by_fallback;
+    let is_public = by_fallback || matches!(vault_info.rebalancer, VaultRebalancer::Anyone { .. });
 
     // NOTE: We always update `LastPriceAndTimestamp` even if theyre not used, for
     //       semantical simplicity of the variable.
@@ -174,26 +266,137 @@ pub fn rebalance(deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Respo
         last_price: price, last_timestamp: env.block.time
     });
 
-    let VaultParameters {
-        base_factor, limit_factor, full_range_weight
-    } = VAULT_PARAMETERS.load(deps.storage).unwrap();
+    // NOTE: The steps of a gradual rebalance count as a single rebalance.
+    if vault_state.rebalance_in_progress.is_none() {
+        record_recent_rebalance(&mut vault_state, &env);
+    }
 
-    let VaultBalancesResponse { 
-        bal0, bal1,
-        protocol_unclaimed_fees0, protocol_unclaimed_fees1,
-        admin_unclaimed_fees0, admin_unclaimed_fees1
-    } = query::vault_balances(deps);
+    let rebalance_step = VAULT_PARAMETERS.load(deps.storage).unwrap().rebalance_step;
+
+    let vault_balances = query::vault_balances(deps, &env);
+    let VaultBalancesResponse { bal0, bal1, .. } = vault_balances;
 
     if bal0.is_zero() && bal1.is_zero() {
         return Err(NothingToRebalance {});
     }
 
+    let liquidity_left = vault_state.rebalance_in_progress
+        .as_ref()
+        .map(|x| x.liquidity_left.clone())
+        .unwrap_or(Weight::max());
+
+    // NOTE: Once less than a step of liquidity is left in the old positions,
+    //       we just do a normal rebalance with all vault funds.
+    let is_gradual_step = vault_state.has_positions() && rebalance_step.0 < liquidity_left.0;
+    let closed_proportion = if is_gradual_step {
+        step_proportion(&rebalance_step, &liquidity_left)
+    } else { Weight::max() };
+    let mut record = rebalance_record(
+        &keeper, price, &vault_info, &vault_balances, &closed_proportion, deps, &env
+    );
+
+    if is_gradual_step {
+        record_rebalance(record, deps_mut.storage);
+        let performance_fee_res = charge_performance_fee(bal0, bal1, &price, deps_mut.branch(), &env);
+        let res = partial_rebalance(
+            res.add_attributes(performance_fee_res.attributes),
+            deps_mut.branch(),
+            env.clone(),
+            vault_state,
+            &vault_balances,
+            &rebalance_step,
+            &liquidity_left
+        );
+        record_delegated_call(&keeper, deps_mut.storage, &env);
+        return Ok(res.add_messages(pay_keeper_reward(keeper, is_public, deps_mut)))
+    }
+
     if price.is_zero() {
         // NOTE: If the pool has no price, we should be able to deposit 
         //       in any proportion. But we keep things simple for the v1.
         return Err(PoolWithoutPrice(pool_id.0));
     }
 
+    let current_tick = vault_info.current_tick(&deps.querier);
+    let planned_positions = plan_positions(bal0, bal1, price, current_tick, deps, &env);
+    record.opened_positions = planned_positions
+        .iter()
+        .map(|(position_type, msg)| {
+            let (amount0, amount1) = query::provided_amounts(msg, deps);
+            PositionSnapshot {
+                position_type: position_type.clone(),
+                lower_tick: msg.lower_tick,
+                upper_tick: msg.upper_tick,
+                amount0,
+                amount1
+            }
+        })
+        .collect();
+
+    let new_position_msgs: Vec<_> = planned_positions
+        .into_iter()
+        .map(create_position_submsg)
+        .collect();
+
+    let liquidity_removal_msgs: Vec<_> = vec![
+        remove_liquidity_msg(PositionType::FullRange, deps, &env, &Weight::max()),
+        remove_liquidity_msg(PositionType::Base, deps, &env, &Weight::max()),
+        remove_liquidity_msg(PositionType::Limit, deps, &env, &Weight::max()),
+    ].into_iter().flatten().collect();
+
+    // Invariant: Wont panic as all types are proper.
+    VAULT_STATE.save(deps_mut.storage, &vault_state.without_positions()).unwrap();
+
+    FUNDS_INFO.update(deps_mut.storage, |_| -> StdResult<_> {
+        Ok(FundsInfo::default())
+    }).unwrap();
+
+    accrue_unclaimed_fees(&vault_balances, deps_mut.branch());
+    record_rebalance(record, deps_mut.storage);
+    record_delegated_call(&keeper, deps_mut.storage, &env);
+    let performance_fee_res = charge_performance_fee(bal0, bal1, &price, deps_mut.branch(), &env);
+    let keeper_reward_msg = pay_keeper_reward(keeper, is_public, deps_mut);
+
+    let position_ids = liquidity_removal_msgs
+        .iter()
+        .map(|msg| msg.position_id)
+        .collect();
+
+    let rewards_claim_msg = MsgCollectSpreadRewards {
+        position_ids,
+        sender: env.contract.address.into(),
+    };
+
+    Ok(res
+        .add_attributes(performance_fee_res.attributes)
+        .add_message(rewards_claim_msg)
+        .add_messages(liquidity_removal_msgs)
+        .add_messages(keeper_reward_msg)
+        .add_submessages(new_position_msgs)
+    )
+}
+
+/// Splits `bal0` and `bal1` into the vault positions at `price`, as described in
+/// the whitepaper. `current_tick` is the pool tick at `price`, next to which the
+/// limit position is created.
+///
+/// # Returns
+///
+/// The creation messages of the new positions, along with their types.
+pub fn plan_positions(
+    bal0: Uint128,
+    bal1: Uint128,
+    price: Decimal,
+    current_tick: i32,
+    deps: Deps,
+    env: &Env
+) -> Vec<(PositionType, MsgCreatePosition)> {
+    // Invariant: Any state will be initialized after instantation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let VaultParameters {
+        base_factor, limit_factor, full_range_weight, ..
+    } = VAULT_PARAMETERS.load(deps.storage).unwrap();
+
     let (balanced_balance0, balanced_balance1) = {
         let bal0 = Decimal::new(bal0);
         let bal1 = Decimal::new(bal1);
@@ -252,9 +455,7 @@ pub fn rebalance(deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Respo
     //            uses full range positions, then both balances for the full
     //            range position shouldnt be zero, or the resulting position
     //            wouldnt be in proportion.
-    if full_range_weight.is_zero() {
-        assert!(full_range_balance0.is_zero() && full_range_balance1.is_zero());
-    } else if balanced_balance1.is_zero() || balanced_balance0.is_zero() {
+    if full_range_weight.is_zero() || balanced_balance1.is_zero() || balanced_balance0.is_zero() {
         assert!(full_range_balance0.is_zero() && full_range_balance1.is_zero());
     } else {
         assert!(!full_range_balance0.is_zero() && !full_range_balance1.is_zero());
@@ -290,7 +491,7 @@ pub fn rebalance(deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Respo
         (limit_balance0, limit_balance1)
     };
 
-    let mut new_position_msgs: Vec<SubMsg> = vec![];
+    let mut new_position_msgs = vec![];
 
     // If `full_range_balance0` is not zero, we already checked that neither
     // `full_range_balance1` will be. If they happened to be zero, it means that
@@ -300,17 +501,14 @@ pub fn rebalance(deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Respo
         let lower_tick = vault_info.min_valid_tick(&deps.querier);
         let upper_tick = vault_info.max_valid_tick(&deps.querier);
 
-        new_position_msgs.push(SubMsg::reply_on_success(
-            create_position_msg(
-                lower_tick,
-                upper_tick,
-                full_range_balance0,
-                full_range_balance1,
-                deps,
-                &env,
-            ),
-            0,
-        ))
+        new_position_msgs.push((PositionType::FullRange, create_position_msg(
+            lower_tick,
+            upper_tick,
+            full_range_balance0,
+            full_range_balance1,
+            deps,
+            env,
+        )))
     }
 
     // We just checked that if `base_range_balance0` is not zero, neither
@@ -323,297 +521,1237 @@ pub fn rebalance(deps_mut: DepsMut, env: Env, info: MessageInfo) -> Result<Respo
         let lower_tick = price_function_inv(&lower_price);
         let upper_tick = price_function_inv(&upper_price);
 
-        new_position_msgs.push(SubMsg::reply_on_success(
-            create_position_msg(
-                lower_tick,
-                upper_tick,
-                base_range_balance0,
-                base_range_balance1,
-                deps,
-                &env,
-            ),
-            1,
-        ))
+        new_position_msgs.push((PositionType::Base, create_position_msg(
+            lower_tick,
+            upper_tick,
+            base_range_balance0,
+            base_range_balance1,
+            deps,
+            env,
+        )))
     }
     
-    if !limit_factor.is_one() && (!limit_balance0.is_zero() || !limit_balance1.is_zero()) {
-        if limit_balance0.is_zero() {
-            // Invariant: `limit_factor > 1`, thus wont panic.
-            let lower_price = price.checked_div(limit_factor.0).unwrap();
-            let lower_tick = price_function_inv(&lower_price);
-
-            // Invariant: Ticks nor Ticks spacings will ever be large enough to
-            //            overflow out of `i32`.
-            let upper_tick = vault_info
-                .current_tick(&deps.querier)
-                .checked_sub(vault_info.tick_spacing(&deps.querier))
-                .unwrap();
-
-            new_position_msgs.push(SubMsg::reply_on_success(
-                create_position_msg(
-                    lower_tick,
-                    upper_tick,
-                    Decimal::zero(),
-                    limit_balance1,
-                    deps,
-                    &env,
-                ),
-                2,
-            ))
-        } else if limit_balance1.is_zero() {
-            let upper_price = price.checked_mul(limit_factor.0).unwrap_or(Decimal::MAX);
-            let upper_tick = price_function_inv(&upper_price);
-
-            // Invariant: Ticks nor Ticks spacings will never be large enough to
-            //            overflow out of `i32`.
-            let lower_tick = vault_info
-                .current_tick(&deps.querier)
-                .checked_add(vault_info.tick_spacing(&deps.querier))
-                .unwrap();
-
-            new_position_msgs.push(SubMsg::reply_on_success(
-                create_position_msg(
-                    lower_tick,
-                    upper_tick,
-                    limit_balance0,
-                    Decimal::zero(),
-                    deps,
-                    &env,
-                ),
-                2,
-            ))
-        } else {
-            // Invariant: Both limit balances cant be non zero, or the resutling position
-            //            wouldnt be a limit position. 
-            // Proof: Assume that wasnt the case due to, for example, roundings during 
-            //        divisions. That would immediately break the invariants stated directly
-            //        after `balanced_balance0` and `balanced_balance1` computation, whose
-            //        proofs are trivial.
-            unreachable!()
-        }
-    }
+    new_position_msgs.extend(limit_position_msg(
+        limit_balance0, limit_balance1, &limit_factor, price, current_tick, deps, env
+    ).map(|msg| (PositionType::Limit, msg)));
 
-    let liquidity_removal_msgs: Vec<_> = vec![
-        remove_liquidity_msg(PositionType::FullRange, deps, &env, &Weight::max()),
-        remove_liquidity_msg(PositionType::Base, deps, &env, &Weight::max()),
-        remove_liquidity_msg(PositionType::Limit, deps, &env, &Weight::max()),
-    ].into_iter().flatten().collect();
+    new_position_msgs
+}
 
-    // Invariant: Wont panic as all types are proper.
-    VAULT_STATE.save(deps_mut.storage, &VaultState { 
-        last_price_and_timestamp: vault_state.last_price_and_timestamp,
-        ..VaultState::default()
-    }).unwrap();
+/// Reply ids are the ones handled by `contract::reply`.
+fn create_position_submsg((position_type, msg): (PositionType, MsgCreatePosition)) -> SubMsg {
+    let id = match position_type {
+        PositionType::FullRange => 0,
+        PositionType::Base => 1,
+        PositionType::Limit => 2
+    };
+    SubMsg::reply_on_success(msg, id)
+}
 
-    FUNDS_INFO.update(deps_mut.storage, |_| -> StdResult<_> {
-        Ok(FundsInfo::default())
-    }).unwrap();
+/// # Returns
+///
+/// - `None`: If the vault has no limit position, or theres nothing to put in it.
+/// - `Some(_)`: The limit position creation message otherwise. Its range will be
+///   next to `price`, thus only one of the balances can be non zero.
+fn limit_position_msg(
+    limit_balance0: Decimal,
+    limit_balance1: Decimal,
+    limit_factor: &PriceFactor,
+    price: Decimal,
+    current_tick: i32,
+    deps: Deps,
+    env: &Env
+) -> Option<MsgCreatePosition> {
+    if limit_factor.is_one() || (limit_balance0.is_zero() && limit_balance1.is_zero()) {
+        return None
+    }
 
-    // Invariant: Any addition of tokens wont overflow, because for that the token
-    //            max supply would have to be above `Uint128::MAX`, but thats impossible.
-    FEES_INFO.update(deps_mut.storage, |mut info| -> StdResult<_> { 
-        info.protocol_tokens0_owned = info.protocol_tokens0_owned
-            .checked_add(protocol_unclaimed_fees0)?;
-        info.protocol_tokens1_owned = info.protocol_tokens1_owned
-            .checked_add(protocol_unclaimed_fees1)?;
-        info.admin_tokens0_owned = info.admin_tokens0_owned
-            .checked_add(admin_unclaimed_fees0)?;
-        info.admin_tokens1_owned = info.admin_tokens1_owned
-            .checked_add(admin_unclaimed_fees1)?;
-        Ok(info)
-    }).unwrap();
+    // Invariant: Any state will be initialized after instantation.
+    let tick_spacing = VAULT_INFO.load(deps.storage).unwrap().tick_spacing(&deps.querier);
 
-    let position_ids = liquidity_removal_msgs
-        .iter()
-        .map(|msg| msg.position_id)
-        .collect();
+    if limit_balance0.is_zero() {
+        // Invariant: `limit_factor > 1`, thus wont panic.
+        let lower_price = price.checked_div(limit_factor.0).unwrap();
+        let lower_tick = price_function_inv(&lower_price);
 
-    let rewards_claim_msg = MsgCollectSpreadRewards {
-        position_ids,
-        sender: env.contract.address.into(),
-    };
+        // Invariant: Ticks nor Ticks spacings will ever be large enough to
+        //            overflow out of `i32`.
+        let upper_tick = current_tick.checked_sub(tick_spacing).unwrap();
+
+        Some(create_position_msg(
+            lower_tick,
+            upper_tick,
+            Decimal::zero(),
+            limit_balance1,
+            deps,
+            env,
+        ))
+    } else if limit_balance1.is_zero() {
+        let upper_price = price.checked_mul(limit_factor.0).unwrap_or(Decimal::MAX);
+        let upper_tick = price_function_inv(&upper_price);
 
-    Ok(Response::new()
-        .add_message(rewards_claim_msg)
-        .add_messages(liquidity_removal_msgs)
-        .add_submessages(new_position_msgs)
-    )
+        // Invariant: Ticks nor Ticks spacings will never be large enough to
+        //            overflow out of `i32`.
+        let lower_tick = current_tick.checked_add(tick_spacing).unwrap();
+
+        Some(create_position_msg(
+            lower_tick,
+            upper_tick,
+            limit_balance0,
+            Decimal::zero(),
+            deps,
+            env,
+        ))
+    } else {
+        // Invariant: Both limit balances cant be non zero, or the resutling position
+        //            wouldnt be a limit position. 
+        // Proof: Assume that wasnt the case due to, for example, roundings during 
+        //        divisions. That would immediately break the invariants stated directly
+        //        after `balanced_balance0` and `balanced_balance1` computation in
+        //        `plan_positions`, whose proofs are trivial. And `rebalance_limit` only
+        //        ever passes one of the balances.
+        unreachable!()
+    }
 }
 
-fn can_rebalance(deps: Deps, env: Env, info: MessageInfo) -> Result<(), RebalanceError> {
+pub fn rebalance_limit(mut deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, RebalanceError> {
     use RebalanceError::*;
-    
-    // Invariant: Any state is always present after instantition.
+
+    can_operate(RebalancerPermission::LimitOnly {}, &info.sender, deps.as_ref(), &env)?;
+
+    match check_circuit_breaker(deps.branch(), &env) {
+        CircuitBreakerCheck::Clear => {},
+        CircuitBreakerCheck::JustTripped => return Ok(circuit_breaker_tripped_response()),
+        CircuitBreakerCheck::Tripped => return Err(CircuitBreakerTripped {})
+    }
+
+    // Invariant: Any state is always present after instantiation.
     let vault_info = VAULT_INFO.load(deps.storage).unwrap();
-    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    let mut vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    let VaultParameters { limit_factor, .. } = VAULT_PARAMETERS.load(deps.storage).unwrap();
+
+    if vault_state.rebalance_in_progress.is_some() {
+        return Err(RebalanceInProgress {})
+    }
+    check_rebalance_limits(&vault_info, &vault_state, &env)?;
+
+    if vault_state.limit_position_id.is_none() {
+        return Err(NoLimitPosition {})
+    }
+
     let price = vault_info.pool_id.price(&deps.querier);
-    let twap_price = vault_info.pool_id.twap(&deps.querier, &env).ok_or(PoolWasJustCreated())?;
-    
-    match vault_info.rebalancer {
-        VaultRebalancer::Admin { } => {
-            // Invariant: The rebalancer cant be `Admin` if admin is not present.
-            let admin = vault_info.admin.clone().unwrap();
-            if admin != info.sender {
-                return Err(UnauthorhizedNonAdminAccount { 
-                    admin: admin.into(), got: info.sender.into() 
-                })
-            }
-        },
-        VaultRebalancer::Delegate { ref rebalancer } => {
-            if rebalancer != info.sender {
-                return Err(UnauthorizedDelegateAccount { 
-                    delegate: rebalancer.into(), got: info.sender.into() 
-                })
-            }
-        },
-        VaultRebalancer::Anyone { 
-            ref price_factor_before_rebalance,
-            time_before_rabalance 
-        } => {
-            if let Some(StateSnapshot {
-                last_price,
-                last_timestamp
-            }) = vault_state.last_price_and_timestamp {
-                let current_time = env.block.time;
-                assert!(current_time.plus_seconds(1) > last_timestamp);
-                if current_time == last_timestamp {
-                    return Err(CantRebalanceTwicePerBlock())
-                }
+    let privileged = !matches!(vault_info.rebalancer, VaultRebalancer::Anyone { .. });
+    price_is_fair(price, privileged, &vault_info, deps.as_ref(), &env)?;
 
-                let threshold = last_timestamp.plus_seconds(time_before_rabalance.seconds());
-                if threshold > current_time {
-                    let time_left = threshold.minus_seconds(current_time.seconds()).seconds();
-                    return Err(NotEnoughTimePassed { time_left })
-                }
+    let limit = query::position_balances_with_fees(PositionType::Limit, deps.as_ref());
+    if !limit.bal0.is_zero() && !limit.bal1.is_zero() {
+        return Err(LimitPositionInRange {})
+    }
 
-                let upper_bound = last_price
-                    .checked_mul(price_factor_before_rebalance.0)
-                    .unwrap_or(Decimal::MAX)
-                    .checked_sub(Decimal::raw(1))
-                    .unwrap_or(Decimal::MIN);
-
-                // Invariant: Wont overflow as price factors are always greater or equal to 1
-                let lower_bound = last_price
-                    .checked_div(price_factor_before_rebalance.0)
-                    .unwrap()
-                    .checked_add(Decimal::raw(1))
-                    .unwrap_or(Decimal::MAX);
-
-                if (lower_bound..=upper_bound).contains(&price) {
-                    return Err(PriceHasntMovedEnough { 
-                        price: price.atomics(),
-                        factor: price_factor_before_rebalance.0.atomics() 
-                    })
-                }
+    let vault_balances = query::vault_balances(deps.as_ref(), &env);
+    let liquidity_removal_msg = remove_liquidity_msg(
+        PositionType::Limit, deps.as_ref(), &env, &Weight::max()
+    );
+    let rewards_claim_msg = collect_spread_rewards(deps.branch(), &env, &vault_balances);
+
+    // Invariant: Wont overflow, as for that token supplies would
+    //            have to be above `Uint128::MAX`.
+    let FundsInfo { available_balance0, available_balance1 } = FUNDS_INFO.load(deps.storage).unwrap();
+    let available_balance0 = available_balance0.checked_add(limit.bal0).unwrap();
+    let available_balance1 = available_balance1.checked_add(limit.bal1).unwrap();
+
+    // NOTE: As the price is outside of its range, the limit position only holds one
+    //       of the tokens. The new one will hold all vault idle funds of that token.
+    let (limit_balance0, limit_balance1) = if limit.bal1.is_zero() {
+        (available_balance0, Uint128::zero())
+    } else {
+        (Uint128::zero(), available_balance1)
+    };
 
-                // Invariant: Wont panic, as 0.01 is a valid weight.
-                let twap_variation = Weight::permille(10).unwrap().mul_dec(&twap_price);
-                let max_twap = twap_price.checked_add(twap_variation).unwrap_or(Decimal::MAX);
-                // Invariant: Wont underflow as `twap_price*0.01 < twap_price`.
-                let min_twap = twap_price.checked_sub(twap_variation).unwrap();
-                if !(min_twap..=max_twap).contains(&price) {
-                    return Err(PriceMovedTooMuchInLastMinute { 
-                        price: price.atomics(),
-                        twap: twap_price.atomics()
-                    })
-                }
-            }
-            
+    let new_position_msg = limit_position_msg(
+        Decimal::new(limit_balance0),
+        Decimal::new(limit_balance1),
+        &limit_factor,
+        price,
+        vault_info.current_tick(&deps.querier),
+        deps.as_ref(),
+        &env
+    ).map(|msg| create_position_submsg((PositionType::Limit, msg)));
+
+    // Invariant: Wont underflow, as the limit balances are taken from the available ones.
+    let funds = match new_position_msg {
+        Some(_) => FundsInfo {
+            available_balance0: available_balance0.checked_sub(limit_balance0).unwrap(),
+            available_balance1: available_balance1.checked_sub(limit_balance1).unwrap()
         },
+        None => FundsInfo { available_balance0, available_balance1 }
     };
-    Ok(())
-}
 
-/// # Returns
-///
-/// - `None`: If `liquidity_proportion == 0` or `for_position` has no open position.
-/// - `Some(_)`: Otherwise.
-pub fn remove_liquidity_msg(
-    for_position: PositionType,
-    deps: Deps,
-    env: &Env,
-    liquidity_proportion: &Weight,
-) -> Option<MsgWithdrawPosition> {
-    if liquidity_proportion.is_zero() { return None }
+    // Invariant: Wont panic as all types are proper.
+    FUNDS_INFO.save(deps.storage, &funds).unwrap();
+    record_recent_rebalance(&mut vault_state, &env);
+    VAULT_STATE.save(deps.storage, &VaultState { limit_position_id: None, ..vault_state }).unwrap();
+    record_delegated_call(&info.sender, deps.storage, &env);
 
-    // Invariant: After instantiation, `VAULT_STATE` is always present.
-    let position_id = VAULT_STATE
-        .load(deps.storage)
-        .unwrap()
-        .from_position_type(for_position)?;
+    Ok(Response::new()
+        .add_message(rewards_claim_msg)
+        .add_messages(liquidity_removal_msg)
+        .add_submessages(new_position_msg)
+    )
+}
 
-    // Invariant: We know that if `position_id` is in the state, then
-    //            it refers to a valid `FullPositionBreakdown`.
-    let position_liquidity = do_some!(PositionByIdRequest { position_id }
-        .query(&deps.querier).ok()?
-        .position?
-        .position?
-        .liquidity
-    ).unwrap();
+pub fn compound(deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, RebalanceError> {
+    can_operate(RebalancerPermission::Compound {}, &info.sender, deps.as_ref(), &env)?;
 
-    // Invariant: We know any position liquidity is a valid Decimal.
-    let position_liquidity = liquidity_proportion
-        .mul_dec(&Decimal::from_str(&position_liquidity).unwrap())
-        .atomics()
-        .to_string();
+    // Invariant: Any state is always present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let mut vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    check_rebalance_limits(&vault_info, &vault_state, &env)?;
+    record_recent_rebalance(&mut vault_state, &env);
+    VAULT_STATE.save(deps.storage, &vault_state).unwrap();
 
-    Some(MsgWithdrawPosition {
-        position_id,
-        sender: env.contract.address.clone().into(),
-        liquidity_amount: position_liquidity,
-    })
+    let vault_balances = query::vault_balances(deps.as_ref(), &env);
+    record_delegated_call(&info.sender, deps.storage, &env);
+    let rewards_claim_msg = collect_spread_rewards(deps, &env, &vault_balances);
+
+    Ok(Response::new().add_message(rewards_claim_msg))
 }
 
-pub fn create_position_msg(
-    lower_tick: i32,
-    upper_tick: i32,
-    tokens_provided0: Decimal,
-    tokens_provided1: Decimal,
-    deps: Deps,
-    env: &Env,
-) -> MsgCreatePosition {
-    use osmosis_std::types::cosmos::base::v1beta1::Coin;
+pub fn harvest_incentives(mut deps: DepsMut, env: Env, info: MessageInfo) -> Result<Response, RebalanceError> {
+    use RebalanceError::*;
+    can_operate(RebalancerPermission::Compound {}, &info.sender, deps.as_ref(), &env)?;
 
-    // Invariant: Any state will be initialized after instantation.
-    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
-    let pool = vault_info.pool(&deps.querier);
+    match check_circuit_breaker(deps.branch(), &env) {
+        CircuitBreakerCheck::Clear => {},
+        CircuitBreakerCheck::JustTripped => return Ok(circuit_breaker_tripped_response()),
+        CircuitBreakerCheck::Tripped => return Err(CircuitBreakerTripped {})
+    }
 
-    let tokens_provided = vec![
-        Coin { denom: pool.token0.clone(), amount: raw(&tokens_provided0) },
-        Coin { denom: pool.token1.clone(), amount: raw(&tokens_provided1) },
-    ].into_iter().filter(|c| c.amount != "0").collect();
+    // Invariant: Any state is present after instantiation.
+    let denom0 = VAULT_INFO.load(deps.storage).unwrap().demon0(&deps.querier);
+    let routes: Vec<_> = HARVEST_ROUTES
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap();
+
+    let mut swaps = vec![];
+    for (denom, route) in routes {
+        let amount = query::harvestable_amount(&denom, deps.as_ref(), &env);
+        if amount.is_zero() { continue }
+
+        let token_out_min_amount = route
+            .min_amount_out(&denom, amount, &deps.querier, &env)
+            .ok_or(HarvestTwapUnavailable { denom: denom.clone(), seconds: route.twap_seconds })?;
+
+        let swap = MsgSwapExactAmountIn {
+            sender: env.contract.address.clone().into(),
+            routes: route.hops.iter().map(|hop| SwapAmountInRoute {
+                pool_id: hop.pool_id,
+                token_out_denom: hop.token_out_denom.clone()
+            }).collect(),
+            token_in: Some(coin(amount.into(), &denom).into()),
+            token_out_min_amount: token_out_min_amount.to_string()
+        };
+
+        // NOTE: Swap replies credit the proceeds to the vault, see `contract::reply`.
+        let reply_id = if route.token_out_denom() == denom0 { 3 } else { 4 };
+        swaps.push(SubMsg::reply_on_success(swap, reply_id));
+    }
 
-    let lower_tick = vault_info.closest_valid_tick(lower_tick, &deps.querier).into();
-    let upper_tick = vault_info.closest_valid_tick(upper_tick, &deps.querier).into();
+    if swaps.is_empty() {
+        return Err(NothingToHarvest {})
+    }
 
-    // Invariant: Wont panic as the const is in [0, 1].
-    let slippage = Weight::try_from(POSITION_CREATION_SLIPPAGE).unwrap();
+    record_delegated_call(&info.sender, deps.storage, &env);
+    Ok(Response::new().add_submessages(swaps))
+}
 
-    MsgCreatePosition {
-        pool_id: pool.id,
-        sender: env.contract.address.clone().into(),
-        lower_tick,
-        upper_tick,
-        tokens_provided,
-        token_min_amount0: raw(&slippage.mul_dec(&tokens_provided0)),
-        token_min_amount1: raw(&slippage.mul_dec(&tokens_provided1)),
+/// Credits `amount` of harvested vault tokens to the vault funds, taking the
+/// protocol and admin cuts as for spread fees in [`query::vault_balances`].
+pub fn credit_harvest(amount: Uint128, is_token0: bool, deps: DepsMut) {
+    // Invariant: Any state is present after instantiation.
+    let mut fees = FEES_INFO.load(deps.storage).unwrap();
+    let mut funds = FUNDS_INFO.load(deps.storage).unwrap();
+
+    let protocol_cut = fees.protocol_fee.0.mul_raw(amount).atomics();
+    let admin_cut = fees.admin_fee.0.mul_raw(amount).atomics();
+
+    // Invariant: Wont underflow, as both fees are capped by `MAX_PROTOCOL_FEE`,
+    //            and thus add up to less than one. Additions wont overflow, as
+    //            for that the token supply would have to be above `Uint128::MAX`.
+    let vault_amount = amount.checked_sub(protocol_cut + admin_cut).unwrap();
+
+    if is_token0 {
+        fees.protocol_tokens0_owned += protocol_cut;
+        fees.admin_tokens0_owned += admin_cut;
+        funds.available_balance0 += vault_amount;
+    } else {
+        fees.protocol_tokens1_owned += protocol_cut;
+        fees.admin_tokens1_owned += admin_cut;
+        funds.available_balance1 += vault_amount;
     }
+
+    FEES_INFO.save(deps.storage, &fees).unwrap();
+    FUNDS_INFO.save(deps.storage, &funds).unwrap();
+
+    // Invariant: Any state is present after instantiation.
+    let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
+    let denom = if is_token0 { denom0 } else { denom1 };
+    let storage = deps.storage;
+    record_revenue(&denom, amount, |x, amount| x.incentives_harvested += amount, storage);
+    record_revenue(&denom, protocol_cut, |x, amount| x.protocol_fees_accrued += amount, storage);
+    record_revenue(&denom, admin_cut, |x, amount| x.admin_fees_accrued += amount, storage);
 }
 
-pub fn withdraw(
-    WithdrawMsg {
-        shares,
-        amount0_min,
+/// Mints the admin performance fee shares if the share price, for vault balances
+/// `bal0` and `bal1` at `price`, is above the high-water mark. Should be called
+/// after the spread fees are accrued.
+pub fn charge_performance_fee(
+    bal0: Uint128,
+    bal1: Uint128,
+    price: &Decimal,
+    deps: DepsMut,
+    env: &Env
+) -> Response {
+    // Invariant: Any state is present after instantiation.
+    let mut fees = FEES_INFO.load(deps.storage).unwrap();
+    let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
+
+    let Some(performance_fee) = fees.performance_fee.as_mut() else { return Response::new() };
+    if price.is_zero() { return Response::new() }
+
+    let value = match performance_fee.reference_token {
+        ReferenceToken::Token0 {} => value_in_token0(bal0, bal1, price),
+        ReferenceToken::Token1 {} => value_in_token1(bal0, bal1, price)
+    };
+    let shares = performance_fee.charge(value, total_supply);
+
+    // Invariant: Wont panic as all types are proper.
+    FEES_INFO.save(deps.storage, &fees).unwrap();
+
+    mint_admin_fee_shares(shares, "performance_fee_shares", deps, env)
+}
+
+/// Mints the admin management fee shares for the time passed since the last
+/// accrual. Should be called before any deposit, withdrawal or rebalance.
+pub fn accrue_management_fee(deps: DepsMut, env: &Env) -> Response {
+    // Invariant: Any state is present after instantiation.
+    let mut fees = FEES_INFO.load(deps.storage).unwrap();
+    let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
+
+    let Some(management_fee) = fees.management_fee.as_mut() else { return Response::new() };
+    let shares = management_fee.accrue(env.block.time, total_supply);
+
+    // Invariant: Wont panic as all types are proper.
+    FEES_INFO.save(deps.storage, &fees).unwrap();
+    mint_admin_fee_shares(shares, "management_fee_shares", deps, env)
+}
+
+fn mint_admin_fee_shares(shares: Uint128, attribute: &str, mut deps: DepsMut, env: &Env) -> Response {
+    if shares.is_zero() { return Response::new() }
+
+    // Invariant: Fees paid in shares can only be set while the vault has an admin.
+    let admin = VAULT_INFO.load(deps.storage).unwrap().admin.unwrap();
+
+    // NOTE: An admin not allowed to hold vault shares forfeits them instead.
+    if not_allowed_holder(&[admin.as_str()], deps.as_ref()).is_some() {
+        return Response::new()
+    }
+    sync_rewards(&[admin.as_str()], deps.branch(), env);
+
+    let info = MessageInfo { sender: env.contract.address.clone(), funds: vec![] };
+    // Invariant: Wont panic, as the only allowed minter is this contract itself.
+    execute_mint(deps, env.clone(), info, admin.into(), shares)
+        .unwrap()
+        .add_attribute(attribute, shares)
+}
+
+/// Proportion of the current positions liquidity to move in a step of a gradual
+/// rebalance, where `rebalance_step < liquidity_left`.
+fn step_proportion(rebalance_step: &Weight, liquidity_left: &Weight) -> Weight {
+    // Invariant: Wont panic, as `rebalance_step < liquidity_left <= 1`, thus the
+    //            division is a valid weight.
+    Weight::try_from(rebalance_step.0.checked_div(liquidity_left.0).unwrap()).unwrap()
+}
+
+/// Records the rebalance about to happen, before any of its state changes.
+/// Opened positions are left to be filled by the caller.
+fn rebalance_record(
+    caller: &Addr,
+    price: Decimal,
+    vault_info: &VaultInfo,
+    vault_balances: &VaultBalancesResponse,
+    closed_proportion: &Weight,
+    deps: Deps,
+    env: &Env
+) -> RebalanceRecord {
+    let twap_seconds = vault_info.twap_config(env.block.time).seconds;
+    let closed_positions = query::position_snapshots(closed_proportion, deps);
+    let positions = [PositionType::FullRange, PositionType::Base, PositionType::Limit];
+    let (fees_collected0, fees_collected1) = positions
+        .into_iter()
+        .map(|x| query::position_balances_with_fees(x, deps))
+        .fold((Uint128::zero(), Uint128::zero()), |(acc0, acc1), x| {
+            (acc0.saturating_add(x.bal0_fees), acc1.saturating_add(x.bal1_fees))
+        });
+
+    // NOTE: Fees are credited and positions moved, but the vault balances stay the same.
+    // Invariant: `TOKEN_INFO` will always be present after instantiation.
+    let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
+    let share_price = Decimal::checked_from_ratio(
+        value_in_token1(vault_balances.bal0, vault_balances.bal1, &price),
+        total_supply
+    ).unwrap_or_default();
+
+    RebalanceRecord {
+        price,
+        twap: vault_info.pool_id.twap(&deps.querier, env, twap_seconds),
+        timestamp: env.block.time,
+        caller: caller.clone(),
+        closed_positions,
+        opened_positions: vec![],
+        fees_collected0,
+        fees_collected1,
+        protocol_fees0: vault_balances.protocol_unclaimed_fees0,
+        protocol_fees1: vault_balances.protocol_unclaimed_fees1,
+        admin_fees0: vault_balances.admin_unclaimed_fees0,
+        admin_fees1: vault_balances.admin_unclaimed_fees1,
+        share_price
+    }
+}
+
+fn record_rebalance(record: RebalanceRecord, storage: &mut dyn Storage) {
+    // Invariant: Wont panic, as all records are proper.
+    let sequence_number = REBALANCE_HISTORY
+        .keys(storage, None, None, Order::Descending)
+        .next()
+        .map(|x| x.unwrap() + 1)
+        .unwrap_or(0);
+    REBALANCE_HISTORY.save(storage, sequence_number, &record).unwrap();
+}
+
+/// Moves `rebalance_step` of the liquidity the vault had at the start of the
+/// rebalance out of its positions, and into its idle balances. Also collects
+/// all position fees, as the proportions of the positions change.
+fn partial_rebalance(
+    res: Response,
+    mut deps: DepsMut,
+    env: Env,
+    vault_state: VaultState,
+    vault_balances: &VaultBalancesResponse,
+    rebalance_step: &Weight,
+    liquidity_left: &Weight
+) -> Response {
+    let liquidity_proportion = step_proportion(rebalance_step, liquidity_left);
+    // Invariant: Wont panic, as `rebalance_step < liquidity_left <= 1`, thus
+    //            the subtraction wont underflow.
+    let liquidity_left = Weight::try_from(
+        liquidity_left.0.checked_sub(rebalance_step.0).unwrap()
+    ).unwrap();
+
+    let positions: Vec<_> = [PositionType::FullRange, PositionType::Base, PositionType::Limit]
+        .into_iter()
+        .map(|x| query::position_balances_with_fees(x, deps.as_ref()))
+        .collect();
+
+    // Invariant: Wont overflow, as for that token supplies would have
+    //            to be above `Uint128::MAX`.
+    let (moved_balance0, moved_balance1) = do_me! {
+        let mut moved_balance0 = Uint128::zero();
+        let mut moved_balance1 = Uint128::zero();
+        for pos in positions {
+            moved_balance0 = moved_balance0
+                .checked_add(liquidity_proportion.mul_raw(pos.bal0).atomics())?;
+            moved_balance1 = moved_balance1
+                .checked_add(liquidity_proportion.mul_raw(pos.bal1).atomics())?;
+        }
+        (moved_balance0, moved_balance1)
+    }.unwrap();
+
+    let liquidity_removal_msgs: Vec<_> = vec![
+        remove_liquidity_msg(PositionType::FullRange, deps.as_ref(), &env, &liquidity_proportion),
+        remove_liquidity_msg(PositionType::Base, deps.as_ref(), &env, &liquidity_proportion),
+        remove_liquidity_msg(PositionType::Limit, deps.as_ref(), &env, &liquidity_proportion),
+    ].into_iter().flatten().collect();
+
+    let rewards_claim_msg = collect_spread_rewards(deps.branch(), &env, vault_balances);
+
+    // Invariant: Wont panic as all types are proper.
+    VAULT_STATE.save(deps.storage, &VaultState {
+        rebalance_in_progress: Some(GradualRebalance { liquidity_left }),
+        ..vault_state
+    }).unwrap();
+
+    // Invariant: Wont overflow, see above.
+    FUNDS_INFO.update(deps.storage, |mut funds| -> StdResult<_> {
+        funds.available_balance0 = funds.available_balance0.checked_add(moved_balance0)?;
+        funds.available_balance1 = funds.available_balance1.checked_add(moved_balance1)?;
+        Ok(funds)
+    }).unwrap();
+
+    res
+        .add_message(rewards_claim_msg)
+        .add_messages(liquidity_removal_msgs)
+}
+
+/// Credits the vault share of the spread fees of all positions to [`FUNDS_INFO`],
+/// and the protocol and admin fees and keeper rewards to [`FEES_INFO`].
+///
+/// # Returns
+///
+/// The message to collect the spread fees of all vault positions.
+fn collect_spread_rewards(
+    mut deps: DepsMut,
+    env: &Env,
+    vault_balances: &VaultBalancesResponse
+) -> MsgCollectSpreadRewards {
+    let position_types = [PositionType::FullRange, PositionType::Base, PositionType::Limit];
+
+    // Invariant: Wont panic.
+    // Proof: Subtractions wont underflow, as the protocol and admin fees and
+    //        keeper rewards are always a weighted part of all position fees.
+    //        Additions wont overflow, as for that token supplies would have to
+    //        be above `Uint128::MAX`.
+    let (vault_fees0, vault_fees1) = do_me! {
+        (
+            vault_balances.spread_fees0
+                .checked_sub(vault_balances.protocol_unclaimed_fees0)?
+                .checked_sub(vault_balances.admin_unclaimed_fees0)?
+                .checked_sub(vault_balances.keeper_unclaimed_fees0)?,
+            vault_balances.spread_fees1
+                .checked_sub(vault_balances.protocol_unclaimed_fees1)?
+                .checked_sub(vault_balances.admin_unclaimed_fees1)?
+                .checked_sub(vault_balances.keeper_unclaimed_fees1)?
+        )
+    }.unwrap();
+
+    // Invariant: Wont overflow, see above.
+    FUNDS_INFO.update(deps.storage, |mut funds| -> StdResult<_> {
+        funds.available_balance0 = funds.available_balance0.checked_add(vault_fees0)?;
+        funds.available_balance1 = funds.available_balance1.checked_add(vault_fees1)?;
+        Ok(funds)
+    }).unwrap();
+
+    accrue_unclaimed_fees(vault_balances, deps.branch());
+
+    // Invariant: Any state is always present after instantiation.
+    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+
+    MsgCollectSpreadRewards {
+        position_ids: position_types
+            .into_iter()
+            .filter_map(|x| vault_state.from_position_type(x))
+            .collect(),
+        sender: env.contract.address.to_string(),
+    }
+}
+
+/// Adds `amount` to the lifetime [`RevenueStats`] of `denom` with `record`.
+pub fn record_revenue(
+    denom: &str,
+    amount: Uint128,
+    record: impl Fn(&mut RevenueStats, Uint128),
+    storage: &mut dyn Storage
+) {
+    if amount.is_zero() {
+        return
+    }
+
+    // Invariant: Wont panic as all types are proper.
+    REVENUE_STATS.update(storage, denom, |stats| -> StdResult<_> {
+        let mut stats = stats.unwrap_or_default();
+        record(&mut stats, amount);
+        Ok(stats)
+    }).unwrap();
+}
+
+/// Adds `amount0` and `amount1` to the lifetime [`RevenueStats`] of the vault
+/// tokens with `record`.
+fn record_revenue_pair(
+    amount0: Uint128,
+    amount1: Uint128,
+    record: impl Fn(&mut RevenueStats, Uint128),
+    deps: DepsMut
+) {
+    // Invariant: Any state is present after instantiation.
+    let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
+    record_revenue(&denom0, amount0, &record, deps.storage);
+    record_revenue(&denom1, amount1, &record, deps.storage);
+}
+
+/// Records entry or exit fees not kept by the vault as accrued by their recipient.
+fn record_entry_exit_fees(fees: &FeesInfo, fee0: Uint128, fee1: Uint128, deps: DepsMut) {
+    // Invariant: Wont overflow, as for that the token supply would have to be
+    //            above `Uint128::MAX`. Same for the other revenue additions.
+    match fees.entry_exit_fees.as_ref().map(|x| &x.recipient) {
+        Some(FeeRecipient::Admin {}) =>
+            record_revenue_pair(fee0, fee1, |x, amount| x.admin_fees_accrued += amount, deps),
+        Some(FeeRecipient::Protocol {}) =>
+            record_revenue_pair(fee0, fee1, |x, amount| x.protocol_fees_accrued += amount, deps),
+        Some(FeeRecipient::Vault {}) | None => {}
+    }
+}
+
+/// Commits the protocol and admin fees and keeper rewards of `vault_balances`
+/// to [`FEES_INFO`], recording them and the spread fees they come from in the
+/// [`RevenueStats`].
+fn accrue_unclaimed_fees(vault_balances: &VaultBalancesResponse, mut deps: DepsMut) {
+    // Invariant: Any addition of tokens wont overflow, because for that the token
+    //            max supply would have to be above `Uint128::MAX`, but thats impossible.
+    FEES_INFO.update(deps.storage, |mut info| -> StdResult<_> { 
+        info.protocol_tokens0_owned = info.protocol_tokens0_owned
+            .checked_add(vault_balances.protocol_unclaimed_fees0)?;
+        info.protocol_tokens1_owned = info.protocol_tokens1_owned
+            .checked_add(vault_balances.protocol_unclaimed_fees1)?;
+        info.admin_tokens0_owned = info.admin_tokens0_owned
+            .checked_add(vault_balances.admin_unclaimed_fees0)?;
+        info.admin_tokens1_owned = info.admin_tokens1_owned
+            .checked_add(vault_balances.admin_unclaimed_fees1)?;
+        info.keeper_tokens0_owned = info.keeper_tokens0_owned
+            .checked_add(vault_balances.keeper_unclaimed_fees0)?;
+        info.keeper_tokens1_owned = info.keeper_tokens1_owned
+            .checked_add(vault_balances.keeper_unclaimed_fees1)?;
+        Ok(info)
+    }).unwrap();
+
+    record_revenue_pair(
+        vault_balances.spread_fees0,
+        vault_balances.spread_fees1,
+        |x, amount| x.spread_fees += amount,
+        deps.branch()
+    );
+    record_revenue_pair(
+        vault_balances.protocol_unclaimed_fees0,
+        vault_balances.protocol_unclaimed_fees1,
+        |x, amount| x.protocol_fees_accrued += amount,
+        deps.branch()
+    );
+    record_revenue_pair(
+        vault_balances.admin_unclaimed_fees0,
+        vault_balances.admin_unclaimed_fees1,
+        |x, amount| x.admin_fees_accrued += amount,
+        deps
+    );
+}
+
+/// Pays `keeper` the owed keeper rewards, up to the per call max amounts of
+/// its [`KeeperReward`]. Should be called after the rebalance fees are accrued.
+/// `is_public` is whether `keeper` rebalanced as anyone, rather than as a
+/// privileged rebalancer, which are never paid.
+///
+/// # Returns
+///
+/// - `None`: If theres nothing to pay.
+/// - `Some(_)`: Otherwise.
+fn pay_keeper_reward(keeper: Addr, is_public: bool, deps: DepsMut) -> Option<BankMsg> {
+    if !is_public { return None }
+
+    // Invariant: Any state is always present after instantiation.
+    let mut fees = FEES_INFO.load(deps.storage).unwrap();
+
+    // NOTE: Rewards left from when the rebalancer was `Anyone` are still
+    //       paid to fallback rebalancers, so they dont get stuck in the vault.
+    let (amount0, amount1) = match fees.keeper_reward {
+        Some(ref reward) => reward.payout(fees.keeper_tokens0_owned, fees.keeper_tokens1_owned),
+        None => (fees.keeper_tokens0_owned, fees.keeper_tokens1_owned)
+    };
+
+    if amount0.is_zero() && amount1.is_zero() { return None }
+
+    // Invariant: Wont underflow, as the payout is at most whats owned.
+    fees.keeper_tokens0_owned = fees.keeper_tokens0_owned.checked_sub(amount0).unwrap();
+    fees.keeper_tokens1_owned = fees.keeper_tokens1_owned.checked_sub(amount1).unwrap();
+    // Invariant: Will serialize as all types are proper.
+    FEES_INFO.save(deps.storage, &fees).unwrap();
+
+    let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
+    Some(BankMsg::Send {
+        to_address: keeper.into(),
+        amount: vec![
+            coin(amount0.into(), denom0),
+            coin(amount1.into(), denom1)
+        ].into_iter().filter(|c| !c.amount.is_zero()).collect()
+    })
+}
+
+/// Why a rebalance is allowed, see [`can_rebalance`].
+pub struct RebalanceAllowance {
+    /// Description of the [`RebalancePolicy`] conditions that passed, if any.
+    pub passed_policy: Option<String>,
+    /// Whether the rebalance is only allowed because
+    /// [`VaultInfo::fallback_rebalancer_active`].
+    pub by_fallback: bool
+}
+
+/// Checks whether `sender` can rebalance now, without side effects.
+pub fn can_rebalance(sender: &Addr, deps: Deps, env: &Env) -> Result<RebalanceAllowance, RebalanceError> {
+    use RebalanceError::*;
+    
+    // Invariant: Any state is always present after instantition.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    let price = vault_info.pool_id.price(&deps.querier);
+
+    if circuit_breaker_tripped(&vault_info, &vault_state, price, deps, env) {
+        return Err(CircuitBreakerTripped {})
+    }
+    // NOTE: Continuing a gradual rebalance is exempt from the limits, so it cant stall.
+    if vault_state.rebalance_in_progress.is_none() {
+        check_rebalance_limits(&vault_info, &vault_state, env)?;
+    }
+
+    let unauthorized = match vault_info.rebalancer {
+        VaultRebalancer::Admin { } => {
+            // Invariant: The rebalancer cant be `Admin` if admin is not present.
+            let admin = vault_info.admin.clone().unwrap();
+            (admin != *sender).then(|| UnauthorhizedNonAdminAccount { 
+                admin: admin.into(), got: sender.to_string() 
+            })
+        },
+        VaultRebalancer::Delegate { ref rebalancer } => {
+            (rebalancer != sender).then(|| UnauthorizedDelegateAccount { 
+                delegate: rebalancer.into(), got: sender.to_string() 
+            })
+        },
+        ref conditions @ VaultRebalancer::Anyone { .. } => {
+            let passed_policy = public_rebalance_conditions(
+                conditions, price, &vault_info, &vault_state, deps, env
+            )?;
+            price_is_fair(price, false, &vault_info, deps, env)?;
+            return Ok(RebalanceAllowance { passed_policy, by_fallback: false })
+        }
+    };
+
+    let by_fallback = match unauthorized {
+        None => false,
+        Some(_) if delegated_rebalancer_allowed(
+            &RebalancerPermission::Full {}, sender, deps, env
+        )? => false,
+        Some(_) if vault_info.fallback_rebalancer_active(&vault_state, env.block.time) => true,
+        Some(err) => return Err(err)
+    };
+
+    let passed_policy = if by_fallback {
+        public_rebalance_conditions(
+            &VaultRebalancer::fallback(), price, &vault_info, &vault_state, deps, env
+        )?
+    } else { None };
+
+    price_is_fair(price, !by_fallback, &vault_info, deps, env)?;
+    Ok(RebalanceAllowance { passed_policy, by_fallback })
+}
+
+enum CircuitBreakerCheck {
+    Clear,
+    /// Tripped by this very check, and already saved to [`VAULT_STATE`].
+    JustTripped,
+    Tripped
+}
+
+/// Whether the vault [`crate::state::CircuitBreaker`] is or would be tripped at `price`,
+/// without side effects.
+fn circuit_breaker_tripped(
+    vault_info: &VaultInfo,
+    vault_state: &VaultState,
+    price: Decimal,
+    deps: Deps,
+    env: &Env
+) -> bool {
+    let Some(ref circuit_breaker) = vault_info.circuit_breaker else { return false };
+    // NOTE: If theres no TWAP, theres no deviation to protect from.
+    let Some(twap) = vault_info.pool_id.twap(&deps.querier, env, circuit_breaker.twap_seconds) else {
+        return vault_state.circuit_breaker_tripped
+    };
+
+    let max_deviation = if vault_state.circuit_breaker_tripped {
+        &circuit_breaker.settle_deviation
+    } else {
+        &circuit_breaker.trip_deviation
+    };
+    twap_is_fair(price, twap, max_deviation).is_err()
+}
+
+/// Updates the vault circuit breaker latch to the current price deviation.
+fn check_circuit_breaker(deps: DepsMut, env: &Env) -> CircuitBreakerCheck {
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let mut vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    let price = vault_info.pool_id.price(&deps.querier);
+
+    let was_tripped = vault_state.circuit_breaker_tripped;
+    let tripped = circuit_breaker_tripped(&vault_info, &vault_state, price, deps.as_ref(), env);
+    if tripped != was_tripped {
+        vault_state.circuit_breaker_tripped = tripped;
+        // Invariant: Wont panic as all types are proper.
+        VAULT_STATE.save(deps.storage, &vault_state).unwrap();
+    }
+
+    match (was_tripped, tripped) {
+        (_, false) => CircuitBreakerCheck::Clear,
+        (false, true) => CircuitBreakerCheck::JustTripped,
+        (true, true) => CircuitBreakerCheck::Tripped
+    }
+}
+
+fn circuit_breaker_tripped_response() -> Response {
+    Response::new().add_attribute("circuit_breaker", "tripped")
+}
+
+/// Records a rebalance operation in [`VaultState::recent_rebalances`], dropping
+/// the ones older than a day.
+fn record_recent_rebalance(vault_state: &mut VaultState, env: &Env) {
+    let day_ago = env.block.time.minus_seconds(SECONDS_IN_A_DAY);
+    vault_state.recent_rebalances.retain(|x| *x > day_ago);
+    vault_state.recent_rebalances.push(env.block.time);
+}
+
+/// Checks the [`crate::state::RebalanceLimits`] in effect, which apply to every
+/// rebalancer and to every operation that moves the vault positions or fees.
+fn check_rebalance_limits(
+    vault_info: &VaultInfo,
+    vault_state: &VaultState,
+    env: &Env
+) -> Result<(), RebalanceError> {
+    use RebalanceError::*;
+
+    let now = env.block.time;
+    let limits = vault_info.rebalance_limits(now);
+
+    // NOTE: Limit rebalances and compounds are only recorded in `recent_rebalances`.
+    let last_operation = vault_state.last_price_and_timestamp
+        .as_ref()
+        .map(|snapshot| snapshot.last_timestamp)
+        .into_iter()
+        .chain(vault_state.recent_rebalances.last().copied())
+        .max();
+
+    if let Some(last_operation) = last_operation {
+        let threshold = last_operation.plus_seconds(limits.min_seconds_between_rebalances);
+        if threshold > now {
+            let time_left = threshold.minus_seconds(now.seconds()).seconds();
+            return Err(NotEnoughTimePassed { time_left })
+        }
+    }
+
+    if let Some(max) = limits.max_rebalances_per_day {
+        let day_ago = now.minus_seconds(SECONDS_IN_A_DAY);
+        let recent: Vec<_> = vault_state.recent_rebalances
+            .iter()
+            .filter(|x| **x > day_ago)
+            .collect();
+
+        if recent.len() >= max as usize {
+            // Invariant: `recent` is not empty, as `max > 0`.
+            // NOTE: Rebalances are recorded in order, so the first one is the oldest.
+            let time_left = recent[0]
+                .plus_seconds(SECONDS_IN_A_DAY)
+                .minus_seconds(now.seconds())
+                .seconds();
+            return Err(MaxRebalancesPerDayReached { max, time_left })
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the conditions for anyone to rebalance, where `conditions` is
+/// a [`VaultRebalancer::Anyone`].
+///
+/// # Returns
+///
+/// - `Ok(Some(_))`: If the rebalance is allowed by a [`RebalancePolicy`], with a
+///   description of the conditions that passed.
+/// - `Ok(None)`: If the rebalance is allowed otherwise.
+fn public_rebalance_conditions(
+    conditions: &VaultRebalancer,
+    price: Decimal,
+    vault_info: &VaultInfo,
+    vault_state: &VaultState,
+    deps: Deps,
+    env: &Env
+) -> Result<Option<String>, RebalanceError> {
+    use RebalanceError::*;
+
+    let VaultRebalancer::Anyone { 
+        price_factor_before_rebalance,
+        time_before_rabalance,
+        policy
+    } = conditions else { return Ok(None) };
+
+    let Some(ref snapshot) = vault_state.last_price_and_timestamp else { return Ok(None) };
+    let StateSnapshot { last_price, last_timestamp } = *snapshot;
+    let current_time = env.block.time;
+    assert!(current_time.plus_seconds(1) > last_timestamp);
+    if current_time == last_timestamp {
+        return Err(CantRebalanceTwicePerBlock())
+    }
+
+    // NOTE: The conditions were already met when the gradual rebalance
+    //       started, so the next steps only need a fair price.
+    if vault_state.rebalance_in_progress.is_some() {
+        return Ok(None)
+    }
+
+    if let Some(policy) = policy {
+        return Ok(Some(evaluate_rebalance_policy(
+            policy, snapshot, price, vault_info, deps, env
+        ).map_err(|reason| RebalancePolicyNotMet { reason })?))
+    }
+
+    let threshold = last_timestamp.plus_seconds(time_before_rabalance.seconds());
+    if threshold > current_time {
+        let time_left = threshold.minus_seconds(current_time.seconds()).seconds();
+        return Err(NotEnoughTimePassed { time_left })
+    }
+
+    let (lower_bound, upper_bound) = 
+        price_movement_bounds(&last_price, price_factor_before_rebalance);
+
+    if (lower_bound..=upper_bound).contains(&price) {
+        return Err(PriceHasntMovedEnough { 
+            price: price.atomics(),
+            factor: price_factor_before_rebalance.0.atomics(),
+            lower_bound: lower_bound.atomics(),
+            upper_bound: upper_bound.atomics()
+        })
+    }
+    Ok(None)
+}
+
+/// # Returns
+///
+/// - `Ok(true)`: If `sender` is in [`DELEGATED_REBALANCERS`] and can do `operation` now.
+/// - `Ok(false)`: If `sender` is not in [`DELEGATED_REBALANCERS`].
+/// - `Err(_)`: If `sender` is in [`DELEGATED_REBALANCERS`], but cant do `operation` now.
+fn delegated_rebalancer_allowed(
+    operation: &RebalancerPermission,
+    sender: &Addr,
+    deps: Deps,
+    env: &Env
+) -> Result<bool, RebalanceError> {
+    use RebalanceError::*;
+
+    // Invariant: Wont panic as all types are proper.
+    let rebalancer = match DELEGATED_REBALANCERS.may_load(deps.storage, sender).unwrap() {
+        Some(rebalancer) => rebalancer,
+        None => return Ok(false)
+    };
+
+    if !rebalancer.allows(operation) {
+        return Err(UnauthorizedRebalancer {
+            address: sender.into(), operation: format!("{operation:?}")
+        })
+    }
+
+    if let Some(last_call) = rebalancer.last_call {
+        let threshold = last_call.plus_seconds(rebalancer.min_seconds_between_calls);
+        if threshold > env.block.time {
+            let time_left = threshold.minus_seconds(env.block.time.seconds()).seconds();
+            return Err(NotEnoughTimePassed { time_left })
+        }
+    }
+
+    Ok(true)
+}
+
+/// Sets the last call of `sender` to now, if its in [`DELEGATED_REBALANCERS`].
+fn record_delegated_call(sender: &Addr, storage: &mut dyn Storage, env: &Env) {
+    // Invariant: Wont panic as all types are proper.
+    if let Some(mut rebalancer) = DELEGATED_REBALANCERS.may_load(storage, sender).unwrap() {
+        rebalancer.last_call = Some(env.block.time);
+        DELEGATED_REBALANCERS.save(storage, sender, &rebalancer).unwrap();
+    }
+}
+
+/// Checks that `sender` can do `operation` on the vault positions. The vault
+/// rebalancer can do any operation, unless its `Anyone`.
+fn can_operate(
+    operation: RebalancerPermission,
+    sender: &Addr,
+    deps: Deps,
+    env: &Env
+) -> Result<(), RebalanceError> {
+    // Invariant: Any state is always present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+
+    let is_rebalancer = match vault_info.rebalancer {
+        VaultRebalancer::Admin {} => vault_info.admin.as_ref() == Some(sender),
+        VaultRebalancer::Delegate { ref rebalancer } => rebalancer == sender,
+        VaultRebalancer::Anyone { .. } => false
+    };
+
+    if is_rebalancer || delegated_rebalancer_allowed(&operation, sender, deps, env)? {
+        Ok(())
+    } else {
+        Err(RebalanceError::UnauthorizedRebalancer {
+            address: sender.into(), operation: format!("{operation:?}")
+        })
+    }
+}
+
+/// # Returns
+///
+/// The price range `[lower_bound, upper_bound]` inside of which the price
+/// hasnt moved `price_factor` since `last_price`.
+fn price_movement_bounds(last_price: &Decimal, price_factor: &PriceFactor) -> (Decimal, Decimal) {
+    let upper_bound = last_price
+        .checked_mul(price_factor.0)
+        .unwrap_or(Decimal::MAX)
+        .checked_sub(Decimal::raw(1))
+        .unwrap_or(Decimal::MIN);
+
+    // Invariant: Wont overflow as price factors are always greater or equal to 1
+    let lower_bound = last_price
+        .checked_div(price_factor.0)
+        .unwrap()
+        .checked_add(Decimal::raw(1))
+        .unwrap_or(Decimal::MAX);
+
+    (lower_bound, upper_bound)
+}
+
+/// # Returns
+///
+/// - `Ok(_)`: Description of the conditions that made `policy` pass.
+/// - `Err(_)`: Description of the conditions that made `policy` fail.
+fn evaluate_rebalance_policy(
+    policy: &RebalancePolicy,
+    snapshot: &StateSnapshot,
+    price: Decimal,
+    vault_info: &VaultInfo,
+    deps: Deps,
+    env: &Env
+) -> Result<String, String> {
+    use RebalancePolicy::*;
+    let evaluate = |policy: &RebalancePolicy| {
+        evaluate_rebalance_policy(policy, snapshot, price, vault_info, deps, env)
+    };
+
+    match policy {
+        And(policies) => {
+            let passed = policies.iter().map(evaluate).collect::<Result<Vec<_>, _>>()?;
+            Ok(format!("({})", passed.join(" and ")))
+        },
+        Or(policies) => {
+            let mut failed = vec![];
+            for policy in policies {
+                match evaluate(policy) {
+                    Ok(passed) => return Ok(passed),
+                    Err(reason) => failed.push(reason)
+                }
+            }
+            // NOTE: Only fails if every policy failed, so all reasons are listed.
+            Err(format!("none of ({})", failed.join("; ")))
+        },
+        TimePassed { time } => {
+            let threshold = snapshot.last_timestamp.plus_seconds(time.seconds());
+            if threshold > env.block.time {
+                let time_left = threshold.minus_seconds(env.block.time.seconds()).seconds();
+                Err(format!("{time_left} seconds left before rebalance"))
+            } else {
+                Ok(format!("{} seconds passed", time.seconds()))
+            }
+        },
+        PriceMoved { price_factor } => {
+            let (lower_bound, upper_bound) = price_movement_bounds(&snapshot.last_price, price_factor);
+            if (lower_bound..=upper_bound).contains(&price) {
+                Err(format!("price {price} inside [{lower_bound}, {upper_bound}]"))
+            } else {
+                Ok(format!("price {price} outside [{lower_bound}, {upper_bound}]"))
+            }
+        },
+        PriceOutsideBaseRange {} => {
+            let (lower_tick, upper_tick) = query::position_range(PositionType::Base, deps)
+                .ok_or_else(|| "vault has no base position".to_string())?;
+            let tick: i64 = vault_info.current_tick(&deps.querier).into();
+            if (lower_tick..upper_tick).contains(&tick) {
+                Err(format!("tick {tick} inside base range [{lower_tick}, {upper_tick})"))
+            } else {
+                Ok(format!("tick {tick} outside base range [{lower_tick}, {upper_tick})"))
+            }
+        },
+        LimitOrderFilled {} => {
+            let (lower_tick, upper_tick) = query::position_range(PositionType::Limit, deps)
+                .ok_or_else(|| "vault has no limit position".to_string())?;
+            let tick: i64 = vault_info.current_tick(&deps.querier).into();
+            let last_tick: i64 = price_function_inv(&snapshot.last_price).into();
+            // NOTE: Limit positions are created at one side of the price, and are
+            //       filled once the price crosses them to the other side.
+            let filled = (last_tick >= upper_tick && tick < lower_tick)
+                || (last_tick < lower_tick && tick >= upper_tick);
+            if filled {
+                Ok(format!("tick {tick} crossed limit range [{lower_tick}, {upper_tick})"))
+            } else {
+                Err(format!("tick {tick} didnt cross limit range [{lower_tick}, {upper_tick})"))
+            }
+        },
+        IdleBalanceAbove { share } => {
+            // Invariant: Any state is always present after instantition.
+            let FundsInfo {
+                available_balance0, available_balance1
+            } = FUNDS_INFO.load(deps.storage).unwrap();
+            let VaultBalancesResponse { bal0, bal1, .. } = query::vault_balances(deps, env);
+
+            let idle_value = value_in_token1(available_balance0, available_balance1, &price);
+            let min_idle_value = share.mul_raw(value_in_token1(bal0, bal1, &price)).atomics();
+            if idle_value < min_idle_value {
+                Err(format!("idle value {idle_value} below {min_idle_value}"))
+            } else {
+                Ok(format!("idle value {idle_value} above {min_idle_value}"))
+            }
+        },
+        UncollectedFeesAbove { value } => {
+            let (fees0, fees1) = [PositionType::FullRange, PositionType::Base, PositionType::Limit]
+                .into_iter()
+                .map(|x| query::position_balances_with_fees(x, deps))
+                .fold((Uint128::zero(), Uint128::zero()), |(fees0, fees1), x| (
+                    fees0.saturating_add(x.bal0_fees),
+                    fees1.saturating_add(x.bal1_fees)
+                ));
+
+            let fees_value = value_in_token1(fees0, fees1, &price);
+            if fees_value < *value {
+                Err(format!("fees value {fees_value} below {value}"))
+            } else {
+                Ok(format!("fees value {fees_value} above {value}"))
+            }
+        }
+    }
+}
+
+/// Checks `price` against the pool TWAP, as configured by [`VaultInfo::twap_config`].
+/// `privileged` is whether the caller is the vault rebalancer, rather than anyone.
+fn price_is_fair(
+    price: Decimal,
+    privileged: bool,
+    vault_info: &VaultInfo,
+    deps: Deps,
+    env: &Env
+) -> Result<(), RebalanceError> {
+    let TwapConfig { seconds, max_deviation, on_failure } = vault_info.twap_config(env.block.time);
+
+    match vault_info.pool_id.twap(&deps.querier, env, seconds) {
+        Some(twap_price) => twap_is_fair(price, twap_price, &max_deviation),
+        None => match on_failure {
+            TwapFallback::SkipCheck {} => Ok(()),
+            TwapFallback::PrivilegedOnly {} if privileged => Ok(()),
+            _ => Err(RebalanceError::TwapUnavailable { seconds })
+        }
+    }
+}
+
+fn twap_is_fair(price: Decimal, twap_price: Decimal, max_deviation: &Weight) -> Result<(), RebalanceError> {
+    let twap_variation = max_deviation.mul_dec(&twap_price);
+    let max_twap = twap_price.checked_add(twap_variation).unwrap_or(Decimal::MAX);
+    // Invariant: Wont underflow as `max_deviation` is at most 1.
+    let min_twap = twap_price.checked_sub(twap_variation).unwrap();
+    if !(min_twap..=max_twap).contains(&price) {
+        Err(RebalanceError::PriceDeviatesFromTwap { 
+            price: price.atomics(),
+            twap: twap_price.atomics(),
+            max_deviation: max_deviation.0.atomics()
+        })
+    } else { Ok(()) }
+}
+
+/// # Returns
+///
+/// - `None`: If `liquidity_proportion == 0` or `for_position` has no open position.
+/// - `Some(_)`: Otherwise.
+pub fn remove_liquidity_msg(
+    for_position: PositionType,
+    deps: Deps,
+    env: &Env,
+    liquidity_proportion: &Weight,
+) -> Option<MsgWithdrawPosition> {
+    if liquidity_proportion.is_zero() { return None }
+
+    // Invariant: After instantiation, `VAULT_STATE` is always present.
+    let position_id = VAULT_STATE
+        .load(deps.storage)
+        .unwrap()
+        .from_position_type(for_position)?;
+
+    // Invariant: We know that if `position_id` is in the state, then
+    //            it refers to a valid `FullPositionBreakdown`.
+    let position_liquidity = do_some!(PositionByIdRequest { position_id }
+        .query(&deps.querier).ok()?
+        .position?
+        .position?
+        .liquidity
+    ).unwrap();
+
+    // Invariant: We know any position liquidity is a valid Decimal.
+    let position_liquidity = liquidity_proportion
+        .mul_dec(&Decimal::from_str(&position_liquidity).unwrap())
+        .atomics()
+        .to_string();
+
+    Some(MsgWithdrawPosition {
+        position_id,
+        sender: env.contract.address.clone().into(),
+        liquidity_amount: position_liquidity,
+    })
+}
+
+pub fn create_position_msg(
+    lower_tick: i32,
+    upper_tick: i32,
+    tokens_provided0: Decimal,
+    tokens_provided1: Decimal,
+    deps: Deps,
+    env: &Env,
+) -> MsgCreatePosition {
+    use osmosis_std::types::cosmos::base::v1beta1::Coin;
+
+    // Invariant: Any state will be initialized after instantation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let pool = vault_info.pool(&deps.querier);
+
+    let tokens_provided = vec![
+        Coin { denom: pool.token0.clone(), amount: raw(&tokens_provided0) },
+        Coin { denom: pool.token1.clone(), amount: raw(&tokens_provided1) },
+    ].into_iter().filter(|c| c.amount != "0").collect();
+
+    let lower_tick = vault_info.closest_valid_tick(lower_tick, &deps.querier).into();
+    let upper_tick = vault_info.closest_valid_tick(upper_tick, &deps.querier).into();
+
+    // Invariant: Wont panic as the const is in [0, 1].
+    let slippage = Weight::try_from(POSITION_CREATION_SLIPPAGE).unwrap();
+
+    MsgCreatePosition {
+        pool_id: pool.id,
+        sender: env.contract.address.clone().into(),
+        lower_tick,
+        upper_tick,
+        tokens_provided,
+        token_min_amount0: raw(&slippage.mul_dec(&tokens_provided0)),
+        token_min_amount1: raw(&slippage.mul_dec(&tokens_provided1)),
+    }
+}
+
+pub fn withdraw(
+    WithdrawMsg {
+        shares,
+        amount0_min,
         amount1_min,
         to,
     }: WithdrawMsg,
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
 ) -> Result<Response, WithdrawalError> {
     use WithdrawalError::*;
     if shares.is_zero() { return Err(ZeroSharesWithdrawal {}) }
 
+    let locked_until = query::shares_locked_until(info.sender.as_str(), shares, deps.as_ref(), &env);
+    if let Some(unlocks_at) = locked_until {
+        return Err(SharesLocked { unlocks_at: unlocks_at.seconds() })
+    }
+
+    // NOTE: Withdrawals are always allowed, but still trip the circuit breaker.
+    check_circuit_breaker(deps.branch(), &env);
+
     let withdrawal_address = deps
         .api
         .addr_validate(&to)
@@ -623,31 +1761,15 @@ pub fn withdraw(
         return Err(CantWithdrawToContract(withdrawal_address.into()));
     }
 
+    let management_fee_res = accrue_management_fee(deps.branch(), &env);
+
     // Invariant: TokenInfo will always be present after instantiation.
     let total_shares_supply = query_token_info(deps.as_ref()).unwrap().total_supply;
 
-    let VaultBalancesResponse { 
-        bal0,
-        bal1,
-        protocol_unclaimed_fees0,
-        protocol_unclaimed_fees1,
-        admin_unclaimed_fees0,
-        admin_unclaimed_fees1
-    } = query::vault_balances(deps.as_ref());
+    let vault_balances = query::vault_balances(deps.as_ref(), &env);
+    let VaultBalancesResponse { bal0, bal1, .. } = vault_balances;
 
-    // Invariant: Any addition of tokens wont overflow, because for that the token
-    //            max supply would have to be above `Uint128::MAX`, but thats impossible.
-    FEES_INFO.update(deps.storage, |mut info| -> StdResult<_> { 
-        info.protocol_tokens0_owned = info.protocol_tokens0_owned
-            .checked_add(protocol_unclaimed_fees0)?;
-        info.protocol_tokens1_owned = info.protocol_tokens1_owned
-            .checked_add(protocol_unclaimed_fees1)?;
-        info.admin_tokens0_owned = info.admin_tokens0_owned
-            .checked_add(admin_unclaimed_fees0)?;
-        info.admin_tokens1_owned = info.admin_tokens1_owned
-            .checked_add(admin_unclaimed_fees1)?;
-        Ok(info)
-    }).unwrap();
+    accrue_unclaimed_fees(&vault_balances, deps.branch());
 
     // Invariant: We know that `info.sender` is a proper address, thus even if it didnt 
     //            own any shares, the query would return Uint128::zero().
@@ -657,8 +1779,8 @@ pub fn withdraw(
 
     if shares > shares_held {
         return Err(InvalidWithdrawalAmount {
-            owned: shares_held.into(),
-            withdrawn: shares.into(),
+            owned: shares_held,
+            withdrawn: shares,
         })
     }
 
@@ -688,6 +1810,27 @@ pub fn withdraw(
         Ok(funds)
     }).unwrap();
 
+    // NOTE: Fees kept by the vault go back to its idle funds, as the withdrawn
+    //       liquidity is left in the contract balance.
+    let (exit_fee0, exit_fee1) = query::exit_fees(
+        expected_withdrawn_amount0, expected_withdrawn_amount1, deps.as_ref()
+    );
+    // Invariant: Any state is present after instantiation.
+    let mut fees = FEES_INFO.load(deps.storage).unwrap();
+    if fees.credit_entry_exit_fees(exit_fee0, exit_fee1) {
+        FUNDS_INFO.update(deps.storage, |mut funds| -> StdResult<_> {
+            funds.available_balance0 = funds.available_balance0.checked_add(exit_fee0)?;
+            funds.available_balance1 = funds.available_balance1.checked_add(exit_fee1)?;
+            Ok(funds)
+        }).unwrap();
+    }
+    FEES_INFO.save(deps.storage, &fees).unwrap();
+    record_entry_exit_fees(&fees, exit_fee0, exit_fee1, deps.branch());
+
+    // Invariant: Wont underflow, as the fee is a valid weight.
+    let expected_withdrawn_amount0 = expected_withdrawn_amount0.checked_sub(exit_fee0).unwrap();
+    let expected_withdrawn_amount1 = expected_withdrawn_amount1.checked_sub(exit_fee1).unwrap();
+
     if expected_withdrawn_amount0 < amount0_min || expected_withdrawn_amount1 < amount1_min {
         return Err(WithdrawnAmontsBelowMin {
             got: format!(
@@ -713,10 +1856,7 @@ pub fn withdraw(
     .collect();
 
     if shares_proportion.is_max() {
-        VAULT_STATE.update(deps.storage, |x| -> StdResult<_> { Ok(VaultState {
-            last_price_and_timestamp: x.last_price_and_timestamp,
-            ..VaultState::default()
-        })}).unwrap();
+        VAULT_STATE.update(deps.storage, |x| -> StdResult<_> { Ok(x.without_positions()) }).unwrap();
     }
 
     let position_ids = liquidity_removal_msgs
@@ -732,10 +1872,13 @@ pub fn withdraw(
     // Invariant: `VAULT_INFO` will always be present after instantiation.
     let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
 
+    sync_rewards(&[info.sender.as_str()], deps.branch(), &env);
+
     // Invariant: We verified earlier that `info.sender` holds at least `shares`.
     let shares_burn_response = execute_burn(deps, env.clone(), info, shares).unwrap();
 
     Ok(shares_burn_response
+        .add_attributes(management_fee_res.attributes)
         .add_message(rewards_claim_msg)
         .add_messages(liquidity_removal_msgs)
         .add_message(BankMsg::Send {
@@ -748,30 +1891,245 @@ pub fn withdraw(
     )
 }
 
-pub fn withdraw_protocol_fees(deps: DepsMut, info: MessageInfo) -> Result<Response, ProtocolOperationError> {
+/// Per address deposit cap exceeded if `recipient` gets `amount` more vault shares, if any.
+pub fn exceeded_shares_cap(recipient: &str, amount: Uint128, deps: Deps) -> Option<Uint128> {
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let max = vault_info.deposit_caps.and_then(|x| x.max_shares_per_address)?;
+    // NOTE: Invalid addresses are skipped, the cw20 logic will reject them later.
+    let recipient = deps.api.addr_validate(recipient).ok()?;
+    let shares = BALANCES.may_load(deps.storage, &recipient).unwrap().unwrap_or_default();
+    (shares.saturating_add(amount) > max).then_some(max)
+}
+
+/// First of `addresses` not allowed to hold vault shares by the vault access list, if any.
+pub fn not_allowed_holder(addresses: &[&str], deps: Deps) -> Option<String> {
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    addresses
+        .iter()
+        .find(|x| !vault_info.is_allowed(x, deps))
+        .map(|x| x.to_string())
+}
 
-    sender_is_protocol(info)?;
+/// Accounts any reward tokens received by the vault, and settles the rewards of
+/// `addresses` for their current shares. Must be called before any share balance
+/// change. Invalid addresses are skipped, the cw20 logic will reject them later.
+pub fn sync_rewards(addresses: &[&str], deps: DepsMut, env: &Env) {
+    let indexes = query::reward_indexes(deps.as_ref(), env);
+
+    // Invariant: Wont panic as all types are proper.
+    for (denom, reward_index) in &indexes {
+        REWARD_INDEXES.save(deps.storage, denom, reward_index).unwrap();
+    }
+
+    for address in addresses.iter().filter_map(|x| deps.api.addr_validate(x).ok()) {
+        for (denom, reward) in query::settled_rewards(&address, &indexes, deps.as_ref()) {
+            SHAREHOLDER_REWARDS.save(deps.storage, (&address, &denom), &reward).unwrap();
+        }
+    }
+}
+
+pub fn claim_rewards(mut deps: DepsMut, env: Env, info: MessageInfo) -> Response {
+    sync_rewards(&[info.sender.as_str()], deps.branch(), &env);
+
+    let rewards: Vec<_> = SHAREHOLDER_REWARDS
+        .prefix(&info.sender)
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap();
+
+    let mut claimed = vec![];
+    for (denom, mut reward) in rewards.into_iter().filter(|(_, x)| !x.pending.is_zero()) {
+        // Invariant: Any settled reward was accounted in its reward index, so its
+        //            always present and `accounted >= pending`.
+        let mut reward_index = REWARD_INDEXES.load(deps.storage, &denom).unwrap();
+        reward_index.accounted = reward_index.accounted.checked_sub(reward.pending).unwrap();
+        REWARD_INDEXES.save(deps.storage, &denom, &reward_index).unwrap();
+
+        claimed.push(coin(reward.pending.into(), &denom));
+        reward.pending = Uint128::zero();
+        SHAREHOLDER_REWARDS.save(deps.storage, (&info.sender, &denom), &reward).unwrap();
+    }
+
+    if claimed.is_empty() {
+        return Response::new();
+    }
+
+    Response::new().add_message(BankMsg::Send { to_address: info.sender.into(), amount: claimed })
+}
+
+pub fn withdraw_protocol_fees(
+    withdrawal: FeesWithdrawalMsg,
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo
+) -> Result<Response, ProtocolOperationError> {
+    use ProtocolOperationError::*;
+
+    sender_is_protocol(deps.as_ref(), info)?;
+
+    let rewards_claim_msg = withdrawal.collect_spread_rewards.then(|| {
+        let vault_balances = query::vault_balances(deps.as_ref(), &env);
+        collect_spread_rewards(deps.branch(), &env, &vault_balances)
+    });
 
     // Invariant: Any state is always present after instantiation.
     let mut fees = FEES_INFO.load(deps.storage).unwrap();
     let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
 
-    let tx = BankMsg::Send { 
-        to_address: PROTOCOL_ADDR.into(),
-        amount: vec![
-            coin(fees.protocol_tokens0_owned.into(), denom0),
-            coin(fees.protocol_tokens1_owned.into(), denom1),
-            coin(fees.protocol_vault_creation_tokens_owned.into(), VAULT_CREATION_COST_DENOM)
-        ].into_iter().filter(|c| !c.amount.is_zero()).collect() 
-    };
+    let (amount0, amount1) = fees_withdrawal_amounts(
+        &withdrawal, fees.protocol_tokens0_owned, fees.protocol_tokens1_owned
+    ).ok_or(WithdrawingMoreFeesThanOwned {
+        owned: format!("({}, {})", fees.protocol_tokens0_owned, fees.protocol_tokens1_owned),
+        got: format!("({:?}, {:?})", withdrawal.amount0, withdrawal.amount1)
+    })?;
+
+    let recipient = match withdrawal.recipient {
+        Some(recipient) => deps.api
+            .addr_validate(&recipient)
+            .map_err(|_| InvalidProtocolFeeRecipient(recipient))?,
+        // Invariant: Any state is always present after instantiation.
+        None => PROTOCOL_INFO.load(deps.storage).unwrap().fee_recipient
+    };
+
+    // Invariant: Wont overflow, see `record_entry_exit_fees`.
+    let paid = |x: &mut RevenueStats, amount| x.protocol_fees_paid += amount;
+    record_revenue(&denom0, amount0, paid, deps.storage);
+    record_revenue(&denom1, amount1, paid, deps.storage);
+
+    // NOTE: The vault creation tokens are always withdrawn in full, see
+    // `ExecuteMsg::WithdrawProtocolFees`.
+    let creation_tokens = fees.protocol_vault_creation_cost.as_ref().map(|x| {
+        coin(fees.protocol_vault_creation_tokens_owned.into(), &x.denom)
+    });
+    if let Some(creation_tokens) = &creation_tokens {
+        // Invariant: Wont overflow, see `record_entry_exit_fees`.
+        let paid = |x: &mut RevenueStats, amount| x.creation_costs_paid += amount;
+        record_revenue(&creation_tokens.denom, creation_tokens.amount, paid, deps.storage);
+    }
+
+    let tx = BankMsg::Send { 
+        to_address: recipient.into(),
+        amount: vec![
+            coin(amount0.into(), denom0),
+            coin(amount1.into(), denom1)
+        ]
+            .into_iter()
+            .chain(creation_tokens)
+            .filter(|c| !c.amount.is_zero())
+            .collect()
+    };
+
+    // Invariant: Wont underflow, as we checked the amounts are owned.
+    fees.protocol_tokens0_owned = fees.protocol_tokens0_owned.checked_sub(amount0).unwrap();
+    fees.protocol_tokens1_owned = fees.protocol_tokens1_owned.checked_sub(amount1).unwrap();
+    fees.protocol_vault_creation_tokens_owned = Uint128::zero();
+
+    // Invariant: Will serialize as all types are proper.
+    FEES_INFO.save(deps.storage, &fees).unwrap();
+    Ok(Response::new().add_messages(rewards_claim_msg).add_message(tx))
+}
+
+/// Amounts of owned fees to withdraw, all of them if not specified.
+///
+/// # Returns
+///
+/// None if trying to withdraw more than `owned0` or `owned1`.
+fn fees_withdrawal_amounts(
+    withdrawal: &FeesWithdrawalMsg,
+    owned0: Uint128,
+    owned1: Uint128
+) -> Option<(Uint128, Uint128)> {
+    let amount0 = withdrawal.amount0.unwrap_or(owned0);
+    let amount1 = withdrawal.amount1.unwrap_or(owned1);
+    (amount0 <= owned0 && amount1 <= owned1).then_some((amount0, amount1))
+}
+
+pub fn change_performance_fee(
+    new_performance_fee: Option<PerformanceFeeInstantiateMsg>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+    // Invariant: Any state is present after instantiation.
+    let fees_info = FEES_INFO.load(deps.storage).unwrap();
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    let new_fees_info = fees_info.update_performance_fee(new_performance_fee, &vault_info)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    FEES_INFO.save(deps.storage, &new_fees_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_management_fee(
+    new_management_fee: Option<Uint128>,
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    let res = accrue_management_fee(deps.branch(), &env);
+
+    // Invariant: Any state is present after instantiation.
+    let fees_info = FEES_INFO.load(deps.storage).unwrap();
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let new_fees_info = fees_info.update_management_fee(new_management_fee, &vault_info, env.block.time)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    FEES_INFO.save(deps.storage, &new_fees_info).unwrap();
+    Ok(res)
+}
+
+pub fn change_entry_exit_fees(
+    new_entry_exit_fees: Option<EntryExitFeesInstantiateMsg>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+    // Invariant: Any state is present after instantiation.
+    let fees_info = FEES_INFO.load(deps.storage).unwrap();
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    let new_fees_info = fees_info.update_entry_exit_fees(new_entry_exit_fees, &vault_info)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    FEES_INFO.save(deps.storage, &new_fees_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_admin_fee_beneficiaries(
+    beneficiaries: Vec<(String, Uint128)>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+    // Invariant: Any state is present after instantiation.
+    let fees_info = FEES_INFO.load(deps.storage).unwrap();
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    let new_fees_info = fees_info.update_admin_fee_beneficiaries(beneficiaries, deps.as_ref())?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    FEES_INFO.save(deps.storage, &new_fees_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_keeper_reward(
+    new_keeper_reward: Option<KeeperRewardInstantiateMsg>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+    // Invariant: Any state is present after instantiation.
+    let fees_info = FEES_INFO.load(deps.storage).unwrap();
 
-    fees.protocol_tokens0_owned = Uint128::zero();
-    fees.protocol_tokens1_owned = Uint128::zero();
-    fees.protocol_vault_creation_tokens_owned = Uint128::zero();
+    sender_is_admin(deps.as_ref(), info)?;
 
-    // Invariant: Will serialize as all types are proper.
-    FEES_INFO.save(deps.storage, &fees).unwrap();
-    Ok(Response::new().add_message(tx))
+    let new_fees_info = fees_info.update_keeper_reward(new_keeper_reward)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    FEES_INFO.save(deps.storage, &new_fees_info).unwrap();
+    Ok(Response::new())
 }
 
 pub fn change_protocol_fee(
@@ -782,7 +2140,7 @@ pub fn change_protocol_fee(
     // Invariant: Any state is present after instantiation.
     let fees_info = FEES_INFO.load(deps.storage).unwrap();
 
-    sender_is_protocol(info)?;
+    sender_is_protocol(deps.as_ref(), info)?;
 
     let new_fees_info = fees_info.update_protocol_fee(new_protocol_fee)?;
     // Invariant: Wont panic as we ensured all types are proper during development.
@@ -790,29 +2148,137 @@ pub fn change_protocol_fee(
     Ok(Response::new())
 }
 
-pub fn withdraw_admin_fees(deps: DepsMut, info: MessageInfo) -> Result<Response, AdminOperationError> {
+pub fn propose_new_protocol(
+    deps: DepsMut,
+    info: MessageInfo,
+    new_protocol: Option<String>
+) -> Result<Response, ProtocolOperationError> {
+
+    sender_is_protocol(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let protocol_info = PROTOCOL_INFO.load(deps.storage).unwrap();
+
+    let protocol_info = if let Some(new_protocol) = new_protocol {
+        protocol_info
+            .propose_new_protocol(new_protocol.clone(), deps.as_ref())
+            .ok_or(ProtocolOperationError::InvalidNewProposedProtocolAddress(new_protocol))?
+    } else {
+        protocol_info.unset_proposed_new_protocol()
+    };
+
+    // Invariant: Will serialize as all types are proper.
+    PROTOCOL_INFO.save(deps.storage, &protocol_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn accept_new_protocol(deps: DepsMut, info: MessageInfo) -> Result<Response, ProtocolOperationError> {
+    use ProtocolOperationError::*;
+    // Invariant: Any state is present after instantiation.
+    let protocol_info = PROTOCOL_INFO.load(deps.storage).unwrap();
+
+    match &protocol_info.proposed_new_protocol {
+        None => return Err(NewProposedProtocolIsNone()),
+        Some(new_protocol) if *new_protocol != info.sender => {
+            return Err(UnauthorizedNewProposedProtocol {
+                expected: new_protocol.into(),
+                got: info.sender.into()
+            })
+        },
+        Some(_) => {}
+    }
+
+    // Invariant: Wont panic, as we just checked there is a proposed protocol.
+    let protocol_info = protocol_info.confirm_new_protocol().unwrap();
+    // Invariant: Will serialize as all types are proper.
+    PROTOCOL_INFO.save(deps.storage, &protocol_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_protocol_fee_recipient(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipient: String
+) -> Result<Response, ProtocolOperationError> {
+
+    sender_is_protocol(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let protocol_info = PROTOCOL_INFO
+        .load(deps.storage)
+        .unwrap()
+        .change_fee_recipient(recipient.clone(), deps.as_ref())
+        .ok_or(ProtocolOperationError::InvalidProtocolFeeRecipient(recipient))?;
+
+    // Invariant: Will serialize as all types are proper.
+    PROTOCOL_INFO.save(deps.storage, &protocol_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn withdraw_admin_fees(
+    withdrawal: FeesWithdrawalMsg,
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+    use AdminOperationError::*;
 
     let admin = sender_is_admin(deps.as_ref(), info)?;
 
+    let rewards_claim_msg = withdrawal.collect_spread_rewards.then(|| {
+        let vault_balances = query::vault_balances(deps.as_ref(), &env);
+        collect_spread_rewards(deps.branch(), &env, &vault_balances)
+    });
+
     // Invariant: Any state is always present after instantiation.
     let mut fees = FEES_INFO.load(deps.storage).unwrap();
     let vault_info = VAULT_INFO.load(deps.storage).unwrap();
     let (denom0, denom1) = vault_info.denoms(&deps.querier);
 
-    let tx = BankMsg::Send { 
-        to_address:  admin.into(),
-        amount: vec![
-            coin(fees.admin_tokens0_owned.into(), denom0),
-            coin(fees.admin_tokens1_owned.into(), denom1)
-        ].into_iter().filter(|c| !c.amount.is_zero()).collect() 
+    let (amount0, amount1) = fees_withdrawal_amounts(
+        &withdrawal, fees.admin_tokens0_owned, fees.admin_tokens1_owned
+    ).ok_or(WithdrawingMoreFeesThanOwned {
+        owned: format!("({}, {})", fees.admin_tokens0_owned, fees.admin_tokens1_owned),
+        got: format!("({:?}, {:?})", withdrawal.amount0, withdrawal.amount1)
+    })?;
+
+    // NOTE: An explicit recipient gets it all, instead of the admin fee beneficiaries.
+    let payouts = match withdrawal.recipient {
+        Some(recipient) => vec![(
+            deps.api
+                .addr_validate(&recipient)
+                .map_err(|_| InvalidAdminFeeRecipient(recipient))?,
+            amount0,
+            amount1
+        )],
+        None => fees.admin_fee_payouts(&admin, amount0, amount1)
     };
 
-    fees.admin_tokens0_owned = Uint128::zero();
-    fees.admin_tokens1_owned = Uint128::zero();
+    // NOTE: One message per beneficiary, so each gets its cut in a single transfer.
+    let txs: Vec<_> = payouts
+        .into_iter()
+        .map(|(address, amount0, amount1)| BankMsg::Send {
+            to_address: address.into(),
+            amount: vec![
+                coin(amount0.into(), denom0.clone()),
+                coin(amount1.into(), denom1.clone())
+            ].into_iter().filter(|c| !c.amount.is_zero()).collect()
+        })
+        .filter(|tx| matches!(tx, BankMsg::Send { amount, .. } if !amount.is_empty()))
+        .collect();
+
+    // Invariant: Wont overflow, see `record_entry_exit_fees`.
+    let paid = |x: &mut RevenueStats, amount| x.admin_fees_paid += amount;
+    record_revenue(&denom0, amount0, paid, deps.storage);
+    record_revenue(&denom1, amount1, paid, deps.storage);
+
+    // Invariant: Wont underflow, as we checked the amounts are owned.
+    fees.admin_tokens0_owned = fees.admin_tokens0_owned.checked_sub(amount0).unwrap();
+    fees.admin_tokens1_owned = fees.admin_tokens1_owned.checked_sub(amount1).unwrap();
 
     // Invariant: Will serialize as all types are proper.
     FEES_INFO.save(deps.storage, &fees).unwrap();
-    Ok(Response::new().add_message(tx))
+    Ok(Response::new().add_messages(rewards_claim_msg).add_messages(txs))
 }
 
 pub fn propose_new_admin(deps: DepsMut, info: MessageInfo, new_admin: Option<String>) -> Result<Response, AdminOperationError> {
@@ -872,14 +2338,30 @@ pub fn burn_vault_admin(deps: DepsMut, info: MessageInfo) -> Result<Response, Ad
         return Err(BurningAdminWithImproperRebalancer())
     }
 
-    if !fees_info.admin_fee.0.is_zero() {
+    if !fees_info.admin_fee.0.is_zero() || fees_info.pending_admin_fee.is_some() {
         return Err(BurningAdminWithNonZeroAdminFee())
     }
 
+    if fees_info.performance_fee.is_some() {
+        return Err(BurningAdminWithPerformanceFee())
+    }
+
+    if fees_info.management_fee.is_some() {
+        return Err(BurningAdminWithManagementFee())
+    }
+
+    if fees_info.entry_exit_fees.as_ref().is_some_and(|x| x.recipient == FeeRecipient::Admin {}) {
+        return Err(BurningAdminWithNonZeroEntryExitFees())
+    }
+
     if !fees_info.admin_tokens0_owned.is_zero() || !fees_info.admin_tokens1_owned.is_zero() {
         return Err(BurningAdminWithUncollectedAdminFees()) 
     }
 
+    if DELEGATED_REBALANCERS.keys(deps.storage, None, None, Order::Ascending).next().is_some() {
+        return Err(BurningAdminWithDelegatedRebalancers())
+    }
+
     // Invariant: Will serialize as all types are proper.
     VAULT_INFO.save(deps.storage, &vault_info.burn_admin()).unwrap();
     Ok(Response::new())
@@ -902,6 +2384,239 @@ pub fn change_vault_rebalancer(
     Ok(Response::new())
 }
 
+pub fn add_delegated_rebalancer(
+    address: String,
+    permission: RebalancerPermission,
+    min_seconds_between_calls: u32,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    let address = deps.api
+        .addr_validate(&address)
+        .map_err(|_| InstantiationError::InvalidDelegateAddress(address))?;
+
+    // NOTE: We keep the last call of updated rebalancers, so updates dont reset their interval.
+    // Invariant: Wont panic as all types are proper.
+    DELEGATED_REBALANCERS.update(deps.storage, &address, |rebalancer| -> StdResult<_> {
+        Ok(DelegatedRebalancer {
+            permission,
+            min_seconds_between_calls: min_seconds_between_calls.into(),
+            last_call: rebalancer.and_then(|x| x.last_call)
+        })
+    }).unwrap();
+    Ok(Response::new())
+}
+
+pub fn remove_delegated_rebalancer(
+    address: String,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    let address = deps.api
+        .addr_validate(&address)
+        .ok()
+        .filter(|x| DELEGATED_REBALANCERS.has(deps.as_ref().storage, x))
+        .ok_or(AdminOperationError::NonExistantDelegatedRebalancer(address))?;
+
+    DELEGATED_REBALANCERS.remove(deps.storage, &address);
+    Ok(Response::new())
+}
+
+pub fn change_twap_config(
+    new_twap_config: Option<TwapConfigInstantiateMsg>,
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_info = vault_info.change_twap_config(new_twap_config, env.block.time)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_INFO.save(deps.storage, &vault_info).unwrap();
+
+    Ok(Response::new().add_attributes(vault_info.pending_twap_config.map(|x|
+        ("twap_config_effective_at", x.effective_at.seconds().to_string())
+    )))
+}
+
+pub fn change_inactivity_timeout(
+    new_inactivity_timeout: Option<u32>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_info = vault_info.change_inactivity_timeout(new_inactivity_timeout)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_INFO.save(deps.storage, &vault_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_rebalance_limits(
+    new_rebalance_limits: RebalanceLimitsInstantiateMsg,
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_info = vault_info.change_rebalance_limits(new_rebalance_limits, env.block.time)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_INFO.save(deps.storage, &vault_info).unwrap();
+
+    Ok(Response::new().add_attributes(vault_info.pending_rebalance_limits.map(|x| 
+        ("rebalance_limits_effective_at", x.effective_at.seconds().to_string())
+    )))
+}
+
+pub fn change_deposit_caps(
+    new_deposit_caps: Option<DepositCapsInstantiateMsg>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_info = vault_info.change_deposit_caps(new_deposit_caps)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_INFO.save(deps.storage, &vault_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_withdrawal_cooldown(
+    new_withdrawal_cooldown: Option<u32>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_info = vault_info.change_withdrawal_cooldown(new_withdrawal_cooldown)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_INFO.save(deps.storage, &vault_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_access_list(
+    new_access_list: Option<AccessListInstantiateMsg>,
+    mut deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_info = vault_info.change_access_list(new_access_list, deps.branch())?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_INFO.save(deps.storage, &vault_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_circuit_breaker(
+    new_circuit_breaker: Option<CircuitBreakerInstantiateMsg>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_info = vault_info.change_circuit_breaker(new_circuit_breaker)?;
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_INFO.save(deps.storage, &vault_info).unwrap();
+    Ok(Response::new())
+}
+
+pub fn change_reward_denom(
+    denom: String,
+    distribute: bool,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
+    if denom == denom0 || denom == denom1 {
+        return Err(InstantiationError::ContradictoryConfig {
+            reason: "Vault tokens cant be distributed as rewards".into()
+        }.into())
+    }
+
+    // NOTE: Rewards already distributed stay claimable after removing the denom.
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    match distribute {
+        true => REWARD_DENOMS.save(deps.storage, &denom, &()).unwrap(),
+        false => REWARD_DENOMS.remove(deps.storage, &denom)
+    }
+
+    Ok(Response::new())
+}
+
+pub fn change_harvest_route(
+    denom: String,
+    new_route: Option<HarvestRouteInstantiateMsg>,
+    deps: DepsMut,
+    info: MessageInfo
+) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    match new_route {
+        Some(route) => {
+            let route = HarvestRoute::new(&denom, route, &vault_info, &deps.querier)?;
+            HARVEST_ROUTES.save(deps.storage, &denom, &route).unwrap();
+        },
+        None => HARVEST_ROUTES.remove(deps.storage, &denom)
+    }
+
+    Ok(Response::new())
+}
+
+pub fn clear_circuit_breaker(deps: DepsMut, info: MessageInfo) -> Result<Response, AdminOperationError> {
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    if !vault_state.circuit_breaker_tripped {
+        return Err(AdminOperationError::CircuitBreakerNotTripped())
+    }
+
+    // NOTE: It will trip again if the price still deviates more than the trip deviation.
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_STATE.save(deps.storage, &VaultState {
+        circuit_breaker_tripped: false,
+        ..vault_state
+    }).unwrap();
+    Ok(Response::new())
+}
+
 pub fn change_vault_parameters(
     new_vault_parameters: VaultParametersInstantiateMsg,
     deps: DepsMut,
@@ -916,24 +2631,60 @@ pub fn change_vault_parameters(
     Ok(Response::new())
 }
 
+pub fn cancel_rebalance(deps: DepsMut, info: MessageInfo) -> Result<Response, AdminOperationError> {
+
+    sender_is_admin(deps.as_ref(), info)?;
+
+    // Invariant: Any state is present after instantiation.
+    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    if vault_state.rebalance_in_progress.is_none() {
+        return Err(AdminOperationError::NoRebalanceInProgress())
+    }
+
+    // NOTE: Liquidity already moved stays in the vault idle balances
+    //       until the next rebalance.
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    VAULT_STATE.save(deps.storage, &VaultState {
+        rebalance_in_progress: None,
+        ..vault_state
+    }).unwrap();
+    Ok(Response::new())
+}
+
 pub fn change_admin_fee(
     new_admin_fee: Uint128,
     deps: DepsMut,
+    env: Env,
     info: MessageInfo
 ) -> Result<Response, AdminOperationError> {
 
     sender_is_admin(deps.as_ref(), info)?;
 
     let fees_info = FEES_INFO.load(deps.storage).unwrap();
-    let new_fees_info = fees_info.update_admin_fee(new_admin_fee, deps.as_ref())?;
+    let new_fees_info = fees_info.update_admin_fee(new_admin_fee, env.block.time, deps.as_ref())?;
     // Invariant: Wont panic as we ensured all types are proper during development.
     FEES_INFO.save(deps.storage, &new_fees_info).unwrap();
 
-    Ok(Response::new())
+    Ok(Response::new().add_attributes(new_fees_info.pending_admin_fee.map(|x|
+        ("admin_fee_effective_at", x.effective_at.seconds().to_string())
+    )))
 }
 
-fn sender_is_protocol(info: MessageInfo) -> Result<(), ProtocolOperationError> {
-    if Addr::unchecked(PROTOCOL_ADDR) != info.sender {
+/// Puts in effect a pending admin fee increase, once its notice period is over.
+pub fn apply_pending_admin_fee(deps: DepsMut, env: &Env) {
+    // Invariant: Any state is present after instantiation.
+    let fees_info = FEES_INFO.load(deps.storage).unwrap();
+    if fees_info.pending_admin_fee.is_none() {
+        return
+    }
+
+    // Invariant: Wont panic as we ensured all types are proper during development.
+    FEES_INFO.save(deps.storage, &fees_info.apply_pending_admin_fee(env.block.time)).unwrap();
+}
+
+fn sender_is_protocol(deps: Deps, info: MessageInfo) -> Result<(), ProtocolOperationError> {
+    // Invariant: Any state is present after instantiation.
+    if PROTOCOL_INFO.load(deps.storage).unwrap().protocol != info.sender {
         Err(ProtocolOperationError::UnauthorizedProtocolAccount(
             info.sender.into()
         ))
@@ -6,7 +6,7 @@ pub mod mock {
     use cosmwasm_std::{testing::mock_dependencies, Addr, Api, Coin, Decimal, Uint128};
     use cw20_base::state::TokenInfo;
     use osmosis_std::types::{
-        cosmos::bank::v1beta1::QueryBalanceRequest,
+        cosmos::bank::v1beta1::{MsgSend, QueryBalanceRequest},
         cosmwasm::wasm::v1::MsgExecuteContractResponse,
         osmosis::{
             concentratedliquidity::v1beta1::{
@@ -17,26 +17,38 @@ pub mod mock {
         },
     };
     use osmosis_test_tube::{
-        Account, Bank, ConcentratedLiquidity, ExecuteResponse, GovWithAppAccess, Module,
+        Account, Bank, ConcentratedLiquidity, ExecuteResponse, Gamm, GovWithAppAccess, Module,
         OsmosisTestApp, PoolManager, SigningAccount, Wasm,
     };
 
     use crate::{
-        constants::{MAX_TICK, MIN_TICK, TWAP_SECONDS, VAULT_CREATION_COST_DENOM},
+        constants::{MAINNET_USDC_DENOM, MAX_TICK, MIN_TICK, TWAP_SECONDS},
         msg::{
-            DepositMsg, ExecuteMsg, InstantiateMsg, PositionBalancesWithFeesResponse, QueryMsg,
-            VaultBalancesResponse, VaultInfoInstantiateMsg, VaultParametersInstantiateMsg,
-            VaultRebalancerInstantiateMsg, WithdrawMsg,
+            AccessListInstantiateMsg, AdminFeeResponse, AdminFeesOwedResponse,
+            CalcSharesAndUsableAmountsResponse, CanRebalanceResponse, CircuitBreakerInstantiateMsg,
+            DelegatedRebalancersResponse, DepositCapsInstantiateMsg, DepositMsg,
+            EntryExitFeesInstantiateMsg, ExecuteMsg, FeesWithdrawalMsg, HarvestRouteInstantiateMsg,
+            InstantiateMsg, IsAllowedResponse, KeeperRewardInstantiateMsg, ManagementFeeResponse,
+            PendingRewardsResponse, PerformanceFeeInstantiateMsg, PositionBalancesWithFeesResponse,
+            QueryMsg, RebalanceHistoryResponse, RebalanceLimitsInstantiateMsg,
+            RebalancePolicyInstantiateMsg, RevenueStatsResponse, SharesUnlockResponse,
+            SimulateRebalanceResponse, SimulateWithdrawResponse, TwapConfigInstantiateMsg,
+            VaultBalancesResponse, VaultInfoInstantiateMsg, VaultInfoResponse,
+            VaultParametersInstantiateMsg, VaultRebalancerInstantiateMsg, WithdrawMsg,
         },
         state::{
-            FeesInfo, PositionType, ProtocolFee, VaultCreationCost, VaultParameters, VaultState,
+            FeesInfo, PositionType, ProtocolFee, ProtocolInfo, RebalancerPermission,
+            VaultParameters, VaultState,
         },
     };
 
     // TODO: Ideally abstract those 2, so the tests dev doesnt has to keep
     // track of whats in the pool.
-    pub const USDC_DENOM: &str = VAULT_CREATION_COST_DENOM;
+    pub const USDC_DENOM: &str = MAINNET_USDC_DENOM;
+    pub const VAULT_CREATION_COST: u128 = 5_000_000;
     pub const OSMO_DENOM: &str = "uosmo";
+    /// Non pool token, sent to vaults as incentives.
+    pub const REWARD_DENOM: &str = "uion";
     
     pub struct PoolMockup {
         pub pool_id: u64,
@@ -45,6 +57,7 @@ pub mod mock {
         pub deployer: SigningAccount,
         pub user1: SigningAccount,
         pub user2: SigningAccount,
+        pub protocol: SigningAccount,
         pub price: Decimal,
     }
 
@@ -55,12 +68,14 @@ pub mod mock {
             let init_coins = &[
                 Coin::new(1_000_000_000_000u128, USDC_DENOM),
                 Coin::new(1_000_000_000_000u128, OSMO_DENOM),
+                Coin::new(1_000_000_000_000u128, REWARD_DENOM),
             ];
 
-            let mut accounts = app.init_accounts(init_coins, 3).unwrap().into_iter();
+            let mut accounts = app.init_accounts(init_coins, 4).unwrap().into_iter();
             let deployer = accounts.next().unwrap();
             let user1 = accounts.next().unwrap();
             let user2 = accounts.next().unwrap();
+            let protocol = accounts.next().unwrap();
 
             let cl = ConcentratedLiquidity::new(&app);
             let gov = GovWithAppAccess::new(&app);
@@ -113,7 +128,7 @@ pub mod mock {
             let price = Decimal::new(osmo_in.into()) / Decimal::new(usdc_in.into());
 
             Self {
-                pool_id, initial_position_id, app, deployer, user1, user2, price
+                pool_id, initial_position_id, app, deployer, user1, user2, protocol, price
             }
             
         }
@@ -180,6 +195,35 @@ pub mod mock {
             Uint128::from_str(&amount).unwrap()
         }
 
+        pub fn reward_balance_query(&self, address: &str) -> Uint128 {
+            let bank = Bank::new(&self.app);
+            let amount = bank.query_balance(&QueryBalanceRequest {
+                address: address.into(),
+                denom: REWARD_DENOM.into()
+            }).unwrap().balance.unwrap().amount;
+            Uint128::from_str(&amount).unwrap()
+        }
+
+        /// Creates a balancer pool of the reward token against USDC, returning its id.
+        pub fn create_reward_pool(&self, reward_in: u128, usdc_in: u128) -> u64 {
+            let gamm = Gamm::new(&self.app);
+            let pool_id = gamm.create_basic_pool(
+                &[Coin::new(reward_in, REWARD_DENOM), Coin::new(usdc_in, USDC_DENOM)],
+                &self.deployer
+            ).unwrap().data.pool_id;
+            self.app.increase_time(TWAP_SECONDS);
+            pool_id
+        }
+
+        pub fn send_rewards(&self, amount: u128, to: &str, from: &SigningAccount) {
+            let bank = Bank::new(&self.app);
+            bank.send(MsgSend {
+                from_address: from.address(),
+                to_address: to.into(),
+                amount: vec![Coin::new(amount, REWARD_DENOM).into()]
+            }, from).unwrap();
+        }
+
         pub fn position_query(&self, position_id: u64) -> Result<FullPositionBreakdown> {
             let cl = ConcentratedLiquidity::new(&self.app);
             let pos = cl.query_position_by_id(&PositionByIdRequest { position_id })?;
@@ -212,13 +256,30 @@ pub mod mock {
             full_range_weight: Decimal::from_str(full).unwrap().atomics(),
             base_factor: Decimal::from_str(base).unwrap().atomics(),
             limit_factor: Decimal::from_str(limit).unwrap().atomics(),
+            rebalance_step: None
+        }
+    }
+
+    pub fn gradual_vault_params(base: &str, limit: &str, full: &str, step: &str) -> VaultParametersInstantiateMsg {
+        VaultParametersInstantiateMsg {
+            rebalance_step: Some(Decimal::from_str(step).unwrap().atomics()),
+            ..vault_params(base, limit, full)
         }
     }
 
     pub fn rebalancer_anyone(price_factor_before_rebalance: &str, seconds_before_rebalance: u32) -> VaultRebalancerInstantiateMsg {
         VaultRebalancerInstantiateMsg::Anyone { 
             price_factor_before_rebalance: Decimal::from_str(price_factor_before_rebalance).unwrap().atomics(),
-            seconds_before_rebalance
+            seconds_before_rebalance,
+            policy: None
+        }
+    }
+
+    pub fn rebalancer_anyone_with_policy(policy: RebalancePolicyInstantiateMsg) -> VaultRebalancerInstantiateMsg {
+        VaultRebalancerInstantiateMsg::Anyone { 
+            price_factor_before_rebalance: Decimal::one().atomics(),
+            seconds_before_rebalance: 0,
+            policy: Some(policy)
         }
     }
 
@@ -245,11 +306,23 @@ pub mod mock {
             params: VaultParametersInstantiateMsg,
             rebalancer: VaultRebalancerInstantiateMsg
         ) -> VaultMockup {
+            let usdc_fee = Coin::new(VAULT_CREATION_COST, USDC_DENOM);
+            Self::new_with_creation_costs(
+                pool_info, params, rebalancer, vec![usdc_fee.clone()], &[usdc_fee]
+            ).unwrap()
+        }
+
+        pub fn new_with_creation_costs(
+            pool_info: &PoolMockup,
+            params: VaultParametersInstantiateMsg,
+            rebalancer: VaultRebalancerInstantiateMsg,
+            vault_creation_costs: Vec<Coin>,
+            paid: &[Coin]
+        ) -> Result<VaultMockup> {
             let wasm = Wasm::new(&pool_info.app);
             let code_id = store_vaults_code(&wasm, &pool_info.deployer);
             let api = mock_dependencies().api;
 
-            let usdc_fee = Coin::new(VaultCreationCost::default().0.into(), USDC_DENOM);
             let vault_addr = wasm
                 .instantiate(
                     code_id,
@@ -260,22 +333,34 @@ pub mod mock {
                             vault_symbol: "USDCOSMOV".into(),
                             admin: Some(pool_info.deployer.address()),
                             admin_fee: ProtocolFee::default().0.0.atomics(),
-                            rebalancer
+                            rebalancer,
+                            twap_config: None,
+                            keeper_reward: None,
+                            inactivity_timeout: None,
+                            rebalance_limits: None,
+                            circuit_breaker: None,
+                            performance_fee: None,
+                            management_fee: None,
+                            entry_exit_fees: None,
+                            deposit_caps: None,
+                            access_list: None,
+                            withdrawal_cooldown: None
                         },
                         vault_parameters: params,
+                        protocol: pool_info.protocol.address(),
+                        vault_creation_costs
                     },
                     None,
                     Some("my vault"),
-                    &[usdc_fee],
+                    paid,
                     &pool_info.deployer,
-                )
-                .unwrap()
+                )?
                 .data
                 .address;
 
             let vault_addr = api.addr_validate(&vault_addr).unwrap();
 
-            VaultMockup { vault_addr, wasm }
+            Ok(VaultMockup { vault_addr, wasm })
 
         }
 
@@ -326,6 +411,24 @@ pub mod mock {
             )?)
         }
 
+        pub fn rebalance_limit(
+            &self,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(), &ExecuteMsg::RebalanceLimit {}, &[], from
+            )?)
+        }
+
+        pub fn compound(
+            &self,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(), &ExecuteMsg::Compound {}, &[], from
+            )?)
+        }
+
         pub fn withdraw(
             &self,
             shares: Uint128,
@@ -347,10 +450,18 @@ pub mod mock {
         pub fn admin_withdraw(
             &self,
             from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            self.admin_withdraw_with(from, FeesWithdrawalMsg::default())
+        }
+
+        pub fn admin_withdraw_with(
+            &self,
+            from: &SigningAccount,
+            withdrawal: FeesWithdrawalMsg
         ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
             Ok(self.wasm.execute(
                 self.vault_addr.as_ref(),
-                &ExecuteMsg::WithdrawAdminFees {},
+                &ExecuteMsg::WithdrawAdminFees(withdrawal),
                 &[],
                 from
             )?)
@@ -362,7 +473,7 @@ pub mod mock {
         ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
             Ok(self.wasm.execute(
                 self.vault_addr.as_ref(),
-                &ExecuteMsg::WithdrawProtocolFees {},
+                &ExecuteMsg::WithdrawProtocolFees(FeesWithdrawalMsg::default()),
                 &[],
                 from
             )?)
@@ -381,6 +492,44 @@ pub mod mock {
             )?)
         }
         
+        pub fn propose_new_protocol(
+            &self,
+            from: &SigningAccount,
+            new: Option<&SigningAccount>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ProposeNewProtocol { new_protocol: new.map(|x| x.address()) },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn accept_new_protocol(
+            &self,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::AcceptNewProtocol {},
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_protocol_fee_recipient(
+            &self,
+            from: &SigningAccount,
+            recipient: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeProtocolFeeRecipient { recipient: recipient.address() },
+                &[],
+                from
+            )?)
+        }
+
         pub fn accept_new_admin(
             &self,
             from: &SigningAccount
@@ -418,6 +567,266 @@ pub mod mock {
             )?)
         }
 
+        pub fn add_delegated_rebalancer(
+            &self,
+            from: &SigningAccount,
+            rebalancer: &SigningAccount,
+            permission: RebalancerPermission,
+            min_seconds_between_calls: u32
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::AddDelegatedRebalancer {
+                    address: rebalancer.address(), permission, min_seconds_between_calls
+                },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn remove_delegated_rebalancer(
+            &self,
+            from: &SigningAccount,
+            rebalancer: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::RemoveDelegatedRebalancer { address: rebalancer.address() },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_twap_config(
+            &self,
+            from: &SigningAccount,
+            new_twap_config: Option<TwapConfigInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeTwapConfig(new_twap_config),
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_inactivity_timeout(
+            &self,
+            from: &SigningAccount,
+            inactivity_timeout: Option<u32>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeInactivityTimeout { inactivity_timeout },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_rebalance_limits(
+            &self,
+            from: &SigningAccount,
+            min_seconds_between_rebalances: u32,
+            max_rebalances_per_day: Option<u32>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeRebalanceLimits(RebalanceLimitsInstantiateMsg {
+                    min_seconds_between_rebalances, max_rebalances_per_day
+                }),
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_withdrawal_cooldown(
+            &self,
+            from: &SigningAccount,
+            withdrawal_cooldown: Option<u32>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeWithdrawalCooldown { withdrawal_cooldown },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_access_list(
+            &self,
+            from: &SigningAccount,
+            access_list: Option<AccessListInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeAccessList(access_list),
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_deposit_caps(
+            &self,
+            from: &SigningAccount,
+            deposit_caps: Option<DepositCapsInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeDepositCaps(deposit_caps),
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_circuit_breaker(
+            &self,
+            from: &SigningAccount,
+            circuit_breaker: Option<CircuitBreakerInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeCircuitBreaker(circuit_breaker),
+                &[],
+                from
+            )?)
+        }
+
+        pub fn claim_rewards(
+            &self,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ClaimRewards {},
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_performance_fee(
+            &self,
+            from: &SigningAccount,
+            performance_fee: Option<PerformanceFeeInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangePerformanceFee(performance_fee),
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_management_fee(
+            &self,
+            from: &SigningAccount,
+            new_management_fee: Option<&str>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            let new_management_fee = new_management_fee.map(|x| Decimal::from_str(x).unwrap().atomics());
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeManagementFee { new_management_fee },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_entry_exit_fees(
+            &self,
+            from: &SigningAccount,
+            entry_exit_fees: Option<EntryExitFeesInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeEntryExitFees(entry_exit_fees),
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_admin_fee_beneficiaries(
+            &self,
+            from: &SigningAccount,
+            beneficiaries: Vec<(&SigningAccount, &str)>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            let beneficiaries = beneficiaries
+                .into_iter()
+                .map(|(x, weight)| (x.address(), Decimal::from_str(weight).unwrap().atomics()))
+                .collect();
+
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeAdminFeeBeneficiaries { beneficiaries },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn harvest_incentives(
+            &self,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::HarvestIncentives {},
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_reward_denom(
+            &self,
+            from: &SigningAccount,
+            denom: &str,
+            distribute: bool
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeRewardDenom { denom: denom.into(), distribute },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn change_harvest_route(
+            &self,
+            from: &SigningAccount,
+            denom: &str,
+            route: Option<HarvestRouteInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeHarvestRoute { denom: denom.into(), route },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn transfer_shares(
+            &self,
+            amount: Uint128,
+            to: &str,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::Transfer { recipient: to.into(), amount },
+                &[],
+                from
+            )?)
+        }
+
+        pub fn clear_circuit_breaker(
+            &self,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ClearCircuitBreaker {},
+                &[],
+                from
+            )?)
+        }
+
         pub fn change_vault_parameters(
             &self,
             from: &SigningAccount,
@@ -431,6 +840,18 @@ pub mod mock {
             )?)
         }
 
+        pub fn cancel_rebalance(
+            &self,
+            from: &SigningAccount
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::CancelRebalance {},
+                &[],
+                from
+            )?)
+        }
+
         pub fn change_admin_fee(
             &self,
             from: &SigningAccount,
@@ -444,6 +865,19 @@ pub mod mock {
             )?)
         }
 
+        pub fn change_keeper_reward(
+            &self,
+            from: &SigningAccount,
+            new_keeper_reward: Option<KeeperRewardInstantiateMsg>
+        ) -> Result<ExecuteResponse<MsgExecuteContractResponse>> {
+            Ok(self.wasm.execute(
+                self.vault_addr.as_ref(),
+                &ExecuteMsg::ChangeKeeperReward(new_keeper_reward),
+                &[],
+                from
+            )?)
+        }
+
         pub fn vault_balances_query(&self) -> VaultBalancesResponse {
             self.wasm.query(
                 self.vault_addr.as_ref(),
@@ -487,6 +921,117 @@ pub mod mock {
             ).unwrap()
         }
 
+        pub fn delegated_rebalancers_query(&self) -> DelegatedRebalancersResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::DelegatedRebalancers { start_after: None, limit: None }
+            ).unwrap()
+        }
+
+        pub fn vault_info_query(&self) -> VaultInfoResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::VaultInfo {}
+            ).unwrap()
+        }
+
+        pub fn can_rebalance_query(&self, sender: &SigningAccount) -> CanRebalanceResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::CanRebalance { sender: sender.address() }
+            ).unwrap()
+        }
+
+        pub fn simulate_rebalance_query(
+            &self,
+            price: Option<Decimal>
+        ) -> Result<SimulateRebalanceResponse> {
+            Ok(self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::SimulateRebalance { price }
+            )?)
+        }
+
+        pub fn rebalance_history_query(&self, start_after: Option<u64>) -> RebalanceHistoryResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::RebalanceHistory { start_after, limit: None }
+            ).unwrap()
+        }
+
+        pub fn pending_rewards_query(&self, address: &str) -> PendingRewardsResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::PendingRewards { address: address.into() }
+            ).unwrap()
+        }
+
+        pub fn management_fee_query(&self) -> ManagementFeeResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::ManagementFee {}
+            ).unwrap()
+        }
+
+        pub fn calc_shares_query(&self, amount0: u128, amount1: u128) -> CalcSharesAndUsableAmountsResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::CalcSharesAndUsableAmounts {
+                    for_amount0: amount0.into(),
+                    for_amount1: amount1.into()
+                }
+            ).unwrap()
+        }
+
+        pub fn simulate_withdraw_query(&self, shares: Uint128) -> SimulateWithdrawResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::SimulateWithdraw { shares }
+            ).unwrap()
+        }
+
+        pub fn admin_fees_owed_query(&self) -> AdminFeesOwedResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::AdminFeesOwed {}
+            ).unwrap()
+        }
+
+        pub fn protocol_info_query(&self) -> ProtocolInfo {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::ProtocolInfo {}
+            ).unwrap()
+        }
+
+        pub fn admin_fee_query(&self) -> AdminFeeResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::AdminFee {}
+            ).unwrap()
+        }
+
+        pub fn revenue_stats_query(&self) -> RevenueStatsResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::RevenueStats {}
+            ).unwrap()
+        }
+
+        pub fn shares_unlock_query(&self, address: &str) -> SharesUnlockResponse {
+            self.wasm.query(
+                self.vault_addr.as_ref(),
+                &QueryMsg::SharesUnlock { address: address.into() }
+            ).unwrap()
+        }
+
+        pub fn is_allowed_query(&self, address: &str) -> bool {
+            self.wasm.query::<_, IsAllowedResponse>(
+                self.vault_addr.as_ref(),
+                &QueryMsg::IsAllowed { address: address.into() }
+            ).unwrap().allowed
+        }
+
         pub fn vault_fees_query(&self) -> FeesInfo {
             self.wasm.query(
                 self.vault_addr.as_ref(),
@@ -1,7 +1,11 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Binary, Uint128};
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Decimal256, Timestamp, Uint128};
 use cw20::{AllowanceResponse, BalanceResponse, Expiration, TokenInfoResponse};
-use crate::state::{FeesInfo, PositionType, VaultInfo, VaultParameters, VaultState};
+use crate::state::{
+    DelegatedRebalancer, FeeRecipient, FeesInfo, ManagementFee, PendingAdminFee, PositionType,
+    ProtocolFee, ProtocolInfo, RebalanceRecord, RebalancerPermission, ReferenceToken, RevenueStats,
+    TwapFallback, VaultInfo, VaultParameters, VaultState,
+};
 
 #[cw_serde]
 pub struct VaultParametersInstantiateMsg {
@@ -11,6 +15,9 @@ pub struct VaultParametersInstantiateMsg {
     pub limit_factor: Uint128,
     /// 18 decimal places [`Weight`].
     pub full_range_weight: Uint128,
+    /// 18 decimal places [`Weight`]. Max proportion of liquidity to move per
+    /// rebalance call, all liquidity at once if not present.
+    pub rebalance_step: Option<Uint128>,
 }
 
 #[cw_serde]
@@ -22,6 +29,139 @@ pub struct VaultInfoInstantiateMsg {
     /// 18 decimal places [`Weight`].
     pub admin_fee: Uint128,
     pub rebalancer: VaultRebalancerInstantiateMsg,
+    /// If not present, rebalances will be checked against a 60 seconds
+    /// TWAP, with a max deviation of 1%.
+    pub twap_config: Option<TwapConfigInstantiateMsg>,
+    pub keeper_reward: Option<KeeperRewardInstantiateMsg>,
+    /// Seconds without rebalances after which anyone can rebalance the vault,
+    /// with conservative conditions, until the vault rebalancer does so again.
+    /// Has no effect if the rebalancer is already `Anyone`.
+    pub inactivity_timeout: Option<u32>,
+    /// Limits on how often any rebalancer can rebalance. If not present,
+    /// theres no limit other than the ones of an `Anyone` rebalancer.
+    pub rebalance_limits: Option<RebalanceLimitsInstantiateMsg>,
+    pub circuit_breaker: Option<CircuitBreakerInstantiateMsg>,
+    pub performance_fee: Option<PerformanceFeeInstantiateMsg>,
+    /// 18 decimal places [`Weight`] of the vault value charged per year, paid
+    /// by minting vault shares to the admin. Capped like the admin fee.
+    pub management_fee: Option<Uint128>,
+    pub entry_exit_fees: Option<EntryExitFeesInstantiateMsg>,
+    pub deposit_caps: Option<DepositCapsInstantiateMsg>,
+    pub access_list: Option<AccessListInstantiateMsg>,
+    /// Seconds deposited shares cant be withdrawn nor transferred for, so deposits
+    /// right before a rebalance cant capture the fees accrued by long term holders.
+    /// Capped by [`crate::constants::MAX_WITHDRAWAL_COOLDOWN_SECONDS`].
+    pub withdrawal_cooldown: Option<u32>,
+}
+
+/// Fees on the amounts deposited and withdrawn, 18 decimal places [`Weight`]s
+/// capped by [`crate::constants::MAX_ENTRY_EXIT_FEE`].
+#[cw_serde]
+pub struct EntryExitFeesInstantiateMsg {
+    pub entry_fee: Uint128,
+    pub exit_fee: Uint128,
+    pub recipient: FeeRecipient,
+}
+
+/// Admin fee charged only on share price gains above the highest share price
+/// it was already charged at, paid by minting vault shares to the admin.
+#[cw_serde]
+pub struct PerformanceFeeInstantiateMsg {
+    /// 18 decimal places [`Weight`], capped by [`crate::constants::MAX_PERFORMANCE_FEE`].
+    pub fee: Uint128,
+    /// Token the share price is measured in.
+    pub reference_token: ReferenceToken,
+}
+
+/// The vault enters a protected state where only withdrawals work once the price
+/// deviates from its TWAP over `twap_seconds` by more than `trip_deviation`, and
+/// stays so until it deviates by at most `settle_deviation`, or the admin clears it.
+/// Deviations are Uint128 Decimals in the range [0, 1].
+#[cw_serde]
+pub struct CircuitBreakerInstantiateMsg {
+    pub twap_seconds: u32,
+    pub trip_deviation: Uint128,
+    pub settle_deviation: Uint128,
+}
+
+/// Limits on how much the vault accepts. Values are atomic amounts of token1,
+/// with token0 valued at the pool price. Deposits above the caps are partly
+/// accepted, and the rest refunded. The per address cap also applies to share
+/// transfers.
+#[cw_serde]
+pub struct DepositCapsInstantiateMsg {
+    pub max_total_shares: Option<Uint128>,
+    pub max_total_value: Option<Uint128>,
+    pub max_shares_per_address: Option<Uint128>,
+    pub min_deposit_value: Option<Uint128>,
+}
+
+/// Addresses allowed to hold vault shares, checked on deposits, share transfers
+/// and fee share mints.
+/// Withdrawals are never gated, so holders can always leave the vault.
+#[cw_serde]
+pub enum AccessListInstantiateMsg {
+    Allowlist { addresses: Vec<String> },
+    Blocklist { addresses: Vec<String> },
+    /// The `registry` contract is asked [`AccessRegistryQueryMsg::IsAllowed`] for
+    /// each address, and an address is not allowed if the query fails.
+    Registry { registry: String },
+}
+
+/// Query an access list registry must answer with an [`IsAllowedResponse`].
+/// Vaults answer it too, so a vault can be the registry of others.
+#[cw_serde]
+pub enum AccessRegistryQueryMsg {
+    IsAllowed { address: String },
+}
+
+#[cw_serde]
+pub struct HarvestHop {
+    pub pool_id: u64,
+    pub token_out_denom: String,
+}
+
+/// Poolmanager route to swap a reward token into one of the vault tokens, at
+/// most `max_slippage` below what the TWAP over `twap_seconds` of each hop
+/// would give. `max_slippage` is a Uint128 Decimal in the range [0, 1].
+#[cw_serde]
+pub struct HarvestRouteInstantiateMsg {
+    pub hops: Vec<HarvestHop>,
+    pub twap_seconds: u32,
+    pub max_slippage: Uint128,
+}
+
+/// Vault-wide limits on how often rebalances, limit rebalances and compounds
+/// can happen, whoever the rebalancer is. The steps of a gradual rebalance
+/// count as a single rebalance.
+#[cw_serde]
+pub struct RebalanceLimitsInstantiateMsg {
+    pub min_seconds_between_rebalances: u32,
+    pub max_rebalances_per_day: Option<u32>,
+}
+
+/// Reward for whoever triggers a rebalance, set aside from the spread fees
+/// collected while the vault rebalancer is `Anyone`.
+#[cw_serde]
+pub struct KeeperRewardInstantiateMsg {
+    /// 18 decimal places [`Weight`], capped like the admin fee. Share of the
+    /// collected spread fees set aside for keepers.
+    pub share: Uint128,
+    /// Max amount of token0 paid per rebalance call, the rest is kept for the
+    /// next ones. With a large enough `share`, its a flat reward per call.
+    pub max_amount0: Option<Uint128>,
+    /// Max amount of token1 paid per rebalance call, see `max_amount0`.
+    pub max_amount1: Option<Uint128>,
+}
+
+/// Rebalances of any kind will only be allowed if the current price is
+/// within `max_deviation` of the pool TWAP over the last `seconds`.
+#[cw_serde]
+pub struct TwapConfigInstantiateMsg {
+    pub seconds: u32,
+    /// 18 decimal places [`Weight`].
+    pub max_deviation: Uint128,
+    pub on_failure: TwapFallback,
 }
 
 #[cw_serde]
@@ -38,14 +178,48 @@ pub enum VaultRebalancerInstantiateMsg {
         /// rebalance if the price has moved this factor since the last rebalance.
         price_factor_before_rebalance: Uint128,
         /// Anyone can only rebalance if this time has passed since the last rebalace.
-        seconds_before_rebalance: u32
+        seconds_before_rebalance: u32,
+        /// If present, replaces the rule given by the two parameters above, which
+        /// would be equivalent to `And([TimePassed, PriceMoved])`.
+        policy: Option<RebalancePolicyInstantiateMsg>
     }
 }
 
+/// Conditions under which anyone can rebalance, see [`VaultRebalancerInstantiateMsg::Anyone`].
+/// All conditions are relative to the last rebalance.
+#[cw_serde]
+pub enum RebalancePolicyInstantiateMsg {
+    /// All policies must pass.
+    And(Vec<RebalancePolicyInstantiateMsg>),
+    /// At least one policy must pass.
+    Or(Vec<RebalancePolicyInstantiateMsg>),
+    TimePassed { seconds: u32 },
+    /// 18 decimal places [`PriceFactor`]. Passes if the price moved this
+    /// factor in any direction.
+    PriceMoved { price_factor: Uint128 },
+    /// Passes if the current tick is outside the base position range.
+    PriceOutsideBaseRange {},
+    /// Passes if the price crossed the whole limit position range.
+    LimitOrderFilled {},
+    /// 18 decimal places [`Weight`]. Passes if the vault idle balances are
+    /// at least this share of the vault value.
+    IdleBalanceAbove { share: Uint128 },
+    /// Passes if the uncollected position fees are worth at least
+    /// this amount of token1.
+    UncollectedFeesAbove { value: Uint128 },
+}
+
 #[cw_serde]
 pub struct InstantiateMsg {
     pub vault_info: VaultInfoInstantiateMsg,
-    pub vault_parameters: VaultParametersInstantiateMsg
+    pub vault_parameters: VaultParametersInstantiateMsg,
+    /// Account allowed to do protocol operations, also the initial protocol
+    /// fee recipient.
+    pub protocol: String,
+    /// Costs accepted to create the vault, any one of which must be paid at
+    /// instantiation. Each capped by [`crate::constants::MAX_VAULT_CREATION_COSTS`].
+    /// Empty if the vault is free to create.
+    pub vault_creation_costs: Vec<Coin>
 }
 
 #[cw_serde]
@@ -63,23 +237,96 @@ pub struct WithdrawMsg {
     pub to: String
 }
 
+#[cw_serde]
+#[derive(Default)]
+pub struct FeesWithdrawalMsg {
+    /// All owned token0 fees if not present.
+    pub amount0: Option<Uint128>,
+    /// All owned token1 fees if not present.
+    pub amount1: Option<Uint128>,
+    /// The usual fees recipient if not present.
+    pub recipient: Option<String>,
+    /// Whether to first collect the spread fees still in the vault positions.
+    pub collect_spread_rewards: bool
+}
+
 #[cw_serde]
 pub enum ExecuteMsg {
     // Core Logic.
     Deposit(DepositMsg),
     Rebalance {},
     Withdraw(WithdrawMsg),
+    /// Moves the limit position next to the current price, with all vault
+    /// idle funds of the token it holds. Cant be done if the price is inside
+    /// the limit position range.
+    RebalanceLimit {},
+    /// Collects the spread fees of all positions into the vault balances, so
+    /// the next rebalance puts them to work.
+    Compound {},
+    /// Sends the sender all its pending reward tokens, i.e., tokens of the
+    /// registered reward denoms sent to the vault, distributed pro rata to shares.
+    ClaimRewards {},
+    /// Swaps the reward tokens with a harvest route into the vault tokens,
+    /// instead of distributing them, see [`HarvestRouteInstantiateMsg`].
+    HarvestIncentives {},
 
     // Admin/Protocol operations.
-    WithdrawProtocolFees {},
-    WithdrawAdminFees {},
+    /// The vault creation tokens still owned are always withdrawn in full,
+    /// besides `amount0` and `amount1`, even if in one of the vault denoms.
+    WithdrawProtocolFees(FeesWithdrawalMsg),
+    /// Without a `recipient`, its split among the admin fee beneficiaries.
+    WithdrawAdminFees(FeesWithdrawalMsg),
     ProposeNewAdmin { new_admin: Option<String> },
     AcceptNewAdmin {},
     BurnVaultAdmin {},
     ChangeVaultRebalancer(VaultRebalancerInstantiateMsg),
+    /// Adds or updates an address that can operate the vault positions besides
+    /// the vault rebalancer, see [`RebalancerPermission`].
+    AddDelegatedRebalancer {
+        address: String,
+        permission: RebalancerPermission,
+        min_seconds_between_calls: u32
+    },
+    RemoveDelegatedRebalancer { address: String },
     ChangeVaultParameters(VaultParametersInstantiateMsg),
+    /// Stricter configs apply right away, looser ones only after
+    /// [`crate::constants::REBALANCE_LIMITS_TIMELOCK_SECONDS`].
+    ChangeTwapConfig(Option<TwapConfigInstantiateMsg>),
+    ChangeInactivityTimeout { inactivity_timeout: Option<u32> },
+    /// Stricter limits apply right away, looser ones only after
+    /// [`crate::constants::REBALANCE_LIMITS_TIMELOCK_SECONDS`].
+    ChangeRebalanceLimits(RebalanceLimitsInstantiateMsg),
+    ChangeCircuitBreaker(Option<CircuitBreakerInstantiateMsg>),
+    /// Lowering the caps below the current deposits only stops new deposits.
+    ChangeDepositCaps(Option<DepositCapsInstantiateMsg>),
+    /// Current holders outside the new list keep their shares, but cant move them.
+    ChangeAccessList(Option<AccessListInstantiateMsg>),
+    /// Removing the cooldown unlocks all shares, while changing it only affects new deposits.
+    ChangeWithdrawalCooldown { withdrawal_cooldown: Option<u32> },
+    ClearCircuitBreaker {},
+    /// Sets whether the reward token `denom` is distributed to shareholders. Only
+    /// registered denoms are, so tokens sent by anyone cant bloat share transfers.
+    ChangeRewardDenom { denom: String, distribute: bool },
+    /// Sets or removes the harvest route of the reward token `denom`.
+    ChangeHarvestRoute { denom: String, route: Option<HarvestRouteInstantiateMsg> },
+    CancelRebalance {},
+    /// Decreases apply right away, while increases are capped and only apply after
+    /// [`crate::constants::ADMIN_FEE_INCREASE_NOTICE_SECONDS`].
     ChangeAdminFee { new_admin_fee: Uint128 },
+    ChangeKeeperReward(Option<KeeperRewardInstantiateMsg>),
+    /// Keeps the high-water mark, unless the reference token changes.
+    ChangePerformanceFee(Option<PerformanceFeeInstantiateMsg>),
+    /// Accrues the current management fee before changing it.
+    ChangeManagementFee { new_management_fee: Option<Uint128> },
+    ChangeEntryExitFees(Option<EntryExitFeesInstantiateMsg>),
+    /// Splits the admin fees among `(address, weight)` pairs, with 18 decimal
+    /// places [`Weight`]s adding up to one. If empty, the admin gets them all.
+    ChangeAdminFeeBeneficiaries { beneficiaries: Vec<(String, Uint128)> },
     ChangeProtocolFee { new_protocol_fee: Uint128 },
+    ProposeNewProtocol { new_protocol: Option<String> },
+    AcceptNewProtocol {},
+    /// Only changes where the protocol fees are paid, not who operates them.
+    ChangeProtocolFeeRecipient { recipient: String },
 
     // Cw20 Realization.
     Transfer { recipient: String, amount: Uint128 },
@@ -113,10 +360,45 @@ pub enum QueryMsg {
     VaultParameters {},
     #[returns(TokenInfoResponse)]
     TokenInfo {},
-    #[returns(VaultInfo)]
+    #[returns(VaultInfoResponse)]
     VaultInfo {},
     #[returns(FeesInfo)]
-    FeesInfo {}
+    FeesInfo {},
+    #[returns(ProtocolInfo)]
+    ProtocolInfo {},
+    #[returns(DelegatedRebalancersResponse)]
+    DelegatedRebalancers { start_after: Option<String>, limit: Option<u32> },
+    /// Whether `sender` could rebalance the vault now, without side effects.
+    #[returns(CanRebalanceResponse)]
+    CanRebalance { sender: String },
+    /// Computes the outcome of a `Rebalance` at `price`, or the current
+    /// pool price if not present, without checking if its allowed.
+    #[returns(SimulateRebalanceResponse)]
+    SimulateRebalance { price: Option<Decimal> },
+    #[returns(RebalanceHistoryResponse)]
+    RebalanceHistory { start_after: Option<u64>, limit: Option<u32> },
+    #[returns(PendingRewardsResponse)]
+    PendingRewards { address: String },
+    #[returns(ManagementFeeResponse)]
+    ManagementFee {},
+    /// Amounts a withdrawal of `shares` would get right now, after exit fees.
+    #[returns(SimulateWithdrawResponse)]
+    SimulateWithdraw { shares: Uint128 },
+    /// Admin fees owed to each beneficiary, as paid by `WithdrawAdminFees`.
+    #[returns(AdminFeesOwedResponse)]
+    AdminFeesOwed {},
+    /// Admin fee in effect now, and any increase pending its notice period.
+    #[returns(AdminFeeResponse)]
+    AdminFee {},
+    /// Lifetime fees and revenue of each denom, see [`RevenueStats`].
+    #[returns(RevenueStatsResponse)]
+    RevenueStats {},
+    /// Whether `address` can hold vault shares, see [`AccessListInstantiateMsg`].
+    #[returns(IsAllowedResponse)]
+    IsAllowed { address: String },
+    /// Shares of `address` still locked by the withdrawal cooldown, and when they unlock.
+    #[returns(SharesUnlockResponse)]
+    SharesUnlock { address: String }
 }
 
 #[cw_serde]
@@ -129,6 +411,11 @@ pub struct VaultBalancesResponse {
     pub protocol_unclaimed_fees1: Uint128,
     pub admin_unclaimed_fees0: Uint128,
     pub admin_unclaimed_fees1: Uint128,
+    pub keeper_unclaimed_fees0: Uint128,
+    pub keeper_unclaimed_fees1: Uint128,
+    /// All uncollected spread fees of the vault positions, before any cut.
+    pub spread_fees0: Uint128,
+    pub spread_fees1: Uint128,
 }
 
 #[cw_serde]
@@ -143,8 +430,144 @@ pub struct PositionBalancesWithFeesResponse {
 #[cw_serde]
 #[derive(Default)]
 pub struct CalcSharesAndUsableAmountsResponse {
+    /// Shares minted to the depositor, after the entry fee.
     pub shares: Uint128,
     pub usable_amount0: Uint128,
-    pub usable_amount1: Uint128
+    pub usable_amount1: Uint128,
+    /// Part of the usable amounts taken as entry fee.
+    pub entry_fee0: Uint128,
+    pub entry_fee1: Uint128,
+    /// Shares that can still be minted before the total shares cap, if any.
+    pub remaining_shares: Option<Uint128>,
+    /// Value that can still be deposited before the total value cap, if any.
+    pub remaining_value: Option<Uint128>
 }
 
+#[cw_serde]
+#[derive(Default)]
+pub struct SimulateWithdrawResponse {
+    /// Amounts sent to the withdrawer, after the exit fee.
+    pub amount0: Uint128,
+    pub amount1: Uint128,
+    pub exit_fee0: Uint128,
+    pub exit_fee1: Uint128
+}
+
+#[cw_serde]
+pub struct DelegatedRebalancersResponse {
+    pub rebalancers: Vec<(Addr, DelegatedRebalancer)>
+}
+
+#[cw_serde]
+pub struct RebalanceHistoryResponse {
+    pub rebalances: Vec<(u64, RebalanceRecord)>
+}
+
+#[cw_serde]
+pub struct SharesUnlockResponse {
+    pub unlocks_at: Option<Timestamp>,
+    /// Shares that cant be withdrawn or transferred until `unlocks_at`.
+    pub locked_shares: Uint128
+}
+
+#[cw_serde]
+pub struct IsAllowedResponse {
+    pub allowed: bool
+}
+
+#[cw_serde]
+pub struct RevenueStatsResponse {
+    pub stats: Vec<(String, RevenueStats)>
+}
+
+#[cw_serde]
+pub struct AdminFeeResponse {
+    pub admin_fee: ProtocolFee,
+    pub pending_admin_fee: Option<PendingAdminFee>
+}
+
+#[cw_serde]
+pub struct AdminFeesOwedResponse {
+    pub owed: Vec<(Addr, Vec<Coin>)>
+}
+
+#[cw_serde]
+pub struct ManagementFeeResponse {
+    pub management_fee: Option<ManagementFee>,
+    /// Shares that would be minted to the admin if the fee accrued now.
+    pub accrued_shares: Uint128
+}
+
+#[cw_serde]
+pub struct PendingRewardsResponse {
+    pub rewards: Vec<Coin>
+}
+
+#[cw_serde]
+pub struct VaultInfoResponse {
+    pub info: VaultInfo,
+    /// See [`VaultInfo::fallback_rebalancer_active`].
+    pub fallback_rebalancer_active: bool
+}
+
+#[cw_serde]
+pub enum CantRebalanceReason {
+    Unauthorized {},
+    RebalancedThisBlock {},
+    NotEnoughTimePassed { time_left: u64 },
+    MaxRebalancesPerDayReached { max: u32 },
+    CircuitBreakerTripped {},
+    /// The price has to go out of `[lower_bound, upper_bound]`.
+    PriceHasntMovedEnough { lower_bound: Decimal, upper_bound: Decimal, distance_left: Decimal },
+    PolicyNotMet { reason: String },
+    PriceDeviatesFromTwap { twap: Decimal, max_deviation: Decimal },
+    TwapUnavailable { seconds: u64 },
+    NothingToRebalance {},
+    PoolWithoutPrice {},
+    /// Any other [`crate::error::RebalanceError`].
+    Other { reason: String },
+}
+
+#[cw_serde]
+pub struct CanRebalanceResponse {
+    /// `None` if the rebalance would pass all checks.
+    pub reason: Option<CantRebalanceReason>,
+    pub price: Decimal,
+    /// When known, the earliest time at which the rebalance could be allowed.
+    pub next_allowed_time: Option<Timestamp>,
+    /// When known, the rebalance wont be allowed until the price
+    /// goes out of this range.
+    pub next_allowed_price_range: Option<(Decimal, Decimal)>,
+}
+
+#[cw_serde]
+pub struct PlannedPosition {
+    pub position_type: PositionType,
+    pub lower_tick: i64,
+    pub upper_tick: i64,
+    pub amount0: Uint128,
+    pub amount1: Uint128,
+    pub min_amount0: Uint128,
+    pub min_amount1: Uint128,
+    /// Expected liquidity of the position at the simulated price.
+    pub liquidity: Decimal256,
+}
+
+#[cw_serde]
+pub struct SimulateRebalanceResponse {
+    pub price: Decimal,
+    /// If present, the rebalance would just be a step of a gradual rebalance,
+    /// moving this proportion of the positions liquidity at its start to the
+    /// vault idle funds. In such case, `positions` is empty.
+    pub gradual_step: Option<Decimal>,
+    pub positions: Vec<PlannedPosition>,
+    /// Spread rewards that would be collected from the current positions.
+    pub collected_fees0: Uint128,
+    pub collected_fees1: Uint128,
+    pub protocol_fees0: Uint128,
+    pub protocol_fees1: Uint128,
+    pub admin_fees0: Uint128,
+    pub admin_fees1: Uint128,
+    pub keeper_fees0: Uint128,
+    pub keeper_fees1: Uint128,
+}
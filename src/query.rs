@@ -1,26 +1,49 @@
-use std::{cmp, str::FromStr};
+use std::{cmp, collections::BTreeMap, str::FromStr};
 
-use cosmwasm_std::{Deps, Uint128, Uint256};
-use cw20_base::state::TOKEN_INFO;
-use osmosis_std::types::osmosis::concentratedliquidity::v1beta1::PositionByIdRequest;
+use cosmwasm_std::{
+    coin, Addr, Decimal, Decimal256, Deps, Env, Order, StdError, StdResult, Timestamp, Uint128,
+    Uint256,
+};
+use cw20_base::state::{BALANCES, TOKEN_INFO};
+use cw_storage_plus::Bound;
+use osmosis_std::types::osmosis::concentratedliquidity::v1beta1::{
+    MsgCreatePosition, PositionByIdRequest,
+};
 
 use crate::{
-    constants::MIN_LIQUIDITY,
+    constants::{DEFAULT_QUERY_LIMIT, MAX_QUERY_LIMIT, MIN_LIQUIDITY, MIN_TICK},
     do_me, do_ok,
+    error::RebalanceError,
+    execute,
     msg::{
-        CalcSharesAndUsableAmountsResponse, PositionBalancesWithFeesResponse, VaultBalancesResponse,
+        AdminFeeResponse, AdminFeesOwedResponse, CalcSharesAndUsableAmountsResponse,
+        CanRebalanceResponse, CantRebalanceReason, DelegatedRebalancersResponse, IsAllowedResponse,
+        ManagementFeeResponse, PendingRewardsResponse, PlannedPosition,
+        PositionBalancesWithFeesResponse, RebalanceHistoryResponse, RevenueStatsResponse,
+        SharesUnlockResponse, SimulateRebalanceResponse, SimulateWithdrawResponse,
+        VaultBalancesResponse, VaultInfoResponse,
+    },
+    state::{
+        FeesInfo, FundsInfo, PositionSnapshot, PositionType, RewardIndex, ShareholderReward,
+        SharesLock, VaultRebalancer, Weight, DELEGATED_REBALANCERS, FEES_INFO, FUNDS_INFO,
+        HARVEST_ROUTES, REBALANCE_HISTORY, REVENUE_STATS, REWARD_DENOMS, REWARD_INDEXES,
+        SHAREHOLDER_REWARDS, SHARES_LOCKS, VAULT_INFO, VAULT_PARAMETERS, VAULT_STATE,
+    },
+    utils::{
+        amounts_for_liquidity, liquidity_for_amounts, price_function, price_function_inv,
+        value_in_token1,
     },
-    state::{FundsInfo, PositionType, FEES_INFO, FUNDS_INFO, VAULT_INFO, VAULT_STATE},
 };
 
-/// Partition available balances to the vault in 3 sets:
+/// Partition available balances to the vault in 4 sets:
 /// - Balances available for business logic, e.g., for creating new positions.
 /// - Idle protocol fees, not yet claimed nor commited to the state.
 /// - Idle vault admin fees, not yet claimed nor commited to the state.
+/// - Idle keeper rewards, not yet claimed nor commited to the state.
 ///
 /// For this, query the fees and balances in all current vault positions and 
 /// funds tracked by [`FUNDS_INFO`] and [`FEES_INFO`].
-pub fn vault_balances(deps: Deps) -> VaultBalancesResponse {
+pub fn vault_balances(deps: Deps, env: &Env) -> VaultBalancesResponse {
     let full_range_balances = position_balances_with_fees(PositionType::FullRange, deps);
     let base_balances = position_balances_with_fees(PositionType::Base, deps);
     let limit_balances = position_balances_with_fees(PositionType::Limit, deps);
@@ -29,11 +52,19 @@ pub fn vault_balances(deps: Deps) -> VaultBalancesResponse {
     let FundsInfo { available_balance0, available_balance1 } = FUNDS_INFO
         .load(deps.storage).unwrap();
 
-    let fees = FEES_INFO.load(deps.storage).unwrap();
+    let fees = FEES_INFO.load(deps.storage).unwrap().apply_pending_admin_fee(env.block.time);
+
+    // NOTE: Keepers are only rewarded for permissionless rebalances.
+    let keeper_share = match (&fees.keeper_reward, VAULT_INFO.load(deps.storage).unwrap().rebalancer) {
+        (Some(reward), VaultRebalancer::Anyone { .. }) => reward.share.0.clone(),
+        _ => Weight::zero()
+    };
 
     // Invariant: Wont panic.
     // Proof: If the contract has unclaimed fees, we know its balance will at
-    //        least be those fees, so the subtractions wont underflow. Any
+    //        least be those fees, so the subtractions wont underflow, as the
+    //        protocol fee, admin fee and keeper share are each capped by
+    //        `MAX_PROTOCOL_FEE`, and thus add up to less than one. Any
     //        addition of token amounts wont overflow, because for that the
     //        token supply of any token would have to be above `Uint128::MAX`.
     //        Products wont overflow, as we know the fees are valid weights.
@@ -62,13 +93,22 @@ pub fn vault_balances(deps: Deps) -> VaultBalancesResponse {
             .mul_raw(total_token1_fees)
             .atomics();
 
+        let keeper_unclaimed_fees0 = keeper_share
+            .mul_raw(total_token0_fees)
+            .atomics();
+
+        let keeper_unclaimed_fees1 = keeper_share
+            .mul_raw(total_token1_fees)
+            .atomics();
+
         let bal0 = available_balance0
             .checked_add(full_range_balances.bal0)?
             .checked_add(base_balances.bal0)?
             .checked_add(limit_balances.bal0)?
             .checked_add(total_token0_fees)?
             .checked_sub(protocol_unclaimed_fees0)?
-            .checked_sub(admin_unclaimed_fees0)?;
+            .checked_sub(admin_unclaimed_fees0)?
+            .checked_sub(keeper_unclaimed_fees0)?;
 
         let bal1 = available_balance1
             .checked_add(full_range_balances.bal1)?
@@ -76,12 +116,16 @@ pub fn vault_balances(deps: Deps) -> VaultBalancesResponse {
             .checked_add(limit_balances.bal1)?
             .checked_add(total_token1_fees)?
             .checked_sub(protocol_unclaimed_fees1)?
-            .checked_sub(admin_unclaimed_fees1)?;
+            .checked_sub(admin_unclaimed_fees1)?
+            .checked_sub(keeper_unclaimed_fees1)?;
         
         VaultBalancesResponse { 
             bal0, bal1,
             protocol_unclaimed_fees0, protocol_unclaimed_fees1,
-            admin_unclaimed_fees0, admin_unclaimed_fees1
+            admin_unclaimed_fees0, admin_unclaimed_fees1,
+            keeper_unclaimed_fees0, keeper_unclaimed_fees1,
+            spread_fees0: total_token0_fees,
+            spread_fees1: total_token1_fees
         }
     }.unwrap()
 }
@@ -160,19 +204,128 @@ pub fn position_balances_with_fees(
     }
 }
 
+/// # Returns
+///
+/// The tick range `(lower_tick, upper_tick)` of the vault `position_type`
+/// position, if the vault has that position open.
+pub fn position_range(position_type: PositionType, deps: Deps) -> Option<(i64, i64)> {
+    // Invariant: `VAULT_STATE` will always be present after instantiation.
+    let id = VAULT_STATE.load(deps.storage).unwrap().from_position_type(position_type)?;
+
+    // Invariant: We verified `id` is a valid position id the moment
+    //            we put it in the state, so the query wont fail.
+    let pos = PositionByIdRequest { position_id: id }
+        .query(&deps.querier)
+        .map(|x| x.position.unwrap().position.unwrap())
+        .unwrap();
+
+    Some((pos.lower_tick, pos.upper_tick))
+}
+
+/// Shares minted for depositing `input_amount0` and `input_amount1`, and the
+/// amounts actually used, of which the entry fee is a part.
+///
 /// # Arguments
 ///
 /// * `input_amount0` - Amount of token0 for which we want to calculate shares for, 
-///                     not yet in the contract state ([`FUNDS_INFO`]).
+///   not yet in the contract state ([`FUNDS_INFO`]).
 ///
 /// * `input_amount1` - Amount of token1 for which we want to calculate shares for, 
-///                     not yet in the contract state ([`FUNDS_INFO`]).
+///   not yet in the contract state ([`FUNDS_INFO`]).
 pub fn calc_shares_and_usable_amounts(
     input_amount0: Uint128,
     input_amount1: Uint128,
-    deps: Deps
+    deps: Deps,
+    env: &Env
+) -> CalcSharesAndUsableAmountsResponse {
+    calc_shares_and_usable_amounts_for(input_amount0, input_amount1, None, deps, env)
+}
+
+/// Same as [`calc_shares_and_usable_amounts`], but also applying the per address
+/// deposit cap to `holder`, if present. Deposits above the caps are scaled down,
+/// up to rounding, so that only the part within the caps is usable.
+pub fn calc_shares_and_usable_amounts_for(
+    input_amount0: Uint128,
+    input_amount1: Uint128,
+    holder: Option<&Addr>,
+    deps: Deps,
+    env: &Env
+) -> CalcSharesAndUsableAmountsResponse {
+    let res = calc_shares_and_usable_amounts_after_fees(input_amount0, input_amount1, deps, env);
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let Some(caps) = &vault_info.deposit_caps else {
+        return res
+    };
+
+    let VaultBalancesResponse { bal0, bal1, .. } = vault_balances(deps, env);
+    let price = vault_info.pool_id.price(&deps.querier);
+    let total_value = value_in_token1(bal0, bal1, &price);
+    // Invariant: `TOKEN_INFO` always present after instantiation.
+    let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
+    let holder_shares = holder
+        .and_then(|x| BALANCES.may_load(deps.storage, x).unwrap())
+        .unwrap_or_default();
+
+    // Invariant: Wont overflow, as the shares are never above the inputed amounts.
+    let minted = if total_supply.is_zero() { res.shares + MIN_LIQUIDITY } else { res.shares };
+    let part = caps.accepted_part(
+        res.shares,
+        minted,
+        value_in_token1(res.usable_amount0, res.usable_amount1, &price),
+        total_supply,
+        total_value,
+        holder_shares
+    );
+
+    let res = if part.is_max() { res } else {
+        let amount0 = part.mul_raw(res.usable_amount0).atomics();
+        let amount1 = part.mul_raw(res.usable_amount1).atomics();
+
+        // NOTE: A first deposit must still be above the min liquidity.
+        if part.is_zero() || (total_supply.is_zero() && cmp::max(amount0, amount1) <= MIN_LIQUIDITY) {
+            CalcSharesAndUsableAmountsResponse::default()
+        } else {
+            calc_shares_and_usable_amounts_after_fees(amount0, amount1, deps, env)
+        }
+    };
+
+    CalcSharesAndUsableAmountsResponse {
+        remaining_shares: caps.max_total_shares.map(|x| x.saturating_sub(total_supply)),
+        remaining_value: caps.max_total_value.map(|x| x.saturating_sub(total_value)),
+        ..res
+    }
+}
+
+fn calc_shares_and_usable_amounts_after_fees(
+    input_amount0: Uint128,
+    input_amount1: Uint128,
+    deps: Deps,
+    env: &Env
+) -> CalcSharesAndUsableAmountsResponse {
+    let res = calc_shares_and_usable_amounts_before_fees(input_amount0, input_amount1, deps, env);
+    // Invariant: Any state is present after instantiation.
+    let entry_fee = FEES_INFO.load(deps.storage).unwrap().entry_fee();
+
+    // NOTE: Shares are proportional to the usable amounts, so taking the fee
+    //       from both is the same as taking it from the shares.
+    // Invariant: Wont underflow, as the fee is a valid weight.
+    CalcSharesAndUsableAmountsResponse {
+        shares: res.shares.checked_sub(entry_fee.mul_raw(res.shares).atomics()).unwrap(),
+        entry_fee0: entry_fee.mul_raw(res.usable_amount0).atomics(),
+        entry_fee1: entry_fee.mul_raw(res.usable_amount1).atomics(),
+        ..res
+    }
+}
+
+fn calc_shares_and_usable_amounts_before_fees(
+    input_amount0: Uint128,
+    input_amount1: Uint128,
+    deps: Deps,
+    env: &Env
 ) -> CalcSharesAndUsableAmountsResponse {
-    let VaultBalancesResponse { bal0: total0, bal1: total1, .. } = vault_balances(deps);
+    let VaultBalancesResponse { bal0: total0, bal1: total1, .. } = vault_balances(deps, env);
 
     // Invariant: `TOKEN_INFO` always present after instantiation.
     let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
@@ -185,6 +338,7 @@ pub fn calc_shares_and_usable_amounts(
                 .checked_sub(MIN_LIQUIDITY).unwrap(),
             usable_amount0: input_amount0,
             usable_amount1: input_amount1,
+            ..Default::default()
         }
     } else if total0.is_zero() {
         // Invariant: If there are shares and there are no tokens
@@ -209,6 +363,7 @@ pub fn calc_shares_and_usable_amounts(
             shares,
             usable_amount0: Uint128::zero(),
             usable_amount1: input_amount1,
+            ..Default::default()
         }
     } else if total1.is_zero() {
         // Invariant: If there are shares and there are no tokens
@@ -226,6 +381,7 @@ pub fn calc_shares_and_usable_amounts(
             shares,
             usable_amount0: input_amount0,
             usable_amount1: Uint128::zero(),
+            ..Default::default()
         }
     } else {
         let input_amount0: Uint256 = input_amount0.into();
@@ -265,8 +421,541 @@ pub fn calc_shares_and_usable_amounts(
                 shares,
                 usable_amount0,
                 usable_amount1,
+                ..Default::default()
             }
         }.unwrap()
     }
 }
 
+pub fn delegated_rebalancers(
+    start_after: Option<String>,
+    limit: Option<u32>,
+    deps: Deps
+) -> StdResult<DelegatedRebalancersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+    let start_after = start_after.map(|x| deps.api.addr_validate(&x)).transpose()?;
+
+    let rebalancers = DELEGATED_REBALANCERS
+        .range(deps.storage, start_after.as_ref().map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+
+    Ok(DelegatedRebalancersResponse { rebalancers })
+}
+
+pub fn rebalance_history(
+    start_after: Option<u64>,
+    limit: Option<u32>,
+    deps: Deps
+) -> StdResult<RebalanceHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_QUERY_LIMIT).min(MAX_QUERY_LIMIT) as usize;
+
+    let rebalances = REBALANCE_HISTORY
+        .range(deps.storage, start_after.map(Bound::exclusive), None, Order::Ascending)
+        .take(limit)
+        .collect::<StdResult<_>>()?;
+
+    Ok(RebalanceHistoryResponse { rebalances })
+}
+
+/// Ticks and balances of the current vault positions, scaled by `proportion`.
+pub fn position_snapshots(proportion: &Weight, deps: Deps) -> Vec<PositionSnapshot> {
+    [PositionType::FullRange, PositionType::Base, PositionType::Limit]
+        .into_iter()
+        .filter_map(|position_type| {
+            let (lower_tick, upper_tick) = position_range(position_type.clone(), deps)?;
+            let balances = position_balances_with_fees(position_type.clone(), deps);
+            Some(PositionSnapshot {
+                position_type,
+                lower_tick,
+                upper_tick,
+                amount0: proportion.mul_raw(balances.bal0).atomics(),
+                amount1: proportion.mul_raw(balances.bal1).atomics()
+            })
+        })
+        .collect()
+}
+
+/// Amounts of each vault token provided by `msg`.
+pub fn provided_amounts(msg: &MsgCreatePosition, deps: Deps) -> (Uint128, Uint128) {
+    // Invariant: `VAULT_INFO` will always be present after instantiation.
+    let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
+    // Invariant: Position creation messages only have valid amounts.
+    let amount = |denom: &str| msg.tokens_provided
+        .iter()
+        .find(|x| x.denom == denom)
+        .map(|x| Uint128::from_str(&x.amount).unwrap())
+        .unwrap_or_default();
+    (amount(&denom0), amount(&denom1))
+}
+
+pub fn vault_info(deps: Deps, env: Env) -> VaultInfoResponse {
+    // Invariant: Any state is present after instantiation.
+    let info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    let fallback_rebalancer_active = info.fallback_rebalancer_active(&vault_state, env.block.time);
+    VaultInfoResponse { info, fallback_rebalancer_active }
+}
+
+/// Runs the same checks as a `Rebalance` from `sender` would, see [`execute::can_rebalance`].
+pub fn can_rebalance(sender: String, deps: Deps, env: Env) -> StdResult<CanRebalanceResponse> {
+    use RebalanceError::*;
+
+    let sender = deps.api.addr_validate(&sender)?;
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    let price = vault_info.pool_id.price(&deps.querier);
+
+    let error = match execute::can_rebalance(&sender, deps, &env) {
+        Err(err) => Some(err),
+        Ok(_) => {
+            let VaultBalancesResponse { bal0, bal1, .. } = vault_balances(deps, &env);
+            if bal0.is_zero() && bal1.is_zero() {
+                Some(NothingToRebalance {})
+            } else if price.is_zero() {
+                Some(PoolWithoutPrice(vault_info.pool_id.0))
+            } else { None }
+        }
+    };
+
+    let (reason, next_allowed_time, next_allowed_price_range) = match error {
+        None => (None, None, None),
+        Some(
+            UnauthorhizedNonAdminAccount { .. }
+            | UnauthorizedDelegateAccount { .. }
+            | UnauthorizedRebalancer { .. }
+        ) => {
+            // NOTE: Anyone can rebalance once the vault rebalancer is inactive for long enough.
+            let fallback_time = vault_info.inactivity_timeout
+                .zip(vault_state.last_price_and_timestamp)
+                .map(|(timeout, snapshot)| snapshot.last_timestamp.plus_seconds(timeout.seconds()));
+            (Some(CantRebalanceReason::Unauthorized {}), fallback_time, None)
+        },
+        Some(CircuitBreakerTripped {}) =>
+            (Some(CantRebalanceReason::CircuitBreakerTripped {}), None, None),
+        Some(CantRebalanceTwicePerBlock()) =>
+            (Some(CantRebalanceReason::RebalancedThisBlock {}), None, None),
+        Some(NotEnoughTimePassed { time_left }) => (
+            Some(CantRebalanceReason::NotEnoughTimePassed { time_left }),
+            Some(env.block.time.plus_seconds(time_left)),
+            None
+        ),
+        Some(MaxRebalancesPerDayReached { max, time_left }) => (
+            Some(CantRebalanceReason::MaxRebalancesPerDayReached { max }),
+            Some(env.block.time.plus_seconds(time_left)),
+            None
+        ),
+        Some(PriceHasntMovedEnough { lower_bound, upper_bound, .. }) => {
+            let (lower_bound, upper_bound) = (Decimal::new(lower_bound), Decimal::new(upper_bound));
+            let distance_left = cmp::min(price.abs_diff(lower_bound), upper_bound.abs_diff(price));
+            (
+                Some(CantRebalanceReason::PriceHasntMovedEnough { lower_bound, upper_bound, distance_left }),
+                None,
+                Some((lower_bound, upper_bound))
+            )
+        },
+        Some(RebalancePolicyNotMet { reason }) =>
+            (Some(CantRebalanceReason::PolicyNotMet { reason }), None, None),
+        Some(PriceDeviatesFromTwap { twap, max_deviation, .. }) => (
+            Some(CantRebalanceReason::PriceDeviatesFromTwap {
+                twap: Decimal::new(twap),
+                max_deviation: Decimal::new(max_deviation)
+            }),
+            None,
+            None
+        ),
+        Some(TwapUnavailable { seconds }) =>
+            (Some(CantRebalanceReason::TwapUnavailable { seconds }), None, None),
+        Some(NothingToRebalance {}) =>
+            (Some(CantRebalanceReason::NothingToRebalance {}), None, None),
+        Some(PoolWithoutPrice(_)) =>
+            (Some(CantRebalanceReason::PoolWithoutPrice {}), None, None),
+        Some(err @ (
+            RebalanceInProgress {}
+            | NoLimitPosition {}
+            | LimitPositionInRange {}
+            | NothingToHarvest {}
+            | HarvestTwapUnavailable { .. }
+        )) => (Some(CantRebalanceReason::Other { reason: err.to_string() }), None, None)
+    };
+
+    Ok(CanRebalanceResponse { reason, price, next_allowed_time, next_allowed_price_range })
+}
+
+/// Amounts the `position_type` position would hold at `price`.
+///
+/// # Returns
+///
+/// - `None`: If the vault has no such position.
+/// - `Some(_)`: Otherwise.
+fn position_amounts_at_price(
+    position_type: PositionType,
+    price: &Decimal,
+    deps: Deps
+) -> Option<(Uint128, Uint128)> {
+    // Invariant: `VAULT_STATE` will always be present after instantiation.
+    let id = VAULT_STATE.load(deps.storage).unwrap().from_position_type(position_type)?;
+
+    // Invariant: We verified `id` is a valid position id the moment
+    //            we put it in the state, so the query wont fail.
+    let pos = PositionByIdRequest { position_id: id }
+        .query(&deps.querier)
+        .map(|x| x.position.unwrap().position.unwrap())
+        .unwrap();
+
+    // Invariant: Osmosis ticks and liquidities are always proper.
+    let liquidity = Decimal256::from_str(&pos.liquidity).unwrap();
+    let lower_tick = pos.lower_tick.try_into().unwrap();
+    let upper_tick = pos.upper_tick.try_into().unwrap();
+    Some(amounts_for_liquidity(price, lower_tick, upper_tick, liquidity))
+}
+
+/// Computes what a rebalance would do at `price` without any side effects.
+/// At a hypothetical price, the current positions are valued at it, while
+/// the vault idle funds and fees stay the same.
+pub fn simulate_rebalance(
+    price: Option<Decimal>,
+    deps: Deps,
+    env: Env
+) -> StdResult<SimulateRebalanceResponse> {
+    use PositionType::*;
+
+    // NOTE: Osmosis spot prices are at least the one of `MIN_TICK`, and any
+    //       `Decimal` is below the one of `MAX_TICK`.
+    if let Some(price) = price {
+        if Decimal256::from(price) < price_function(MIN_TICK) {
+            return Err(StdError::generic_err(format!(
+                "Cant simulate a rebalance at price {price}, its outside of the pool tick range"
+            )))
+        }
+    }
+
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let vault_state = VAULT_STATE.load(deps.storage).unwrap();
+    let rebalance_step = VAULT_PARAMETERS.load(deps.storage).unwrap().rebalance_step;
+    let current_price = vault_info.pool_id.price(&deps.querier);
+
+    let vault_balances = vault_balances(deps, &env);
+    let VaultBalancesResponse { mut bal0, mut bal1, .. } = vault_balances;
+    let positions_balances: Vec<_> = [FullRange, Base, Limit]
+        .into_iter()
+        .map(|position_type| (position_type.clone(), position_balances_with_fees(position_type, deps)))
+        .collect();
+
+    let (price, current_tick) = match price {
+        Some(price) if price != current_price => {
+            for (position_type, current) in positions_balances.iter() {
+                if let Some((amount0, amount1)) = position_amounts_at_price(position_type.clone(), &price, deps) {
+                    // Invariant: Wont underflow, as the vault balances include the ones of
+                    //            its positions. And wont overflow, as amounts are bounded
+                    //            by the pool liquidity.
+                    bal0 = bal0.checked_sub(current.bal0).unwrap().checked_add(amount0).unwrap();
+                    bal1 = bal1.checked_sub(current.bal1).unwrap().checked_add(amount1).unwrap();
+                }
+            }
+            (price, price_function_inv(&price))
+        },
+        _ => (current_price, vault_info.current_tick(&deps.querier))
+    };
+
+    let liquidity_left = vault_state.rebalance_in_progress
+        .as_ref()
+        .map(|x| x.liquidity_left.clone())
+        .unwrap_or(Weight::max());
+
+    let gradual_step = (vault_state.has_positions() && rebalance_step.0 < liquidity_left.0)
+        .then_some(rebalance_step.0);
+
+    let nothing_to_rebalance = bal0.is_zero() && bal1.is_zero();
+    let positions = if gradual_step.is_some() || nothing_to_rebalance || price.is_zero() {
+        vec![]
+    } else {
+        execute::plan_positions(bal0, bal1, price, current_tick, deps, &env)
+            .into_iter()
+            .map(|(position_type, msg)| {
+                let (amount0, amount1) = provided_amounts(&msg, deps);
+
+                // Invariant: Ticks are within `i32` after `closest_valid_tick`.
+                let liquidity = liquidity_for_amounts(
+                    &price,
+                    msg.lower_tick.try_into().unwrap(),
+                    msg.upper_tick.try_into().unwrap(),
+                    amount0,
+                    amount1
+                );
+
+                PlannedPosition {
+                    position_type,
+                    lower_tick: msg.lower_tick,
+                    upper_tick: msg.upper_tick,
+                    amount0,
+                    amount1,
+                    min_amount0: Uint128::from_str(&msg.token_min_amount0).unwrap(),
+                    min_amount1: Uint128::from_str(&msg.token_min_amount1).unwrap(),
+                    liquidity
+                }
+            })
+            .collect()
+    };
+
+    let (collected_fees0, collected_fees1) = positions_balances
+        .iter()
+        .fold((Uint128::zero(), Uint128::zero()), |(acc0, acc1), (_, x)| {
+            (acc0.saturating_add(x.bal0_fees), acc1.saturating_add(x.bal1_fees))
+        });
+
+    Ok(SimulateRebalanceResponse {
+        price,
+        gradual_step,
+        positions,
+        collected_fees0,
+        collected_fees1,
+        protocol_fees0: vault_balances.protocol_unclaimed_fees0,
+        protocol_fees1: vault_balances.protocol_unclaimed_fees1,
+        admin_fees0: vault_balances.admin_unclaimed_fees0,
+        admin_fees1: vault_balances.admin_unclaimed_fees1,
+        keeper_fees0: vault_balances.keeper_unclaimed_fees0,
+        keeper_fees1: vault_balances.keeper_unclaimed_fees1,
+    })
+}
+
+/// [`RewardIndex`] of every reward denom, accounting any reward tokens
+/// of the [`REWARD_DENOMS`] received since the last update.
+pub fn reward_indexes(deps: Deps, env: &Env) -> Vec<(String, RewardIndex)> {
+    // Invariant: Any state is present after instantiation.
+    let (denom0, denom1) = VAULT_INFO.load(deps.storage).unwrap().denoms(&deps.querier);
+    let fees = FEES_INFO.load(deps.storage).unwrap();
+    let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
+
+    let mut indexes: BTreeMap<String, RewardIndex> = REWARD_INDEXES
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap();
+
+    // NOTE: Rewards with a harvest route are swapped into the vault tokens instead.
+    // Invariant: The bank module always answers balance queries.
+    let distributed_rewards = REWARD_DENOMS
+        .keys(deps.storage, None, None, Order::Ascending)
+        .map(|x| x.unwrap())
+        .filter(|x| *x != denom0 && *x != denom1)
+        .filter(|x| !HARVEST_ROUTES.has(deps.storage, x))
+        .map(|x| deps.querier.query_balance(&env.contract.address, x).unwrap())
+        .collect::<Vec<_>>();
+
+    for balance in distributed_rewards {
+        let reserved = reserved_reward_tokens(&balance.denom, &fees);
+        let reward_index = indexes.entry(balance.denom).or_default();
+        let new_rewards = balance.amount
+            .saturating_sub(reserved)
+            .saturating_sub(reward_index.accounted);
+
+        // NOTE: Without shares, rewards stay unaccounted until the first deposit.
+        if new_rewards.is_zero() || total_supply.is_zero() {
+            continue;
+        }
+
+        reward_index.index += Decimal256::from_ratio(new_rewards, total_supply);
+        reward_index.accounted += new_rewards;
+    }
+
+    indexes.into_iter().collect()
+}
+
+/// Reward tokens held by the vault that belong to someone else.
+fn reserved_reward_tokens(denom: &str, fees: &FeesInfo) -> Uint128 {
+    // NOTE: The vault creation cost belongs to the protocol, its not a reward.
+    match &fees.protocol_vault_creation_cost {
+        Some(cost) if cost.denom == denom => fees.protocol_vault_creation_tokens_owned,
+        _ => Uint128::zero()
+    }
+}
+
+/// Reward tokens of `denom` that can be swapped into the vault tokens, i.e.,
+/// the ones not already distributed to shareholders.
+pub fn harvestable_amount(denom: &str, deps: Deps, env: &Env) -> Uint128 {
+    // Invariant: Any state is present after instantiation.
+    let fees = FEES_INFO.load(deps.storage).unwrap();
+    let accounted = REWARD_INDEXES
+        .may_load(deps.storage, denom)
+        .unwrap()
+        .unwrap_or_default()
+        .accounted;
+
+    // Invariant: The bank module always answers balance queries.
+    deps.querier
+        .query_balance(&env.contract.address, denom)
+        .unwrap()
+        .amount
+        .saturating_sub(reserved_reward_tokens(denom, &fees))
+        .saturating_sub(accounted)
+}
+
+/// Rewards of `address` up to `indexes`, for its current shares.
+pub fn settled_rewards(
+    address: &Addr,
+    indexes: &[(String, RewardIndex)],
+    deps: Deps
+) -> Vec<(String, ShareholderReward)> {
+    let shares = BALANCES.may_load(deps.storage, address).unwrap().unwrap_or_default();
+
+    indexes
+        .iter()
+        .map(|(denom, reward_index)| {
+            let mut reward = SHAREHOLDER_REWARDS
+                .may_load(deps.storage, (address, denom))
+                .unwrap()
+                .unwrap_or_default();
+
+            // Invariant: Rounding down the accrued rewards ensures all shareholders
+            //            together never get more than the accounted rewards.
+            let accrued: Uint128 = reward_index.index
+                .checked_sub(reward.index)
+                .unwrap_or_default()
+                .checked_mul(Decimal256::from_ratio(shares, 1u128))
+                .map(|x| x.to_uint_floor())
+                .unwrap_or(Uint256::MAX)
+                .try_into()
+                .unwrap_or(Uint128::MAX);
+
+            reward.pending = reward.pending.saturating_add(accrued);
+            reward.index = reward_index.index;
+            (denom.clone(), reward)
+        })
+        .collect()
+}
+
+pub fn pending_rewards(address: String, deps: Deps, env: Env) -> StdResult<PendingRewardsResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let indexes = reward_indexes(deps, &env);
+
+    let rewards = settled_rewards(&address, &indexes, deps)
+        .into_iter()
+        .filter(|(_, x)| !x.pending.is_zero())
+        .map(|(denom, x)| coin(x.pending.into(), denom))
+        .collect();
+
+    Ok(PendingRewardsResponse { rewards })
+}
+
+pub fn management_fee(deps: Deps, env: Env) -> ManagementFeeResponse {
+    // Invariant: Any state is present after instantiation.
+    let management_fee = FEES_INFO.load(deps.storage).unwrap().management_fee;
+    let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
+
+    let accrued_shares = management_fee
+        .clone()
+        .map(|mut x| x.accrue(env.block.time, total_supply))
+        .unwrap_or_default();
+
+    ManagementFeeResponse { management_fee, accrued_shares }
+}
+
+pub fn simulate_withdraw(shares: Uint128, deps: Deps, env: &Env) -> SimulateWithdrawResponse {
+    let VaultBalancesResponse { bal0, bal1, .. } = vault_balances(deps, env);
+    // Invariant: `TOKEN_INFO` always present after instantiation.
+    let total_supply = TOKEN_INFO.load(deps.storage).unwrap().total_supply;
+
+    // NOTE: Withdrawing more shares than the supply would fail, so get nothing.
+    let Some(shares_proportion) = Decimal::checked_from_ratio(shares, total_supply)
+        .ok()
+        .and_then(|x| Weight::try_from(x).ok()) else {
+        return SimulateWithdrawResponse::default()
+    };
+
+    let amount0 = shares_proportion.mul_raw(bal0).atomics();
+    let amount1 = shares_proportion.mul_raw(bal1).atomics();
+    let (exit_fee0, exit_fee1) = exit_fees(amount0, amount1, deps);
+
+    // Invariant: Wont underflow, as the fee is a valid weight.
+    SimulateWithdrawResponse {
+        amount0: amount0.checked_sub(exit_fee0).unwrap(),
+        amount1: amount1.checked_sub(exit_fee1).unwrap(),
+        exit_fee0,
+        exit_fee1
+    }
+}
+
+pub fn revenue_stats(deps: Deps) -> RevenueStatsResponse {
+    // Invariant: Wont panic as all types are proper.
+    let stats = REVENUE_STATS
+        .range(deps.storage, None, None, Order::Ascending)
+        .collect::<StdResult<_>>()
+        .unwrap();
+
+    RevenueStatsResponse { stats }
+}
+
+/// Shares of `address` still locked by the vault withdrawal cooldown, if any.
+pub fn shares_lock(address: &str, deps: Deps, env: &Env) -> Option<SharesLock> {
+    // Invariant: Any state is present after instantiation.
+    VAULT_INFO.load(deps.storage).unwrap().withdrawal_cooldown?;
+    // Invariant: Wont panic as all types are proper.
+    SHARES_LOCKS
+        .may_load(deps.storage, &Addr::unchecked(address))
+        .unwrap()
+        .filter(|x| x.unlocks_at > env.block.time)
+}
+
+/// When `shares` of `address` can be moved, if some of them are still locked
+/// by the vault withdrawal cooldown.
+pub fn shares_locked_until(address: &str, shares: Uint128, deps: Deps, env: &Env) -> Option<Timestamp> {
+    let lock = shares_lock(address, deps, env)?;
+    // Invariant: Wont panic as all types are proper.
+    let held_shares = BALANCES
+        .may_load(deps.storage, &Addr::unchecked(address))
+        .unwrap()
+        .unwrap_or_default();
+    (shares > held_shares.saturating_sub(lock.shares)).then_some(lock.unlocks_at)
+}
+
+pub fn shares_unlock(address: String, deps: Deps, env: Env) -> SharesUnlockResponse {
+    let lock = shares_lock(&address, deps, &env);
+    SharesUnlockResponse {
+        unlocks_at: lock.as_ref().map(|x| x.unlocks_at),
+        locked_shares: lock.map(|x| x.shares).unwrap_or_default()
+    }
+}
+
+pub fn is_allowed(address: String, deps: Deps) -> IsAllowedResponse {
+    // Invariant: Any state is present after instantiation.
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    IsAllowedResponse { allowed: vault_info.is_allowed(&address, deps) }
+}
+
+pub fn admin_fee(deps: Deps, env: Env) -> AdminFeeResponse {
+    // Invariant: Any state is present after instantiation.
+    let fees = FEES_INFO.load(deps.storage).unwrap().apply_pending_admin_fee(env.block.time);
+    AdminFeeResponse { admin_fee: fees.admin_fee, pending_admin_fee: fees.pending_admin_fee }
+}
+
+pub fn admin_fees_owed(deps: Deps) -> AdminFeesOwedResponse {
+    // Invariant: Any state is present after instantiation.
+    let fees = FEES_INFO.load(deps.storage).unwrap();
+    let vault_info = VAULT_INFO.load(deps.storage).unwrap();
+    let (denom0, denom1) = vault_info.denoms(&deps.querier);
+
+    // NOTE: Without an admin there are no admin fees to owe.
+    let Some(admin) = vault_info.admin else {
+        return AdminFeesOwedResponse { owed: vec![] }
+    };
+
+    let owed = fees
+        .admin_fee_payouts(&admin, fees.admin_tokens0_owned, fees.admin_tokens1_owned)
+        .into_iter()
+        .map(|(address, amount0, amount1)| (address, vec![
+            coin(amount0.into(), denom0.clone()),
+            coin(amount1.into(), denom1.clone())
+        ]))
+        .collect();
+
+    AdminFeesOwedResponse { owed }
+}
+
+/// Part of the withdrawn `amount0` and `amount1` taken as exit fee.
+pub fn exit_fees(amount0: Uint128, amount1: Uint128, deps: Deps) -> (Uint128, Uint128) {
+    // Invariant: Any state is present after instantiation.
+    let exit_fee = FEES_INFO.load(deps.storage).unwrap().exit_fee();
+    (exit_fee.mul_raw(amount0).atomics(), exit_fee.mul_raw(amount1).atomics())
+}
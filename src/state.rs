@@ -1,23 +1,35 @@
 use crate::constants::{
-    DEFAULT_PROTOCOL_FEE, DEFAULT_VAULT_CREATION_COST, MAX_PROTOCOL_FEE, MAX_TICK,
-    MAX_VAULT_CREATION_COST, TWAP_SECONDS, VAULT_CREATION_COST_DENOM,
+    ADMIN_FEE_INCREASE_NOTICE_SECONDS, DEFAULT_PROTOCOL_FEE, DEFAULT_TWAP_MAX_DEVIATION,
+    FALLBACK_PRICE_FACTOR_BEFORE_REBALANCE, FALLBACK_SECONDS_BEFORE_REBALANCE,
+    MAX_ADMIN_FEE_INCREASE, MAX_ENTRY_EXIT_FEE, MAX_PERFORMANCE_FEE, MAX_PROTOCOL_FEE, MAX_TICK,
+    MAX_TWAP_SECONDS, MAX_VAULT_CREATION_COSTS, MAX_WITHDRAWAL_COOLDOWN_SECONDS,
+    REBALANCE_LIMITS_TIMELOCK_SECONDS, SECONDS_IN_A_YEAR, TWAP_SECONDS,
 };
 use crate::do_some;
-use crate::error::{InstantiationError, ProtocolOperationError};
+use crate::error::{AdminOperationError, InstantiationError, ProtocolOperationError};
 use crate::{
     constants::MIN_TICK,
-    msg::{VaultInfoInstantiateMsg, VaultParametersInstantiateMsg, VaultRebalancerInstantiateMsg},
+    msg::{
+        AccessListInstantiateMsg, AccessRegistryQueryMsg, CircuitBreakerInstantiateMsg,
+        DepositCapsInstantiateMsg, EntryExitFeesInstantiateMsg, HarvestHop,
+        HarvestRouteInstantiateMsg, IsAllowedResponse, KeeperRewardInstantiateMsg,
+        PerformanceFeeInstantiateMsg, RebalanceLimitsInstantiateMsg, RebalancePolicyInstantiateMsg,
+        TwapConfigInstantiateMsg, VaultInfoInstantiateMsg, VaultParametersInstantiateMsg,
+        VaultRebalancerInstantiateMsg,
+    },
 };
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Addr, Decimal, Deps, Env, MessageInfo, QuerierWrapper, Timestamp, Uint128};
-use cw_storage_plus::Item;
+use cosmwasm_std::{
+    Addr, Coin, Decimal, Decimal256, Deps, DepsMut, Env, MessageInfo, QuerierWrapper, Timestamp,
+    Uint128, Uint256,
+};
+use cw_storage_plus::{Item, Map};
 use osmosis_std::types::osmosis::twap::v1beta1::TwapQuerier;
 use osmosis_std::types::osmosis::{
     concentratedliquidity::v1beta1::Pool, poolmanager::v1beta1::PoolmanagerQuerier,
 };
 use readonly;
-use std::i32;
-use std::{cmp::min_by_key, str::FromStr};
+use std::{cmp::{min, min_by_key}, str::FromStr};
 
 #[cw_serde]
 #[readonly::make]
@@ -122,18 +134,19 @@ impl PoolId {
         Decimal::from_str(&p).unwrap()
     }
 
-    pub fn twap(&self, querier: &QuerierWrapper, env: &Env) -> Option<Decimal> {
+    pub fn twap(&self, querier: &QuerierWrapper, env: &Env, seconds: u64) -> Option<Decimal> {
         let start_time = env.block.time;
         // Invariant: Wont overflow as `env.block.time` is reasonable.
         let osmosis_start_time = Some(osmosis_std::shim::Timestamp {
-            seconds: start_time.seconds().saturating_sub(TWAP_SECONDS).try_into().unwrap(),
+            seconds: start_time.seconds().saturating_sub(seconds).try_into().unwrap(),
             nanos: 0
         });
         let pool = self.to_pool(querier);
 
-        // Invariant: Will only return `None` if `pool` was recently created, as
-        //            we already ensured that `self` is valid during instantiation
-        //            and that the start time is in the near past.
+        // NOTE: Will only return `None` if `pool` was created less than `seconds`
+        //       ago, or if `seconds` goes further back than the chain keeps twap
+        //       records, as we already ensured that `self` is valid during
+        //       instantiation.
         let p = TwapQuerier::new(querier)
             .geometric_twap_to_now(self.0, pool.token0, pool.token1, osmosis_start_time)
             .ok()?
@@ -185,21 +198,54 @@ impl Default for ProtocolFee {
 
 #[cw_serde]
 #[readonly::make]
-pub struct VaultCreationCost(pub Uint128);
+pub struct VaultCreationCost {
+    pub denom: String,
+    pub amount: Uint128
+}
+
 impl VaultCreationCost {
-    pub fn max() -> Uint128 {
-        MAX_VAULT_CREATION_COST
+    /// Max cost that can be paid in `denom`, if any, see [`MAX_VAULT_CREATION_COSTS`].
+    pub fn max(denom: &str) -> Option<Uint128> {
+        MAX_VAULT_CREATION_COSTS.iter().find(|(x, _)| *x == denom).map(|(_, max)| *max)
     }
 
-    pub fn new(value: Uint128) -> Option<Self> {
-        (value <= Self::max()).then_some(Self(value))
+    pub fn new(cost: Coin) -> Result<Self, InstantiationError> {
+        use InstantiationError::*;
+        let max = Self::max(&cost.denom).ok_or(UnknownVaultCreationCostDenom(cost.denom.clone()))?;
+        if cost.amount > max {
+            return Err(InvalidVaultCreationCost { max, got: cost.amount })
+        }
+
+        Ok(Self { denom: cost.denom, amount: cost.amount })
     }
-}
 
-impl Default for VaultCreationCost {
-    fn default() -> Self {
-        // Invariant: Wont panic, as the const is clearly below `Self::max()`.
-        Self::new(DEFAULT_VAULT_CREATION_COST).unwrap()
+    /// Which one of the `accepted` costs was paid at instantiation, if any was due.
+    pub fn paid(
+        accepted: &[VaultCreationCost],
+        info: &MessageInfo
+    ) -> Result<Option<VaultCreationCost>, InstantiationError> {
+        // NOTE: Vaults are free to create if no creation costs are accepted.
+        if accepted.is_empty() {
+            return Ok(None)
+        }
+
+        let paid = cw_utils::one_coin(info).ok();
+        let vault_creation_cost = paid.and_then(|paid| {
+            accepted.iter().find(|x| paid.denom == x.denom && paid.amount == x.amount)
+        });
+
+        if let Some(vault_creation_cost) = vault_creation_cost {
+            Ok(Some(vault_creation_cost.clone()))
+        } else {
+            Err(InstantiationError::VaultCreationCostNotPaid {
+                accepted: accepted
+                    .iter()
+                    .map(|x| format!("{}{}", x.amount, x.denom))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                got: info.funds.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(", ")
+            })
+        }
     }
 }
 
@@ -218,7 +264,11 @@ pub struct VaultParameters {
     pub limit_factor: PriceFactor,
     /// Exact liquidity weight to put into the full range order. 
     /// Zero if we dont want a full range position.
-    pub full_range_weight: Weight
+    pub full_range_weight: Weight,
+    /// Max proportion of the vault liquidity moved per rebalance call. If its
+    /// below one, rebalances will first take several calls to move liquidity
+    /// out of the current positions, see [`GradualRebalance`].
+    pub rebalance_step: Weight
 }
 
 impl VaultParameters {
@@ -233,6 +283,17 @@ impl VaultParameters {
         let full_range_weight = Weight::new(&params.full_range_weight)
             .ok_or(InvalidWeight(params.full_range_weight))?;
 
+        let rebalance_step = match params.rebalance_step {
+            Some(step) => Weight::new(&step).ok_or(InvalidWeight(step))?,
+            None => Weight::max()
+        };
+
+        if rebalance_step.is_zero() {
+            return Err(ContradictoryConfig {
+                reason: "A zero rebalance step would never move any liquidity".into()
+            })
+        }
+
         // NOTE: We dont support vaults with idle capital nor less than 3 positions for now.
         //       Integrating both options is trivial, but we keep it simple for the v1.
         match (
@@ -261,7 +322,51 @@ impl VaultParameters {
             })
         }?;
 
-        Ok(VaultParameters { base_factor, limit_factor, full_range_weight })
+        Ok(VaultParameters { base_factor, limit_factor, full_range_weight, rebalance_step })
+    }
+}
+
+#[cw_serde]
+pub struct ProtocolInfo {
+    /// Account allowed to do protocol operations.
+    pub protocol: Addr,
+    pub proposed_new_protocol: Option<Addr>,
+    /// Account the protocol fees are paid to.
+    pub fee_recipient: Addr
+}
+
+impl ProtocolInfo {
+    /// The protocol account starts as its own fee recipient.
+    pub fn new(protocol: &str, deps: Deps) -> Result<Self, InstantiationError> {
+        let protocol = deps.api
+            .addr_validate(protocol)
+            .map_err(|_| InstantiationError::InvalidProtocolAddress(protocol.into()))?;
+
+        Ok(ProtocolInfo {
+            protocol: protocol.clone(),
+            proposed_new_protocol: None,
+            fee_recipient: protocol
+        })
+    }
+
+    pub fn propose_new_protocol(self, new_protocol: String, deps: Deps) -> Option<Self> {
+        let proposed_new_protocol = Some(deps.api.addr_validate(&new_protocol).ok()?);
+        Some(Self { proposed_new_protocol, ..self })
+    }
+
+    pub fn unset_proposed_new_protocol(self) -> Self {
+        Self { proposed_new_protocol: None, ..self }
+    }
+
+    /// Doesnt change the fee recipient, the new protocol has to do it itself.
+    pub fn confirm_new_protocol(self) -> Option<Self> {
+        let protocol = self.proposed_new_protocol?;
+        Some(Self { protocol, proposed_new_protocol: None, ..self })
+    }
+
+    pub fn change_fee_recipient(self, fee_recipient: String, deps: Deps) -> Option<Self> {
+        let fee_recipient = deps.api.addr_validate(&fee_recipient).ok()?;
+        Some(Self { fee_recipient, ..self })
     }
 }
 
@@ -272,7 +377,26 @@ pub struct VaultInfo {
     pub pool_id: PoolId,
     pub admin: Option<Addr>,
     pub proposed_new_admin: Option<Addr>,
-    pub rebalancer: VaultRebalancer
+    pub rebalancer: VaultRebalancer,
+    /// If not present, rebalances are checked with [`TwapConfig::default`].
+    /// Use [`VaultInfo::twap_config`] to account for `pending_twap_config`.
+    pub twap_config: Option<TwapConfig>,
+    pub pending_twap_config: Option<PendingTwapConfig>,
+    /// If the vault rebalancer doesnt rebalance for this long, anyone can
+    /// rebalance as with [`VaultRebalancer::fallback`].
+    pub inactivity_timeout: Option<Timestamp>,
+    /// If not present, the vault never enters the protected state.
+    pub circuit_breaker: Option<CircuitBreaker>,
+    /// If not present, the vault accepts deposits of any size.
+    pub deposit_caps: Option<DepositCaps>,
+    /// If not present, anyone can hold vault shares. Set with
+    /// [`VaultInfo::change_access_list`], as its addresses live in [`ACCESS_LIST`].
+    pub access_list: Option<AccessList>,
+    /// If not present, deposited shares can be withdrawn or transferred right away.
+    pub withdrawal_cooldown: Option<Timestamp>,
+    /// Use [`VaultInfo::rebalance_limits`] to account for `pending_rebalance_limits`.
+    pub rebalance_limits: RebalanceLimits,
+    pub pending_rebalance_limits: Option<PendingRebalanceLimits>
 }
 
 impl VaultInfo {
@@ -291,13 +415,55 @@ impl VaultInfo {
 
         rebalancer.rebalancer_consistent_with_admin(&admin)?;
 
+        let twap_config = info.twap_config.map(TwapConfig::new).transpose()?;
+        let inactivity_timeout = Self::validate_inactivity_timeout(info.inactivity_timeout)?;
+        let circuit_breaker = info.circuit_breaker.map(CircuitBreaker::new).transpose()?;
+        let deposit_caps = info.deposit_caps.map(DepositCaps::new).transpose()?;
+        let withdrawal_cooldown = Self::validate_withdrawal_cooldown(info.withdrawal_cooldown)?;
+        let rebalance_limits = info.rebalance_limits
+            .map(RebalanceLimits::new)
+            .transpose()?
+            .unwrap_or_default();
+
         Ok(VaultInfo {
             pool_id,
             rebalancer,
             admin,
-            proposed_new_admin: None
+            proposed_new_admin: None,
+            twap_config,
+            pending_twap_config: None,
+            inactivity_timeout,
+            circuit_breaker,
+            deposit_caps,
+            access_list: None,
+            withdrawal_cooldown,
+            rebalance_limits,
+            pending_rebalance_limits: None
         })
     }
+
+    fn validate_inactivity_timeout(seconds: Option<u32>) -> Result<Option<Timestamp>, InstantiationError> {
+        match seconds {
+            Some(0) => Err(InstantiationError::ContradictoryConfig {
+                reason: "A zero inactivity timeout would always let anyone rebalance".into()
+            }),
+            seconds => Ok(seconds.map(|x| Timestamp::from_seconds(x.into())))
+        }
+    }
+
+    fn validate_withdrawal_cooldown(seconds: Option<u32>) -> Result<Option<Timestamp>, InstantiationError> {
+        match seconds {
+            Some(0) => Err(InstantiationError::ContradictoryConfig {
+                reason: "A zero withdrawal cooldown doesnt lock anything, leave it empty instead".into()
+            }),
+            Some(x) if u64::from(x) > MAX_WITHDRAWAL_COOLDOWN_SECONDS =>
+                Err(InstantiationError::InvalidWithdrawalCooldown {
+                    max: MAX_WITHDRAWAL_COOLDOWN_SECONDS,
+                    got: x.into()
+                }),
+            seconds => Ok(seconds.map(|x| Timestamp::from_seconds(x.into())))
+        }
+    }
     
     pub fn propose_new_admin(self, new_admin: String, deps: Deps) -> Option<Self> {
         let proposed_new_admin = Some(deps.api.addr_validate(&new_admin).ok()?);
@@ -327,6 +493,167 @@ impl VaultInfo {
         Ok(Self { rebalancer, ..self })
     }
 
+    pub fn change_inactivity_timeout(
+        self,
+        new_inactivity_timeout: Option<u32>
+    ) -> Result<Self, InstantiationError> {
+        let inactivity_timeout = Self::validate_inactivity_timeout(new_inactivity_timeout)?;
+        Ok(Self { inactivity_timeout, ..self })
+    }
+
+    /// Whether anyone can rebalance as with [`VaultRebalancer::fallback`], because
+    /// the vault rebalancer didnt rebalance for [`VaultInfo::inactivity_timeout`].
+    /// Once active, it stays so until the vault rebalancer rebalances again.
+    pub fn fallback_rebalancer_active(&self, vault_state: &VaultState, now: Timestamp) -> bool {
+        if matches!(self.rebalancer, VaultRebalancer::Anyone { .. }) {
+            return false
+        }
+
+        match (&self.inactivity_timeout, &vault_state.last_price_and_timestamp) {
+            (Some(timeout), Some(snapshot)) => {
+                vault_state.last_rebalance_by_fallback
+                    || snapshot.last_timestamp.plus_seconds(timeout.seconds()) <= now
+            },
+            _ => false
+        }
+    }
+
+    pub fn change_circuit_breaker(
+        self,
+        new_circuit_breaker: Option<CircuitBreakerInstantiateMsg>
+    ) -> Result<Self, InstantiationError> {
+        let circuit_breaker = new_circuit_breaker.map(CircuitBreaker::new).transpose()?;
+        Ok(Self { circuit_breaker, ..self })
+    }
+
+    pub fn change_deposit_caps(
+        self,
+        new_deposit_caps: Option<DepositCapsInstantiateMsg>
+    ) -> Result<Self, InstantiationError> {
+        let deposit_caps = new_deposit_caps.map(DepositCaps::new).transpose()?;
+        Ok(Self { deposit_caps, ..self })
+    }
+
+    pub fn change_withdrawal_cooldown(
+        self,
+        new_withdrawal_cooldown: Option<u32>
+    ) -> Result<Self, InstantiationError> {
+        let withdrawal_cooldown = Self::validate_withdrawal_cooldown(new_withdrawal_cooldown)?;
+        Ok(Self { withdrawal_cooldown, ..self })
+    }
+
+    /// Shares of a holder locked by `lock` after `new_shares` are deposited to it
+    /// at `now`. Only the still locked and the new shares are locked, until the share
+    /// weighted average of both unlock times, so dust deposits to an address cant
+    /// extend its lockup, and its already unlocked shares stay unlocked.
+    pub fn shares_lock_after_deposit(
+        &self,
+        lock: Option<SharesLock>,
+        new_shares: Uint128,
+        now: Timestamp
+    ) -> Option<SharesLock> {
+        let cooldown = self.withdrawal_cooldown?.seconds();
+        let (locked_shares, locked_seconds) = lock
+            .filter(|x| x.unlocks_at > now)
+            .map_or((Uint128::zero(), 0), |x| (x.shares, x.unlocks_at.seconds() - now.seconds()));
+
+        // Invariant: Wont overflow, as products of 128 and 64 bits fit in 256 bits.
+        let weighted_seconds = locked_shares.full_mul(locked_seconds) + new_shares.full_mul(cooldown);
+        let total_shares = Uint256::from(locked_shares) + Uint256::from(new_shares);
+
+        // Invariant: Wont panic, as `new_shares` is never zero, a weighted average
+        //            of seconds always fits in a u64, and locked shares are always
+        //            part of the total supply.
+        let seconds = Uint128::try_from(weighted_seconds / total_shares).unwrap().u128();
+        Some(SharesLock {
+            shares: Uint128::try_from(total_shares).unwrap(),
+            unlocks_at: now.plus_seconds(u64::try_from(seconds).unwrap())
+        })
+    }
+
+    /// Replaces the access list, along with its addresses in [`ACCESS_LIST`].
+    pub fn change_access_list(
+        self,
+        new_access_list: Option<AccessListInstantiateMsg>,
+        deps: DepsMut
+    ) -> Result<Self, InstantiationError> {
+        let access_list = new_access_list.map(|x| AccessList::new(x, deps)).transpose()?;
+        Ok(Self { access_list, ..self })
+    }
+
+    /// Whether `address` can hold vault shares.
+    pub fn is_allowed(&self, address: &str, deps: Deps) -> bool {
+        self.access_list.as_ref().is_none_or(|x| x.is_allowed(address, deps))
+    }
+
+    /// Rebalance limits in effect at `now`.
+    pub fn rebalance_limits(&self, now: Timestamp) -> &RebalanceLimits {
+        match self.pending_rebalance_limits {
+            Some(ref pending) if pending.effective_at <= now => &pending.limits,
+            _ => &self.rebalance_limits
+        }
+    }
+
+    /// Stricter limits apply right away, while looser ones are
+    /// left pending for [`REBALANCE_LIMITS_TIMELOCK_SECONDS`].
+    pub fn change_rebalance_limits(
+        self,
+        new_rebalance_limits: RebalanceLimitsInstantiateMsg,
+        now: Timestamp
+    ) -> Result<Self, InstantiationError> {
+        let limits = RebalanceLimits::new(new_rebalance_limits)?;
+        let current = self.rebalance_limits(now).clone();
+
+        Ok(if limits.is_at_least_as_strict_as(&current) {
+            Self { rebalance_limits: limits, pending_rebalance_limits: None, ..self }
+        } else {
+            Self {
+                rebalance_limits: current,
+                pending_rebalance_limits: Some(PendingRebalanceLimits {
+                    limits,
+                    effective_at: now.plus_seconds(REBALANCE_LIMITS_TIMELOCK_SECONDS)
+                }),
+                ..self
+            }
+        })
+    }
+
+    /// TWAP config in effect at `now`.
+    pub fn twap_config(&self, now: Timestamp) -> TwapConfig {
+        self.twap_config_set(now).clone().unwrap_or_default()
+    }
+
+    fn twap_config_set(&self, now: Timestamp) -> &Option<TwapConfig> {
+        match self.pending_twap_config {
+            Some(ref pending) if pending.effective_at <= now => &pending.config,
+            _ => &self.twap_config
+        }
+    }
+
+    /// Stricter TWAP configs apply right away, while looser ones are
+    /// left pending for [`REBALANCE_LIMITS_TIMELOCK_SECONDS`].
+    pub fn change_twap_config(
+        self,
+        new_twap_config: Option<TwapConfigInstantiateMsg>,
+        now: Timestamp
+    ) -> Result<Self, InstantiationError> {
+        let config = new_twap_config.map(TwapConfig::new).transpose()?;
+        let current = self.twap_config_set(now).clone();
+
+        Ok(if config.clone().unwrap_or_default().is_at_least_as_strict_as(&self.twap_config(now)) {
+            Self { twap_config: config, pending_twap_config: None, ..self }
+        } else {
+            Self {
+                twap_config: current,
+                pending_twap_config: Some(PendingTwapConfig {
+                    config,
+                    effective_at: now.plus_seconds(REBALANCE_LIMITS_TIMELOCK_SECONDS)
+                }),
+                ..self
+            }
+        })
+    }
+
     pub fn demon0(&self, querier: &QuerierWrapper) -> String {
         self.pool_id.to_pool(querier).token0
     }
@@ -411,6 +738,369 @@ impl VaultInfo {
     }
 }
 
+/// What to do when the pool TWAP cant be queried, see [`PoolId::twap`].
+#[cw_serde]
+pub enum TwapFallback {
+    /// Dont rebalance until the TWAP is available.
+    Reject {},
+    /// Rebalance without checking the price against the TWAP.
+    SkipCheck {},
+    /// Only `Admin` and `Delegate` rebalancers can rebalance without
+    /// checking the price against the TWAP.
+    PrivilegedOnly {},
+}
+
+impl TwapFallback {
+    fn strictness(&self) -> u8 {
+        match self {
+            TwapFallback::SkipCheck {} => 0,
+            TwapFallback::PrivilegedOnly {} => 1,
+            TwapFallback::Reject {} => 2
+        }
+    }
+}
+
+/// See [`CircuitBreakerInstantiateMsg`].
+#[cw_serde]
+pub struct CircuitBreaker {
+    pub twap_seconds: u64,
+    pub trip_deviation: Weight,
+    pub settle_deviation: Weight
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerInstantiateMsg) -> Result<Self, InstantiationError> {
+        use InstantiationError::*;
+
+        let twap_seconds: u64 = config.twap_seconds.into();
+        if twap_seconds == 0 || twap_seconds > MAX_TWAP_SECONDS {
+            return Err(ContradictoryConfig {
+                reason: format!("The TWAP window must be in [1, {MAX_TWAP_SECONDS}] seconds")
+            })
+        }
+
+        let trip_deviation = Weight::new(&config.trip_deviation)
+            .ok_or(InvalidWeight(config.trip_deviation))?;
+        let settle_deviation = Weight::new(&config.settle_deviation)
+            .ok_or(InvalidWeight(config.settle_deviation))?;
+
+        if settle_deviation.0 > trip_deviation.0 {
+            return Err(ContradictoryConfig {
+                reason: "The circuit breaker cant settle above its trip deviation".into()
+            })
+        }
+
+        Ok(CircuitBreaker { twap_seconds, trip_deviation, settle_deviation })
+    }
+}
+
+/// See [`DepositCapsInstantiateMsg`].
+#[cw_serde]
+pub struct DepositCaps {
+    pub max_total_shares: Option<Uint128>,
+    pub max_total_value: Option<Uint128>,
+    pub max_shares_per_address: Option<Uint128>,
+    pub min_deposit_value: Option<Uint128>
+}
+
+impl DepositCaps {
+    pub fn new(caps: DepositCapsInstantiateMsg) -> Result<Self, InstantiationError> {
+        if let (Some(min), Some(max)) = (caps.min_deposit_value, caps.max_total_value) {
+            if min > max {
+                return Err(InstantiationError::ContradictoryConfig {
+                    reason: "The min deposit cant be above the max total value".into()
+                })
+            }
+        }
+
+        Ok(DepositCaps {
+            max_total_shares: caps.max_total_shares,
+            max_total_value: caps.max_total_value,
+            max_shares_per_address: caps.max_shares_per_address,
+            min_deposit_value: caps.min_deposit_value
+        })
+    }
+
+    /// Part of a deposit of `shares` worth `value` that can be accepted by a vault
+    /// with `total_supply` shares worth `total_value`, if `holder_shares` are already
+    /// owned by the depositor. `minted` are all the shares the deposit mints, which
+    /// on a first deposit also include the min liquidity.
+    pub fn accepted_part(
+        &self,
+        shares: Uint128,
+        minted: Uint128,
+        value: Uint128,
+        total_supply: Uint128,
+        total_value: Uint128,
+        holder_shares: Uint128
+    ) -> Weight {
+        let part = |max: Option<Uint128>, current: Uint128, wanted: Uint128| max
+            .map(|max| Decimal::checked_from_ratio(max.saturating_sub(current), wanted))
+            .and_then(|x| x.ok())
+            .map_or(Weight::MAX, |x| min(x, Weight::MAX));
+
+        let part = [
+            part(self.max_total_shares, total_supply, minted),
+            part(self.max_shares_per_address, holder_shares, shares),
+            part(self.max_total_value, total_value, value)
+        ].into_iter().min().unwrap_or(Weight::MAX);
+
+        // Invariant: Wont panic, as its capped by `Weight::MAX`.
+        Weight::try_from(part).unwrap()
+    }
+}
+
+/// Deposited shares of a holder locked by the vault withdrawal cooldown.
+/// See [`VaultInfo::shares_lock_after_deposit`].
+#[cw_serde]
+pub struct SharesLock {
+    pub shares: Uint128,
+    pub unlocks_at: Timestamp
+}
+
+/// See [`AccessListInstantiateMsg`]. The allowlist and blocklist addresses
+/// are kept in [`ACCESS_LIST`].
+#[cw_serde]
+pub enum AccessList {
+    Allowlist,
+    Blocklist,
+    Registry(Addr)
+}
+
+impl AccessList {
+    /// Validates `list`, replacing the addresses in [`ACCESS_LIST`] with its own.
+    pub fn new(list: AccessListInstantiateMsg, deps: DepsMut) -> Result<Self, InstantiationError> {
+        use InstantiationError::*;
+        let validate = |address: String| deps.api
+            .addr_validate(&address)
+            .map_err(|_| InvalidAccessListAddress(address));
+
+        let (access_list, addresses) = match list {
+            AccessListInstantiateMsg::Allowlist { addresses } => (AccessList::Allowlist, addresses),
+            AccessListInstantiateMsg::Blocklist { addresses } => (AccessList::Blocklist, addresses),
+            AccessListInstantiateMsg::Registry { registry } =>
+                (AccessList::Registry(validate(registry)?), vec![]),
+        };
+        let addresses = addresses
+            .into_iter()
+            .map(validate)
+            .collect::<Result<Vec<_>, InstantiationError>>()?;
+
+        // Invariant: Wont panic as all types are proper.
+        ACCESS_LIST.clear(deps.storage);
+        for address in &addresses {
+            ACCESS_LIST.save(deps.storage, address, &()).unwrap();
+        }
+
+        Ok(access_list)
+    }
+
+    pub fn is_allowed(&self, address: &str, deps: Deps) -> bool {
+        let listed = || ACCESS_LIST.has(deps.storage, &Addr::unchecked(address));
+        match self {
+            AccessList::Allowlist => listed(),
+            AccessList::Blocklist => !listed(),
+            AccessList::Registry(registry) => deps.querier
+                .query_wasm_smart::<IsAllowedResponse>(
+                    registry,
+                    &AccessRegistryQueryMsg::IsAllowed { address: address.into() }
+                )
+                .is_ok_and(|x| x.allowed)
+        }
+    }
+}
+
+/// See [`HarvestRouteInstantiateMsg`].
+#[cw_serde]
+pub struct HarvestRoute {
+    pub hops: Vec<HarvestHop>,
+    pub twap_seconds: u64,
+    pub max_slippage: Weight
+}
+
+impl HarvestRoute {
+    pub fn new(
+        denom: &str,
+        route: HarvestRouteInstantiateMsg,
+        vault_info: &VaultInfo,
+        querier: &QuerierWrapper
+    ) -> Result<Self, InstantiationError> {
+        use InstantiationError::*;
+        let (denom0, denom1) = vault_info.denoms(querier);
+
+        if denom == denom0 || denom == denom1 {
+            return Err(ContradictoryConfig { reason: "Vault tokens cant be harvested".into() })
+        }
+
+        let token_out = route.hops.last().map(|x| x.token_out_denom.clone());
+        if token_out != Some(denom0) && token_out != Some(denom1) {
+            return Err(ContradictoryConfig {
+                reason: "A harvest route must end in one of the vault tokens".into()
+            })
+        }
+
+        // NOTE: A spot price only exists if the hop pool holds both denoms.
+        let mut denom_in = denom.to_string();
+        for hop in &route.hops {
+            let connected = denom_in != hop.token_out_denom && PoolmanagerQuerier::new(querier)
+                .spot_price(hop.pool_id, denom_in.clone(), hop.token_out_denom.clone())
+                .is_ok();
+
+            if !connected {
+                return Err(ContradictoryConfig {
+                    reason: format!(
+                        "Pool {} cant swap {denom_in} into {}", hop.pool_id, hop.token_out_denom
+                    )
+                })
+            }
+            denom_in = hop.token_out_denom.clone();
+        }
+
+        let twap_seconds: u64 = route.twap_seconds.into();
+        if twap_seconds == 0 || twap_seconds > MAX_TWAP_SECONDS {
+            return Err(ContradictoryConfig {
+                reason: format!("The TWAP window must be in [1, {MAX_TWAP_SECONDS}] seconds")
+            })
+        }
+
+        let max_slippage = Weight::new(&route.max_slippage)
+            .ok_or(InvalidWeight(route.max_slippage))?;
+
+        Ok(HarvestRoute { hops: route.hops, twap_seconds, max_slippage })
+    }
+
+    pub fn token_out_denom(&self) -> &str {
+        // Invariant: Routes are never empty, as they end in a vault token.
+        &self.hops.last().unwrap().token_out_denom
+    }
+
+    /// Min amount out of swapping `amount_in` of `denom_in` along the route.
+    ///
+    /// # Returns
+    ///
+    /// - `None`: If any hop has no TWAP for the route window.
+    /// - `Some(_)`: Otherwise.
+    pub fn min_amount_out(
+        &self,
+        denom_in: &str,
+        amount_in: Uint128,
+        querier: &QuerierWrapper,
+        env: &Env
+    ) -> Option<Uint128> {
+        let start_time = Some(osmosis_std::shim::Timestamp {
+            seconds: env.block.time.seconds().saturating_sub(self.twap_seconds).try_into().ok()?,
+            nanos: 0
+        });
+
+        let mut denom_in = denom_in.to_string();
+        let mut amount = Decimal::raw(amount_in.u128());
+        for hop in &self.hops {
+            // NOTE: The TWAP is the price of `denom_in` in terms of `token_out_denom`.
+            let twap = TwapQuerier::new(querier)
+                .geometric_twap_to_now(
+                    hop.pool_id, denom_in, hop.token_out_denom.clone(), start_time.clone()
+                )
+                .ok()?
+                .geometric_twap;
+
+            amount = amount.checked_mul(Decimal::from_str(&twap).ok()?).ok()?;
+            denom_in = hop.token_out_denom.clone();
+        }
+
+        // Invariant: Wont underflow, as `max_slippage` is a valid weight.
+        let min_amount = amount.checked_sub(self.max_slippage.mul_dec(&amount)).unwrap();
+        Some(min_amount.atomics())
+    }
+}
+
+/// See [`RebalanceLimitsInstantiateMsg`].
+#[cw_serde]
+#[derive(Default)]
+pub struct RebalanceLimits {
+    pub min_seconds_between_rebalances: u64,
+    pub max_rebalances_per_day: Option<u32>
+}
+
+impl RebalanceLimits {
+    pub fn new(limits: RebalanceLimitsInstantiateMsg) -> Result<Self, InstantiationError> {
+        if limits.max_rebalances_per_day == Some(0) {
+            return Err(InstantiationError::ContradictoryConfig {
+                reason: "Zero max rebalances per day would lock the vault positions".into()
+            })
+        }
+
+        Ok(RebalanceLimits {
+            min_seconds_between_rebalances: limits.min_seconds_between_rebalances.into(),
+            max_rebalances_per_day: limits.max_rebalances_per_day
+        })
+    }
+
+    pub fn is_at_least_as_strict_as(&self, other: &RebalanceLimits) -> bool {
+        let max_per_day_as_strict = match (self.max_rebalances_per_day, other.max_rebalances_per_day) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(a), Some(b)) => a <= b
+        };
+        self.min_seconds_between_rebalances >= other.min_seconds_between_rebalances
+            && max_per_day_as_strict
+    }
+}
+
+#[cw_serde]
+pub struct PendingRebalanceLimits {
+    pub limits: RebalanceLimits,
+    pub effective_at: Timestamp
+}
+
+/// See [`TwapConfigInstantiateMsg`].
+#[cw_serde]
+pub struct TwapConfig {
+    pub seconds: u64,
+    pub max_deviation: Weight,
+    pub on_failure: TwapFallback
+}
+
+impl TwapConfig {
+    pub fn new(config: TwapConfigInstantiateMsg) -> Result<Self, InstantiationError> {
+        use InstantiationError::*;
+        let seconds: u64 = config.seconds.into();
+        if seconds == 0 || seconds > MAX_TWAP_SECONDS {
+            return Err(ContradictoryConfig {
+                reason: format!("The TWAP window must be in [1, {MAX_TWAP_SECONDS}] seconds")
+            })
+        }
+
+        let max_deviation = Weight::new(&config.max_deviation)
+            .ok_or(InvalidWeight(config.max_deviation))?;
+
+        Ok(TwapConfig { seconds, max_deviation, on_failure: config.on_failure })
+    }
+
+    /// Shorter windows are easier to manipulate, so they count as looser.
+    pub fn is_at_least_as_strict_as(&self, other: &TwapConfig) -> bool {
+        self.seconds >= other.seconds
+            && self.max_deviation.0 <= other.max_deviation.0
+            && self.on_failure.strictness() >= other.on_failure.strictness()
+    }
+}
+
+#[cw_serde]
+pub struct PendingTwapConfig {
+    pub config: Option<TwapConfig>,
+    pub effective_at: Timestamp
+}
+
+impl Default for TwapConfig {
+    fn default() -> Self {
+        Self {
+            seconds: TWAP_SECONDS,
+            // Invariant: Wont panic as the const is in [0, 1].
+            max_deviation: Weight::try_from(DEFAULT_TWAP_MAX_DEVIATION).unwrap(),
+            on_failure: TwapFallback::Reject {}
+        }
+    }
+}
+
 /// See [`VaultRebalancerInstantiateMsg`].
 #[cw_serde]
 pub enum VaultRebalancer {
@@ -421,10 +1111,67 @@ pub enum VaultRebalancer {
     Anyone {
         price_factor_before_rebalance: PriceFactor,
         time_before_rabalance: Timestamp,
+        policy: Option<RebalancePolicy>
+    }
+}
+
+/// See [`RebalancePolicyInstantiateMsg`].
+#[cw_serde]
+pub enum RebalancePolicy {
+    And(Vec<RebalancePolicy>),
+    Or(Vec<RebalancePolicy>),
+    TimePassed { time: Timestamp },
+    PriceMoved { price_factor: PriceFactor },
+    PriceOutsideBaseRange {},
+    LimitOrderFilled {},
+    IdleBalanceAbove { share: Weight },
+    UncollectedFeesAbove { value: Uint128 },
+}
+
+impl RebalancePolicy {
+    pub fn new(policy: RebalancePolicyInstantiateMsg) -> Result<Self, InstantiationError> {
+        use InstantiationError::*;
+        use RebalancePolicyInstantiateMsg as Msg;
+
+        let new_all = |policies: Vec<Msg>| -> Result<Vec<Self>, InstantiationError> {
+            if policies.is_empty() {
+                return Err(ContradictoryConfig {
+                    reason: "Rebalance policy combinations cant be empty".into()
+                })
+            }
+            policies.into_iter().map(Self::new).collect()
+        };
+
+        match policy {
+            Msg::And(policies) => Ok(Self::And(new_all(policies)?)),
+            Msg::Or(policies) => Ok(Self::Or(new_all(policies)?)),
+            Msg::TimePassed { seconds } => Ok(Self::TimePassed {
+                time: Timestamp::from_seconds(seconds.into())
+            }),
+            Msg::PriceMoved { price_factor } => Ok(Self::PriceMoved {
+                price_factor: PriceFactor::new(&price_factor)
+                    .ok_or(InvalidPriceFactor(price_factor))?
+            }),
+            Msg::PriceOutsideBaseRange {} => Ok(Self::PriceOutsideBaseRange {}),
+            Msg::LimitOrderFilled {} => Ok(Self::LimitOrderFilled {}),
+            Msg::IdleBalanceAbove { share } => Ok(Self::IdleBalanceAbove {
+                share: Weight::new(&share).ok_or(InvalidWeight(share))?
+            }),
+            Msg::UncollectedFeesAbove { value } => Ok(Self::UncollectedFeesAbove { value })
+        }
     }
 }
 
 impl VaultRebalancer {
+    /// Conditions for anyone to rebalance while [`VaultInfo::fallback_rebalancer_active`].
+    pub fn fallback() -> Self {
+        Self::Anyone {
+            price_factor_before_rebalance: PriceFactor(FALLBACK_PRICE_FACTOR_BEFORE_REBALANCE),
+            time_before_rabalance: Timestamp::from_seconds(FALLBACK_SECONDS_BEFORE_REBALANCE),
+            policy: None
+        }
+    }
+
     pub fn new(
         rebalancer: VaultRebalancerInstantiateMsg,
         deps: Deps
@@ -442,11 +1189,12 @@ impl VaultRebalancer {
             }
             Admin {} => Ok(Self::Admin {}),
             Anyone {
-                seconds_before_rebalance, price_factor_before_rebalance
+                seconds_before_rebalance, price_factor_before_rebalance, policy
             } => Ok(Self::Anyone {
                 price_factor_before_rebalance: PriceFactor::new(&price_factor_before_rebalance)
                     .ok_or(InvalidPriceFactor(price_factor_before_rebalance))?,
-                time_before_rabalance: Timestamp::from_seconds(seconds_before_rebalance.into())
+                time_before_rabalance: Timestamp::from_seconds(seconds_before_rebalance.into()),
+                policy: policy.map(RebalancePolicy::new).transpose()?
             })
         }
     }
@@ -466,6 +1214,31 @@ impl VaultRebalancer {
     }
 }
 
+/// Operations an address in [`DELEGATED_REBALANCERS`] can do.
+#[cw_serde]
+pub enum RebalancerPermission {
+    /// Full rebalances, and any other operation below.
+    Full {},
+    /// Only moving the limit position next to the current price.
+    LimitOnly {},
+    /// Only collecting spread fees into the vault balances.
+    Compound {},
+}
+
+#[cw_serde]
+pub struct DelegatedRebalancer {
+    pub permission: RebalancerPermission,
+    pub min_seconds_between_calls: u64,
+    /// Last time the address did any operation, if it did.
+    pub last_call: Option<Timestamp>
+}
+
+impl DelegatedRebalancer {
+    pub fn allows(&self, operation: &RebalancerPermission) -> bool {
+        self.permission == RebalancerPermission::Full {} || self.permission == *operation
+    }
+}
+
 #[cw_serde]
 pub enum PositionType { FullRange, Base, Limit }
 
@@ -477,6 +1250,15 @@ pub struct StateSnapshot {
     pub last_timestamp: Timestamp
 }
 
+/// Progress of a rebalance that moves liquidity in several calls. See
+/// [`VaultParameters::rebalance_step`].
+#[cw_serde]
+pub struct GradualRebalance {
+    /// Proportion of the liquidity the vault had when the rebalance started
+    /// thats still in the old positions.
+    pub liquidity_left: Weight
+}
+
 #[cw_serde]
 #[derive(Default)]
 pub struct VaultState {
@@ -491,7 +1273,20 @@ pub struct VaultState {
     /// last price and last timestamp since the last rebalance. Optional as it
     /// requires a first rebalance to happen to be set. After that, both will
     /// always be set.
-    pub last_price_and_timestamp: Option<StateSnapshot>
+    pub last_price_and_timestamp: Option<StateSnapshot>,
+
+    /// Only present while a gradual rebalance is being done.
+    pub rebalance_in_progress: Option<GradualRebalance>,
+
+    /// Whether the last rebalance was done by anyone, as allowed by
+    /// [`VaultInfo::fallback_rebalancer_active`].
+    pub last_rebalance_by_fallback: bool,
+
+    /// Timestamps of the rebalances done in the last day, see [`RebalanceLimits`].
+    pub recent_rebalances: Vec<Timestamp>,
+
+    /// Whether the vault is in the protected state of its [`CircuitBreaker`].
+    pub circuit_breaker_tripped: bool
 }
 
 impl VaultState {
@@ -502,6 +1297,322 @@ impl VaultState {
             PositionType::Limit => self.limit_position_id
         }
     }
+
+    /// The state once all vault positions are closed.
+    pub fn without_positions(self) -> Self {
+        VaultState {
+            full_range_position_id: None,
+            base_position_id: None,
+            limit_position_id: None,
+            rebalance_in_progress: None,
+            ..self
+        }
+    }
+
+    pub fn has_positions(&self) -> bool {
+        self.full_range_position_id.is_some()
+            || self.base_position_id.is_some()
+            || self.limit_position_id.is_some()
+    }
+}
+
+#[cw_serde]
+pub struct PositionSnapshot {
+    pub position_type: PositionType,
+    pub lower_tick: i64,
+    pub upper_tick: i64,
+    pub amount0: Uint128,
+    pub amount1: Uint128
+}
+
+#[cw_serde]
+pub struct RebalanceRecord {
+    pub price: Decimal,
+    /// Not present if the pool had no TWAP for the vault [`TwapConfig`] window.
+    pub twap: Option<Decimal>,
+    pub timestamp: Timestamp,
+    pub caller: Addr,
+    /// Liquidity taken out of the vault positions. For a step of a gradual
+    /// rebalance, only the moved proportion of each position.
+    pub closed_positions: Vec<PositionSnapshot>,
+    /// Positions created, with the amounts provided to them.
+    pub opened_positions: Vec<PositionSnapshot>,
+    pub fees_collected0: Uint128,
+    pub fees_collected1: Uint128,
+    pub protocol_fees0: Uint128,
+    pub protocol_fees1: Uint128,
+    pub admin_fees0: Uint128,
+    pub admin_fees1: Uint128,
+    /// Value of a vault share in token1 at `price`, from the vault balances
+    /// before the rebalance, with the uncollected spread fees already credited.
+    pub share_price: Decimal
+}
+
+/// See [`KeeperRewardInstantiateMsg`].
+#[cw_serde]
+pub struct KeeperReward {
+    pub share: ProtocolFee,
+    pub max_amount0: Option<Uint128>,
+    pub max_amount1: Option<Uint128>
+}
+
+impl KeeperReward {
+    pub fn new(reward: KeeperRewardInstantiateMsg) -> Result<Self, InstantiationError> {
+        let share = ProtocolFee::new(&reward.share).ok_or(InstantiationError::InvalidKeeperReward {
+            max: ProtocolFee::max().atomics(),
+            got: reward.share,
+        })?;
+
+        Ok(KeeperReward { share, max_amount0: reward.max_amount0, max_amount1: reward.max_amount1 })
+    }
+
+    /// # Returns
+    ///
+    /// The amounts to pay to a keeper out of the `owned` keeper rewards.
+    pub fn payout(&self, owned0: Uint128, owned1: Uint128) -> (Uint128, Uint128) {
+        (
+            self.max_amount0.map_or(owned0, |max| min(max, owned0)),
+            self.max_amount1.map_or(owned1, |max| min(max, owned1))
+        )
+    }
+}
+
+#[cw_serde]
+pub enum ReferenceToken {
+    Token0 {},
+    Token1 {},
+}
+
+/// See [`PerformanceFeeInstantiateMsg`].
+#[cw_serde]
+pub struct PerformanceFee {
+    pub fee: Weight,
+    pub reference_token: ReferenceToken,
+    /// Share price in the reference token after the last charge. Its only
+    /// `None` until the first rebalance or harvest after setting the fee.
+    pub high_water_mark: Option<Decimal>
+}
+
+impl PerformanceFee {
+    pub fn new(
+        performance_fee: PerformanceFeeInstantiateMsg,
+        high_water_mark: Option<Decimal>,
+        vault_info: &VaultInfo
+    ) -> Result<Self, InstantiationError> {
+        let fee = Weight::new(&performance_fee.fee)
+            .filter(|x| x.0 <= MAX_PERFORMANCE_FEE)
+            .ok_or(InstantiationError::InvalidPerformanceFee {
+                max: MAX_PERFORMANCE_FEE.atomics(),
+                got: performance_fee.fee
+            })?;
+
+        if vault_info.admin.is_none() {
+            return Err(InstantiationError::AdminFeeWithoutAdmin {})
+        }
+
+        Ok(PerformanceFee { fee, reference_token: performance_fee.reference_token, high_water_mark })
+    }
+
+    /// Charges the fee on the gains above the high-water mark, for a vault
+    /// worth `value` of the reference token, and raises the mark.
+    ///
+    /// # Returns
+    ///
+    /// The amount of shares to mint as fee.
+    pub fn charge(&mut self, value: Uint128, total_supply: Uint128) -> Uint128 {
+        let Ok(share_price) = Decimal::checked_from_ratio(value, total_supply) else {
+            return Uint128::zero()
+        };
+
+        let high_water_mark = match self.high_water_mark {
+            Some(x) if share_price > x => x,
+            Some(_) => return Uint128::zero(),
+            None => {
+                self.high_water_mark = Some(share_price);
+                return Uint128::zero()
+            }
+        };
+
+        let high_water_value = Decimal::new(total_supply)
+            .checked_mul(high_water_mark)
+            .map(|x| x.atomics())
+            .unwrap_or(value);
+        let fee_value = self.fee.mul_raw(value.saturating_sub(high_water_value)).atomics();
+
+        // NOTE: Minting `s` shares gives away `s / (total_supply + s)` of the vault
+        //       value, so `s = fee_value * total_supply / (value - fee_value)`.
+        // Invariant: Wont panic, as the fee is below one and so `fee_value < value`.
+        let shares = total_supply
+            .checked_multiply_ratio(fee_value, value.checked_sub(fee_value).unwrap())
+            .unwrap_or_default();
+
+        self.high_water_mark = Some(
+            Decimal::checked_from_ratio(value, total_supply.saturating_add(shares)).unwrap_or(share_price)
+        );
+        shares
+    }
+}
+
+/// See [`crate::msg::VaultInfoInstantiateMsg::management_fee`].
+#[cw_serde]
+pub struct ManagementFee {
+    /// Per year.
+    pub fee: ProtocolFee,
+    /// Its only `None` until the first deposit, withdrawal or
+    /// rebalance after setting the fee.
+    pub last_accrual: Option<Timestamp>
+}
+
+impl ManagementFee {
+    pub fn new(fee: Uint128, vault_info: &VaultInfo) -> Result<Self, InstantiationError> {
+        let fee = ProtocolFee::new(&fee).ok_or(InstantiationError::InvalidManagementFee {
+            max: ProtocolFee::max().atomics(),
+            got: fee
+        })?;
+
+        if vault_info.admin.is_none() {
+            return Err(InstantiationError::AdminFeeWithoutAdmin {})
+        }
+
+        Ok(ManagementFee { fee, last_accrual: None })
+    }
+
+    /// Accrues the fee for the time passed since the last accrual, compounding
+    /// it once per full year.
+    ///
+    /// # Returns
+    ///
+    /// The amount of shares to mint as fee.
+    pub fn accrue(&mut self, now: Timestamp, total_supply: Uint128) -> Uint128 {
+        let Some(last_accrual) = self.last_accrual.replace(now) else {
+            return Uint128::zero()
+        };
+
+        let elapsed = now.seconds().saturating_sub(last_accrual.seconds());
+        let years = u32::try_from(elapsed / SECONDS_IN_A_YEAR).unwrap_or(u32::MAX);
+        let remainder = Decimal::from_ratio(elapsed % SECONDS_IN_A_YEAR, SECONDS_IN_A_YEAR);
+        let remainder = self.fee.0.mul_dec(&remainder);
+
+        // NOTE: Each full year charges the fee on what the previous ones left.
+        // Invariant: Wont underflow, as both the fee and `remainder` are below one.
+        let kept = (Decimal::one() - self.fee.0.0)
+            .checked_pow(years)
+            .unwrap_or_default()
+            .checked_mul(Decimal::one() - remainder)
+            .unwrap();
+
+        // NOTE: Minting `s` shares gives away `s / (total_supply + s)` of the vault
+        //       value, so `s = total_supply * (1 - kept) / kept`. Only if `kept` rounds
+        //       down to zero, after centuries of inactivity, nothing is charged.
+        total_supply
+            .checked_multiply_ratio((Decimal::one() - kept).atomics(), kept.atomics())
+            .unwrap_or_default()
+    }
+}
+
+#[cw_serde]
+pub enum FeeRecipient {
+    /// Kept by the vault, for the remaining shareholders.
+    Vault {},
+    Admin {},
+    Protocol {},
+}
+
+/// See [`EntryExitFeesInstantiateMsg`].
+#[cw_serde]
+pub struct EntryExitFees {
+    pub entry_fee: Weight,
+    pub exit_fee: Weight,
+    pub recipient: FeeRecipient
+}
+
+impl EntryExitFees {
+    pub fn new(fees: EntryExitFeesInstantiateMsg, vault_info: &VaultInfo) -> Result<Self, InstantiationError> {
+        let validate = |fee: Uint128| Weight::new(&fee)
+            .filter(|x| x.0 <= MAX_ENTRY_EXIT_FEE)
+            .ok_or(InstantiationError::InvalidEntryExitFee {
+                max: MAX_ENTRY_EXIT_FEE.atomics(),
+                got: fee
+            });
+
+        let entry_fee = validate(fees.entry_fee)?;
+        let exit_fee = validate(fees.exit_fee)?;
+
+        if fees.recipient == (FeeRecipient::Admin {}) && vault_info.admin.is_none() {
+            return Err(InstantiationError::AdminFeeWithoutAdmin {})
+        }
+
+        Ok(EntryExitFees { entry_fee, exit_fee, recipient: fees.recipient })
+    }
+}
+
+/// Lifetime amounts of a denom, never reset by fee withdrawals.
+#[cw_serde]
+#[derive(Default)]
+pub struct RevenueStats {
+    /// All spread fees collected from the vault positions, before any cut.
+    pub spread_fees: Uint128,
+    pub protocol_fees_accrued: Uint128,
+    pub protocol_fees_paid: Uint128,
+    pub admin_fees_accrued: Uint128,
+    pub admin_fees_paid: Uint128,
+    pub creation_costs_collected: Uint128,
+    pub creation_costs_paid: Uint128,
+    /// Vault tokens got from swapping reward tokens, before any cut.
+    pub incentives_harvested: Uint128
+}
+
+#[cw_serde]
+pub struct PendingAdminFee {
+    pub admin_fee: ProtocolFee,
+    pub effective_at: Timestamp
+}
+
+#[cw_serde]
+pub struct AdminFeeBeneficiary {
+    pub address: Addr,
+    pub weight: Weight
+}
+
+impl AdminFeeBeneficiary {
+    pub fn new_list(
+        beneficiaries: Vec<(String, Uint128)>,
+        deps: Deps
+    ) -> Result<Vec<Self>, InstantiationError> {
+        use InstantiationError::*;
+
+        let beneficiaries = beneficiaries
+            .into_iter()
+            .map(|(address, weight)| Ok(AdminFeeBeneficiary {
+                address: deps.api
+                    .addr_validate(&address)
+                    .map_err(|_| InvalidBeneficiaryAddress(address))?,
+                weight: Weight::new(&weight).ok_or(InvalidWeight(weight))?
+            }))
+            .collect::<Result<Vec<_>, InstantiationError>>()?;
+
+        if beneficiaries.is_empty() {
+            return Ok(beneficiaries)
+        }
+
+        let mut addresses: Vec<_> = beneficiaries.iter().map(|x| &x.address).collect();
+        addresses.sort();
+        addresses.dedup();
+        if addresses.len() != beneficiaries.len() {
+            return Err(ContradictoryConfig { reason: "Repeated admin fee beneficiary".into() })
+        }
+
+        let total_weight = beneficiaries
+            .iter()
+            .try_fold(Decimal::zero(), |acc, x| acc.checked_add(x.weight.0).ok());
+        if total_weight != Some(Decimal::one()) {
+            return Err(ContradictoryConfig {
+                reason: "Admin fee beneficiary weights must add up to one".into()
+            })
+        }
+
+        Ok(beneficiaries)
+    }
 }
 
 #[cw_serde]
@@ -510,29 +1621,30 @@ pub struct FeesInfo {
     pub protocol_fee: ProtocolFee,
     pub protocol_tokens0_owned: Uint128,
     pub protocol_tokens1_owned: Uint128,
-    pub protocol_vault_creation_cost: VaultCreationCost,
+    /// Cost paid at instantiation, if any was due.
+    pub protocol_vault_creation_cost: Option<VaultCreationCost>,
     pub protocol_vault_creation_tokens_owned: Uint128,
+    /// Can be stale until stored again, so read it after
+    /// [`FeesInfo::apply_pending_admin_fee`].
     pub admin_fee: ProtocolFee,
+    pub pending_admin_fee: Option<PendingAdminFee>,
     pub admin_tokens0_owned: Uint128,
-    pub admin_tokens1_owned: Uint128
+    pub admin_tokens1_owned: Uint128,
+    /// Only taken while the vault rebalancer is `Anyone`.
+    pub keeper_reward: Option<KeeperReward>,
+    pub keeper_tokens0_owned: Uint128,
+    pub keeper_tokens1_owned: Uint128,
+    /// Paid in vault shares minted to the admin, so it has no owned tokens.
+    pub performance_fee: Option<PerformanceFee>,
+    /// Paid in vault shares minted to the admin, so it has no owned tokens.
+    pub management_fee: Option<ManagementFee>,
+    pub entry_exit_fees: Option<EntryExitFees>,
+    /// If empty, the admin gets all admin fees.
+    pub admin_fee_beneficiaries: Vec<AdminFeeBeneficiary>
 }
 
 impl FeesInfo {
     
-    fn validate_vault_creation_cost(info: &MessageInfo) -> Result<Uint128, InstantiationError> {
-        let vault_creation_cost = VaultCreationCost::default();
-
-        let paid_amount = cw_utils::must_pay(info, VAULT_CREATION_COST_DENOM).unwrap_or_default();
-
-        if paid_amount != vault_creation_cost.0 {
-            Err(InstantiationError::VaultCreationCostNotPaid {
-                cost: vault_creation_cost.0.into(),
-                denom: VAULT_CREATION_COST_DENOM.into(),
-                got: paid_amount.into()
-            })
-        } else { Ok(paid_amount) }
-    }
-
     fn validate_admin_fee(admin_fee: Uint128, vault_info: &VaultInfo) -> Result<ProtocolFee, InstantiationError> {
         let admin_fee = ProtocolFee::new(&admin_fee).ok_or(InstantiationError::InvalidAdminFee {
             max: ProtocolFee::max().atomics(),
@@ -546,24 +1658,213 @@ impl FeesInfo {
 
     pub fn new(
         admin_fee: Uint128,
-        vault_info: &VaultInfo,
-        info: &MessageInfo
+        keeper_reward: Option<KeeperRewardInstantiateMsg>,
+        performance_fee: Option<PerformanceFeeInstantiateMsg>,
+        management_fee: Option<Uint128>,
+        entry_exit_fees: Option<EntryExitFeesInstantiateMsg>,
+        vault_creation_cost: Option<VaultCreationCost>,
+        vault_info: &VaultInfo
     ) -> Result<FeesInfo, InstantiationError> {
-        let paid_amount = Self::validate_vault_creation_cost(info)?;
         let admin_fee = Self::validate_admin_fee(admin_fee, vault_info)?;
+        let keeper_reward = keeper_reward.map(KeeperReward::new).transpose()?;
+        let performance_fee = performance_fee
+            .map(|x| PerformanceFee::new(x, None, vault_info))
+            .transpose()?;
+        let management_fee = management_fee
+            .map(|x| ManagementFee::new(x, vault_info))
+            .transpose()?;
+        let entry_exit_fees = entry_exit_fees
+            .map(|x| EntryExitFees::new(x, vault_info))
+            .transpose()?;
 
         Ok(FeesInfo {
             admin_fee,
-            protocol_vault_creation_tokens_owned: paid_amount,
+            keeper_reward,
+            performance_fee,
+            management_fee,
+            entry_exit_fees,
+            protocol_vault_creation_tokens_owned: vault_creation_cost
+                .as_ref()
+                .map_or(Uint128::zero(), |x| x.amount),
+            protocol_vault_creation_cost: vault_creation_cost,
             ..FeesInfo::default()
         })
     }
 
-    pub fn update_admin_fee(&self, admin_fee: Uint128, deps: Deps) -> Result<FeesInfo, InstantiationError> {
+    /// Decreases apply right away, while increases of at most [`MAX_ADMIN_FEE_INCREASE`]
+    /// are left pending for [`ADMIN_FEE_INCREASE_NOTICE_SECONDS`].
+    pub fn update_admin_fee(
+        &self,
+        admin_fee: Uint128,
+        now: Timestamp,
+        deps: Deps
+    ) -> Result<FeesInfo, AdminOperationError> {
         // Invariant: Any state is present after instantitation.
         let vault_info = VAULT_INFO.load(deps.storage).unwrap();
         let admin_fee = Self::validate_admin_fee(admin_fee, &vault_info)?;
-        Ok(FeesInfo { admin_fee, ..self.clone() })
+        let current = self.clone().apply_pending_admin_fee(now);
+
+        if admin_fee.0.0 <= current.admin_fee.0.0 {
+            return Ok(FeesInfo { admin_fee, pending_admin_fee: None, ..current })
+        }
+
+        // Invariant: Wont overflow, as both are below one.
+        let max = current.admin_fee.0.0 + MAX_ADMIN_FEE_INCREASE;
+        if admin_fee.0.0 > max {
+            return Err(AdminOperationError::AdminFeeIncreaseTooLarge {
+                max: max.atomics(),
+                got: admin_fee.0.0.atomics()
+            })
+        }
+
+        Ok(FeesInfo {
+            pending_admin_fee: Some(PendingAdminFee {
+                admin_fee,
+                effective_at: now.plus_seconds(ADMIN_FEE_INCREASE_NOTICE_SECONDS)
+            }),
+            ..current
+        })
+    }
+
+    /// Admin fee in effect at `now`, see [`FeesInfo::update_admin_fee`].
+    pub fn apply_pending_admin_fee(self, now: Timestamp) -> Self {
+        match self.pending_admin_fee {
+            Some(pending) if pending.effective_at <= now =>
+                Self { admin_fee: pending.admin_fee, pending_admin_fee: None, ..self },
+            _ => self
+        }
+    }
+
+    pub fn update_keeper_reward(
+        &self,
+        keeper_reward: Option<KeeperRewardInstantiateMsg>
+    ) -> Result<FeesInfo, InstantiationError> {
+        let keeper_reward = keeper_reward.map(KeeperReward::new).transpose()?;
+        Ok(FeesInfo { keeper_reward, ..self.clone() })
+    }
+
+    pub fn update_performance_fee(
+        &self,
+        performance_fee: Option<PerformanceFeeInstantiateMsg>,
+        vault_info: &VaultInfo
+    ) -> Result<FeesInfo, InstantiationError> {
+        // NOTE: Keeping the mark prevents charging again gains already charged.
+        let performance_fee = performance_fee.map(|x| {
+            let high_water_mark = self.performance_fee
+                .as_ref()
+                .filter(|current| current.reference_token == x.reference_token)
+                .and_then(|current| current.high_water_mark);
+            PerformanceFee::new(x, high_water_mark, vault_info)
+        }).transpose()?;
+
+        Ok(FeesInfo { performance_fee, ..self.clone() })
+    }
+
+    /// Should only be called right after accruing the current management fee.
+    pub fn update_management_fee(
+        &self,
+        management_fee: Option<Uint128>,
+        vault_info: &VaultInfo,
+        now: Timestamp
+    ) -> Result<FeesInfo, InstantiationError> {
+        let management_fee = management_fee
+            .map(|x| ManagementFee::new(x, vault_info))
+            .transpose()?
+            .map(|x| ManagementFee { last_accrual: Some(now), ..x });
+
+        Ok(FeesInfo { management_fee, ..self.clone() })
+    }
+
+    pub fn update_entry_exit_fees(
+        &self,
+        entry_exit_fees: Option<EntryExitFeesInstantiateMsg>,
+        vault_info: &VaultInfo
+    ) -> Result<FeesInfo, InstantiationError> {
+        let entry_exit_fees = entry_exit_fees
+            .map(|x| EntryExitFees::new(x, vault_info))
+            .transpose()?;
+        Ok(FeesInfo { entry_exit_fees, ..self.clone() })
+    }
+
+    pub fn update_admin_fee_beneficiaries(
+        &self,
+        beneficiaries: Vec<(String, Uint128)>,
+        deps: Deps
+    ) -> Result<FeesInfo, InstantiationError> {
+        let admin_fee_beneficiaries = AdminFeeBeneficiary::new_list(beneficiaries, deps)?;
+        Ok(FeesInfo { admin_fee_beneficiaries, ..self.clone() })
+    }
+
+    /// Splits `amount0` and `amount1` of the owned admin tokens among the admin
+    /// fee beneficiaries, or gives them all to `admin` if there are none.
+    ///
+    /// # Returns
+    ///
+    /// The amounts of token0 and token1 owed to each address.
+    pub fn admin_fee_payouts(
+        &self,
+        admin: &Addr,
+        amount0: Uint128,
+        amount1: Uint128
+    ) -> Vec<(Addr, Uint128, Uint128)> {
+        let Some((last, rest)) = self.admin_fee_beneficiaries.split_last() else {
+            return vec![(admin.clone(), amount0, amount1)]
+        };
+
+        let mut payouts: Vec<_> = rest
+            .iter()
+            .map(|x| (
+                x.address.clone(),
+                x.weight.mul_raw(amount0).atomics(),
+                x.weight.mul_raw(amount1).atomics()
+            ))
+            .collect();
+
+        // NOTE: The last beneficiary gets the rounding dust, so all is paid.
+        // Invariant: Wont underflow, as the weights add up to one.
+        let (paid0, paid1) = payouts
+            .iter()
+            .fold((Uint128::zero(), Uint128::zero()), |(acc0, acc1), (_, x0, x1)| {
+                (acc0 + x0, acc1 + x1)
+            });
+        payouts.push((
+            last.address.clone(),
+            amount0.checked_sub(paid0).unwrap(),
+            amount1.checked_sub(paid1).unwrap()
+        ));
+
+        payouts
+    }
+
+    pub fn entry_fee(&self) -> Weight {
+        self.entry_exit_fees.as_ref().map_or(Weight::zero(), |x| x.entry_fee.clone())
+    }
+
+    pub fn exit_fee(&self) -> Weight {
+        self.entry_exit_fees.as_ref().map_or(Weight::zero(), |x| x.exit_fee.clone())
+    }
+
+    /// Credits `fee0` and `fee1` of entry or exit fees to their recipient.
+    ///
+    /// # Returns
+    ///
+    /// Whether the fees are kept by the vault, and so must be credited to its funds.
+    pub fn credit_entry_exit_fees(&mut self, fee0: Uint128, fee1: Uint128) -> bool {
+        // Invariant: Wont overflow, as for that the token supply would have
+        //            to be above `Uint128::MAX`.
+        match self.entry_exit_fees.as_ref().map(|x| &x.recipient) {
+            Some(FeeRecipient::Admin {}) => {
+                self.admin_tokens0_owned += fee0;
+                self.admin_tokens1_owned += fee1;
+                false
+            },
+            Some(FeeRecipient::Protocol {}) => {
+                self.protocol_tokens0_owned += fee0;
+                self.protocol_tokens1_owned += fee1;
+                false
+            },
+            Some(FeeRecipient::Vault {}) | None => true
+        }
     }
 
     pub fn update_protocol_fee(&self, protocol_fee: Uint128) -> Result<FeesInfo, ProtocolOperationError> {
@@ -584,6 +1885,26 @@ pub struct FundsInfo {
     pub available_balance1: Uint128
 }
 
+/// Distribution state of a reward token, i.e., any token other than the
+/// pool pair sent to the vault, like incentives or airdrops.
+#[cw_serde]
+#[derive(Default)]
+pub struct RewardIndex {
+    /// Cumulative amount of reward tokens per vault share.
+    pub index: Decimal256,
+    /// Reward tokens held by the vault that are already distributed
+    /// through `index`, but not yet claimed.
+    pub accounted: Uint128
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct ShareholderReward {
+    /// Value of the [`RewardIndex`] when the shareholder rewards were last settled.
+    pub index: Decimal256,
+    pub pending: Uint128
+}
+
 /// VAULT_INFO Holds non-mathematical generally immutable information
 /// about the vault. Its generally immutable as in it can only be
 /// changed by the vault admin, but its state cant be changed with
@@ -603,6 +1924,42 @@ pub const VAULT_STATE: Item<VaultState> = Item::new("vault_state");
 /// FEES_INFO Holds any uncollected admin/protocol fees and fee parameters.
 pub const FEES_INFO: Item<FeesInfo> = Item::new("fees_info");
 
+/// PROTOCOL_INFO Holds the protocol account and its fee recipient, which are
+/// set at instantiation and can only be changed by the protocol.
+pub const PROTOCOL_INFO: Item<ProtocolInfo> = Item::new("protocol_info");
+
+/// DELEGATED_REBALANCERS Holds the addresses the admin allowed to operate
+/// the vault positions, besides the vault rebalancer.
+pub const DELEGATED_REBALANCERS: Map<&Addr, DelegatedRebalancer> = Map::new("delegated_rebalancers");
+
+/// REBALANCE_HISTORY Holds a record of every rebalance, keyed by sequence number.
+pub const REBALANCE_HISTORY: Map<u64, RebalanceRecord> = Map::new("rebalance_history");
+
+/// REWARD_DENOMS Holds the reward denoms registered by the admin to be
+/// distributed to shareholders. Other tokens sent to the vault are ignored.
+pub const REWARD_DENOMS: Map<&str, ()> = Map::new("reward_denoms");
+
+/// REWARD_INDEXES Holds the [`RewardIndex`] of every reward denom ever distributed.
+pub const REWARD_INDEXES: Map<&str, RewardIndex> = Map::new("reward_indexes");
+
+/// SHAREHOLDER_REWARDS Holds the settled rewards of each shareholder, per reward denom.
+pub const SHAREHOLDER_REWARDS: Map<(&Addr, &str), ShareholderReward> = Map::new("shareholder_rewards");
+
+/// HARVEST_ROUTES Holds the [`HarvestRoute`] of each reward denom that gets
+/// swapped into the vault tokens instead of distributed to shareholders.
+pub const HARVEST_ROUTES: Map<&str, HarvestRoute> = Map::new("harvest_routes");
+
+/// ACCESS_LIST Holds the addresses of the vault allowlist or blocklist,
+/// see [`AccessList`].
+pub const ACCESS_LIST: Map<&Addr, ()> = Map::new("access_list");
+
+/// REVENUE_STATS Holds the lifetime [`RevenueStats`] of each denom.
+pub const REVENUE_STATS: Map<&str, RevenueStats> = Map::new("revenue_stats");
+
+/// SHARES_LOCKS Holds the [`SharesLock`] of each depositor, if the vault has a
+/// withdrawal cooldown. Expired locks are left in place.
+pub const SHARES_LOCKS: Map<&Addr, SharesLock> = Map::new("shares_locks");
+
 /// FUNDS_INFO Refers to the known funds available to the contract,
 /// without counting protocol/admin fees.
 pub const FUNDS_INFO: Item<FundsInfo> = Item::new("funds_info");
@@ -60,6 +60,26 @@ pub fn raw<T: From<Uint128>>(d: &Decimal) -> T {
     d.atomics().into()
 }
 
+/// Value of atomic amounts of both vault tokens in atomic amounts of
+/// token1, ie, `amount0*price + amount1`. Saturates on overflow.
+pub fn value_in_token1(amount0: Uint128, amount1: Uint128, price: &Decimal) -> Uint128 {
+    Decimal::new(amount0)
+        .checked_mul(*price)
+        .and_then(|x| x.checked_add(Decimal::new(amount1)))
+        .map(|x| x.atomics())
+        .unwrap_or(Uint128::MAX)
+}
+
+/// Value of atomic amounts of both vault tokens in atomic amounts of
+/// token0, ie, `amount0 + amount1/price`. Saturates on overflow.
+pub fn value_in_token0(amount0: Uint128, amount1: Uint128, price: &Decimal) -> Uint128 {
+    Decimal::new(amount1)
+        .checked_div(*price).ok()
+        .and_then(|x| x.checked_add(Decimal::new(amount0)).ok())
+        .map(|x| x.atomics())
+        .unwrap_or(Uint128::MAX)
+}
+
 /// Generalized inverse of Osmosis price function. Ie, it 
 /// maps each price to its closest tick. Read whitepaper
 /// for further clarification.
@@ -98,12 +118,104 @@ pub fn price_function_inv(p: &Decimal) -> i32 {
     compute_price_inverse(p).unwrap()
 }
 
+/// Osmosis price function. Ie, it maps each tick to its price,
+/// being the inverse of [`price_function_inv`] on ticks. Read
+/// whitepaper for further clarification.
+pub fn price_function(tick: i32) -> Decimal256 {
+    let compute_price = || -> Option<Decimal256> {
+        // NOTE: Each power of ten has 9e6 ticks, and tick `0` is price `1`.
+        let exponent = tick.div_euclid(9_000_000);
+        let mantissa = tick.checked_sub(exponent.checked_mul(9)?.checked_sub(1)?.checked_mul(1_000_000)?)?;
+        let mantissa = Decimal256::from_ratio(u128::try_from(mantissa).ok()?, 1u128);
+
+        let ten = Decimal256::from_ratio(10u128, 1u128);
+        let scale = ten.checked_pow(exponent.checked_sub(6)?.unsigned_abs()).ok()?;
+        if exponent >= 6 {
+            mantissa.checked_mul(scale).ok()
+        } else {
+            mantissa.checked_div(scale).ok()
+        }
+    };
+
+    // Invariant: Wont panic for ticks within the pool tick range.
+    compute_price().unwrap()
+}
+
+/// Liquidity of a position in `[lower_tick, upper_tick]` holding
+/// `amount0` and `amount1` at `price`. Ie, the largest liquidity
+/// that both amounts can provide. Saturates on overflow.
+pub fn liquidity_for_amounts(
+    price: &Decimal,
+    lower_tick: i32,
+    upper_tick: i32,
+    amount0: Uint128,
+    amount1: Uint128
+) -> Decimal256 {
+    let sqrt_price = Decimal256::from(*price).sqrt();
+    let sqrt_lower = price_function(lower_tick).sqrt();
+    let sqrt_upper = price_function(upper_tick).sqrt();
+    let sqrt_price = sqrt_price.clamp(sqrt_lower, sqrt_upper);
+
+    // NOTE: `L = x * sqrt(p) * sqrt(pb) / (sqrt(pb) - sqrt(p))`.
+    let liquidity0 = do_some! {
+        Decimal256::from_ratio(amount0, 1u128)
+            .checked_mul(sqrt_price).ok()?
+            .checked_mul(sqrt_upper).ok()?
+            .checked_div(sqrt_upper.checked_sub(sqrt_price).ok()?).ok()?
+    };
+
+    // NOTE: `L = y / (sqrt(p) - sqrt(pa))`.
+    let liquidity1 = do_some! {
+        Decimal256::from_ratio(amount1, 1u128)
+            .checked_div(sqrt_price.checked_sub(sqrt_lower).ok()?).ok()?
+    };
+
+    match (liquidity0, liquidity1) {
+        (Some(l0), Some(l1)) => std::cmp::min(l0, l1),
+        (Some(l), None) | (None, Some(l)) => l,
+        (None, None) => Decimal256::MAX
+    }
+}
+
+/// Amounts held at `price` by a position in `[lower_tick, upper_tick]`
+/// with `liquidity`. Ie, the inverse of [`liquidity_for_amounts`].
+pub fn amounts_for_liquidity(
+    price: &Decimal,
+    lower_tick: i32,
+    upper_tick: i32,
+    liquidity: Decimal256
+) -> (Uint128, Uint128) {
+    let sqrt_price = Decimal256::from(*price).sqrt();
+    let sqrt_lower = price_function(lower_tick).sqrt();
+    let sqrt_upper = price_function(upper_tick).sqrt();
+    let sqrt_price = sqrt_price.clamp(sqrt_lower, sqrt_upper);
+
+    // NOTE: `x = L * (sqrt(pb) - sqrt(p)) / (sqrt(p) * sqrt(pb))`.
+    let amount0 = do_some! {
+        liquidity
+            .checked_mul(sqrt_upper.checked_sub(sqrt_price).ok()?).ok()?
+            .checked_div(sqrt_price.checked_mul(sqrt_upper).ok()?).ok()?
+            .to_uint_floor()
+            .try_into().ok()?
+    };
+
+    // NOTE: `y = L * (sqrt(p) - sqrt(pa))`.
+    let amount1 = do_some! {
+        liquidity
+            .checked_mul(sqrt_price.checked_sub(sqrt_lower).ok()?).ok()?
+            .to_uint_floor()
+            .try_into().ok()?
+    };
+
+    (amount0.unwrap_or(Uint128::MAX), amount1.unwrap_or(Uint128::MAX))
+}
+
 /// # Arguments
 ///
 /// * `k` - Price factor for the base range position.
 /// * `w` - Weight for the full range position.
 /// * `x` - Amount of token0 to be used for the full range position
-///         and the base one. Ie, the balanced amount of token0 (`y = p*x`).
+///   and the base one. Ie, the balanced amount of token0 (`y = p*x`).
 ///
 /// # Returns
 ///